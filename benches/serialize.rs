@@ -0,0 +1,52 @@
+use clvmr::allocator::Allocator;
+use clvmr::serde::{
+    node_from_bytes, node_to_bytes, node_to_bytes_backrefs, node_to_bytes_backrefs_max,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::include_bytes;
+use std::time::Instant;
+
+fn serialize_benchmark(c: &mut Criterion) {
+    let block = include_bytes!("block_af9c3d98.bin");
+    let block_2000 = hex::decode(include_str!("../benchmark/block-2000.hex").trim())
+        .expect("invalid hex in block-2000.hex");
+
+    let mut group = c.benchmark_group("serialize");
+
+    for (name, bl) in [
+        ("block_af9c3d98", block as &[u8]),
+        ("block-2000", &block_2000),
+    ] {
+        let mut a = Allocator::new();
+        let node = node_from_bytes(&mut a, bl).expect("failed to parse input file");
+
+        group.bench_function(format!("node_to_bytes {name}"), |b| {
+            b.iter(|| {
+                let start = Instant::now();
+                node_to_bytes(&a, node).expect("node_to_bytes");
+                start.elapsed()
+            })
+        });
+
+        group.bench_function(format!("node_to_bytes_backrefs {name}"), |b| {
+            b.iter(|| {
+                let start = Instant::now();
+                node_to_bytes_backrefs(&a, node).expect("node_to_bytes_backrefs");
+                start.elapsed()
+            })
+        });
+
+        group.bench_function(format!("node_to_bytes_backrefs_max {name}"), |b| {
+            b.iter(|| {
+                let start = Instant::now();
+                node_to_bytes_backrefs_max(&a, node).expect("node_to_bytes_backrefs_max");
+                start.elapsed()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(serialize, serialize_benchmark);
+criterion_main!(serialize);
@@ -0,0 +1,63 @@
+use clvmr::allocator::Allocator;
+use clvmr::serde::{node_from_bytes, tree_hash_from_stream};
+use clvmr::tree_hash;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::include_bytes;
+use std::time::Instant;
+
+fn tree_hash_benchmark(c: &mut Criterion) {
+    let block = include_bytes!("block_af9c3d98.bin");
+
+    let mut group = c.benchmark_group("tree-hash");
+
+    group.bench_function("tree_hash_from_stream", |b| {
+        b.iter(|| {
+            let mut cur = std::io::Cursor::new(block as &[u8]);
+            let start = Instant::now();
+            tree_hash_from_stream(&mut cur).expect("tree_hash_from_stream");
+            start.elapsed()
+        })
+    });
+
+    let mut a = Allocator::new();
+    let node = node_from_bytes(&mut a, block).expect("failed to parse input file");
+
+    group.bench_function("tree_hash", |b| {
+        b.iter(|| {
+            let start = Instant::now();
+            tree_hash(&a, node);
+            start.elapsed()
+        })
+    });
+
+    // include the time to parse the block, to compare against hashing the
+    // serialized form directly
+    let mut a = Allocator::new();
+    let iter_checkpoint = a.checkpoint();
+    group.bench_function("node_from_bytes + tree_hash", |b| {
+        b.iter(|| {
+            a.restore_checkpoint(&iter_checkpoint);
+            let start = Instant::now();
+            let node = node_from_bytes(&mut a, block).expect("node_from_bytes");
+            tree_hash(&a, node);
+            start.elapsed()
+        })
+    });
+
+    // the first call populates the cache, subsequent calls hit it
+    let mut a = Allocator::new();
+    a.enable_tree_hash_cache();
+    let node = node_from_bytes(&mut a, block).expect("failed to parse input file");
+    group.bench_function("tree_hash-cached", |b| {
+        b.iter(|| {
+            let start = Instant::now();
+            tree_hash(&a, node);
+            start.elapsed()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(tree_hash_group, tree_hash_benchmark);
+criterion_main!(tree_hash_group);
@@ -0,0 +1,183 @@
+//! Derive macros for the `ToClvm` and `FromClvm` traits in `clvmr`.
+//!
+//! A struct must specify how it's represented in clvm, with one of:
+//!
+//! * `#[clvm(list)]` the fields form a proper list: `(field1 field2 ...)`
+//! * `#[clvm(curry)]` the fields form curried arguments, as passed to a
+//!   curried program: `(c (q . field1) (c (q . field2) ... 1))`
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Fields, GenericParam, Generics, Index,
+    Member,
+};
+
+enum Repr {
+    List,
+    Curry,
+}
+
+fn parse_repr(ast: &DeriveInput) -> syn::Result<Repr> {
+    let mut repr = None;
+    for attr in &ast.attrs {
+        if !attr.path().is_ident("clvm") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            let r = if meta.path.is_ident("list") {
+                Repr::List
+            } else if meta.path.is_ident("curry") {
+                Repr::Curry
+            } else {
+                return Err(meta.error("expected `list` or `curry`"));
+            };
+            if repr.replace(r).is_some() {
+                return Err(meta.error("the clvm representation can only be specified once"));
+            }
+            Ok(())
+        })?;
+    }
+    repr.ok_or_else(|| {
+        syn::Error::new_spanned(
+            &ast.ident,
+            "missing clvm representation, add #[clvm(list)] or #[clvm(curry)]",
+        )
+    })
+}
+
+struct Struct {
+    repr: Repr,
+    members: Vec<Member>,
+    fields: Fields,
+}
+
+fn parse_struct(ast: &DeriveInput) -> syn::Result<Struct> {
+    let Data::Struct(data) = &ast.data else {
+        return Err(syn::Error::new_spanned(
+            &ast.ident,
+            "ToClvm and FromClvm can only be derived for structs",
+        ));
+    };
+    let repr = parse_repr(ast)?;
+    let members = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(idx)),
+        })
+        .collect();
+    Ok(Struct {
+        repr,
+        members,
+        fields: data.fields.clone(),
+    })
+}
+
+// add `bound` to every type parameter
+fn add_bounds(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(type_param) = param {
+            type_param.bounds.push(parse_quote!(#bound));
+        }
+    }
+    generics
+}
+
+#[proc_macro_derive(ToClvm, attributes(clvm))]
+pub fn to_clvm(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    match impl_to_clvm(&ast) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn impl_to_clvm(ast: &DeriveInput) -> syn::Result<TokenStream2> {
+    let s = parse_struct(ast)?;
+    let name = &ast.ident;
+    let generics = add_bounds(&ast.generics, quote!(::clvmr::to_clvm::ToClvm));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // the list is built back to front
+    let members = s.members.iter().rev();
+    let (terminator, cons) = match s.repr {
+        Repr::List => (quote!(a.nil()), quote!(a.new_pair(value, node)?)),
+        Repr::Curry => (
+            quote!(a.one()),
+            quote!(::clvmr::curry::new_curried_arg(a, value, node)?),
+        ),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::clvmr::to_clvm::ToClvm for #name #ty_generics #where_clause {
+            fn to_clvm(
+                &self,
+                a: &mut ::clvmr::allocator::Allocator,
+            ) -> ::std::result::Result<::clvmr::allocator::NodePtr, ::clvmr::reduction::EvalErr> {
+                let node = #terminator;
+                #(
+                    let value = ::clvmr::to_clvm::ToClvm::to_clvm(&self.#members, a)?;
+                    let node = #cons;
+                )*
+                Ok(node)
+            }
+        }
+    })
+}
+
+#[proc_macro_derive(FromClvm, attributes(clvm))]
+pub fn from_clvm(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    match impl_from_clvm(&ast) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn impl_from_clvm(ast: &DeriveInput) -> syn::Result<TokenStream2> {
+    let s = parse_struct(ast)?;
+    let name = &ast.ident;
+    let generics = add_bounds(&ast.generics, quote!(::clvmr::from_clvm::FromClvm));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let vars: Vec<_> = (0..s.members.len())
+        .map(|idx| format_ident!("field{}", idx))
+        .collect();
+    let members = &s.members;
+    let construct = match s.fields {
+        Fields::Named(_) => quote!(Self { #(#members: #vars,)* }),
+        Fields::Unnamed(_) => quote!(Self(#(#vars,)*)),
+        Fields::Unit => quote!(Self),
+    };
+    let (next, end) = match s.repr {
+        Repr::List => (
+            quote!(::clvmr::from_clvm::expect_pair(a, node)?),
+            quote!(::clvmr::from_clvm::expect_nil(a, node)?),
+        ),
+        Repr::Curry => (
+            quote!(::clvmr::from_clvm::expect_curried_arg(a, node)?),
+            quote!(::clvmr::from_clvm::expect_curried_args_end(a, node)?),
+        ),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::clvmr::from_clvm::FromClvm for #name #ty_generics #where_clause {
+            fn from_clvm(
+                a: &::clvmr::allocator::Allocator,
+                node: ::clvmr::allocator::NodePtr,
+            ) -> ::std::result::Result<Self, ::clvmr::from_clvm::FromClvmError> {
+                #(
+                    let (value, node) = #next;
+                    let #vars = ::clvmr::from_clvm::FromClvm::from_clvm(a, value)?;
+                )*
+                #end;
+                Ok(#construct)
+            }
+        }
+    })
+}
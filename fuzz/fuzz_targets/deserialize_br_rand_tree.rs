@@ -4,7 +4,7 @@ mod fuzzing_utils;
 
 use clvmr::allocator::Allocator;
 use clvmr::serde::node_from_bytes_backrefs;
-use clvmr::serde::node_to_bytes_backrefs;
+use clvmr::serde::{node_to_bytes, node_to_bytes_backrefs, node_to_bytes_backrefs_max};
 use libfuzzer_sys::fuzz_target;
 
 fn do_fuzz(data: &[u8], short_atoms: bool) {
@@ -13,15 +13,27 @@ fn do_fuzz(data: &[u8], short_atoms: bool) {
 
     let program = fuzzing_utils::make_tree(&mut allocator, &mut cursor, short_atoms);
 
+    let plain = node_to_bytes(&allocator, program).unwrap();
     let b1 = node_to_bytes_backrefs(&allocator, program).unwrap();
+    let m1 = node_to_bytes_backrefs_max(&allocator, program).unwrap();
 
     let mut allocator = Allocator::new();
     let program = node_from_bytes_backrefs(&mut allocator, &b1).unwrap();
+    assert_eq!(node_to_bytes(&allocator, program).unwrap(), plain);
 
     let b2 = node_to_bytes_backrefs(&allocator, program).unwrap();
     if b1 != b2 {
         panic!("b1 and b2 do not match");
     }
+
+    let mut allocator = Allocator::new();
+    let program = node_from_bytes_backrefs(&mut allocator, &m1).unwrap();
+    assert_eq!(node_to_bytes(&allocator, program).unwrap(), plain);
+
+    let m2 = node_to_bytes_backrefs_max(&allocator, program).unwrap();
+    if m1 != m2 {
+        panic!("m1 and m2 do not match");
+    }
 }
 
 fuzz_target!(|data: &[u8]| {
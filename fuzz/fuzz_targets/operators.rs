@@ -14,7 +14,7 @@ use clvmr::more_ops::{
     op_logand, op_logior, op_lognot, op_logxor, op_lsh, op_mod, op_modpow, op_multiply, op_not,
     op_point_add, op_pubkey_for_exp, op_sha256, op_strlen, op_substr, op_subtract,
 };
-use clvmr::reduction::{EvalErr, Response};
+use clvmr::reduction::{EvalErr, EvalErrKind, Response};
 use clvmr::secp_ops::{op_secp256k1_verify, op_secp256r1_verify};
 use clvmr::serde::node_from_bytes;
 
@@ -86,8 +86,8 @@ fuzz_target!(|data: &[u8]| {
         for max_cost in [11000000, 1100000, 110000, 10, 1, 0] {
             allocator.restore_checkpoint(&allocator_checkpoint);
             match op(&mut allocator, args, max_cost) {
-                Err(EvalErr(n, msg)) => {
-                    assert!(!msg.contains("internal error"));
+                Err(EvalErr(n, kind)) => {
+                    assert!(!matches!(kind, EvalErrKind::InternalError(_)));
                     // make sure n is a valid node in the allocator
                     allocator.sexp(n);
                 }
@@ -1,17 +1,53 @@
 use crate::err_utils::err;
+use crate::iter::{ListIter, PathIter, PostOrderIter, PreOrderIter};
 use crate::number::{node_from_number, number_from_u8, Number};
-use crate::reduction::EvalErr;
+use crate::reduction::{EvalErr, EvalErrKind};
+use crate::tree_hash::{tree_hash_cached, tree_hash_uncached, TreeHash, TreeHashCache};
 use chia_bls::{G1Element, G2Element};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
 use std::hash::Hash;
 use std::hash::Hasher;
-
-const MAX_NUM_ATOMS: usize = 62500000;
-const MAX_NUM_PAIRS: usize = 62500000;
+use std::hash::RandomState;
+use std::sync::Mutex;
+
+/// The default limit on the number of atoms and pairs an `Allocator` can hold
+pub const MAX_NUM_ATOMS: usize = 62500000;
+pub const MAX_NUM_PAIRS: usize = 62500000;
+
+// By default, NodePtr is 32 bits and offsets into the heap are 32 bits, which
+// limits the heap to 4 GiB and the number of atoms and pairs to 2^26 each.
+// This is what consensus uses. The "wide-heap" feature makes NodePtr and heap
+// offsets 64 bits, with 40 bits of index, for tools that need to build larger
+// trees. It only raises MAX_HEAP_LIMIT and MAX_NODE_COUNT, the allocator still
+// has to be created with new_with_limits() to use them. Small atoms are 26 bits
+// in both cases.
+#[cfg(not(feature = "wide-heap"))]
+type RawNodePtr = u32;
+#[cfg(not(feature = "wide-heap"))]
+type HeapIdx = u32;
+#[cfg(not(feature = "wide-heap"))]
 const NODE_PTR_IDX_BITS: u32 = 26;
-const NODE_PTR_IDX_MASK: u32 = (1 << NODE_PTR_IDX_BITS) - 1;
+
+#[cfg(feature = "wide-heap")]
+type RawNodePtr = u64;
+#[cfg(feature = "wide-heap")]
+type HeapIdx = u64;
+#[cfg(feature = "wide-heap")]
+const NODE_PTR_IDX_BITS: u32 = 40;
+
+const NODE_PTR_IDX_MASK: RawNodePtr = (1 << NODE_PTR_IDX_BITS) - 1;
+const SMALL_ATOM_MAX: u32 = (1 << 26) - 1;
+
+/// The largest heap limit that can be passed to `Allocator::new_with_limits()`
+pub const MAX_HEAP_LIMIT: usize = HeapIdx::MAX as usize;
+/// The largest limit on the number of atoms (or pairs) that can be passed to
+/// `Allocator::new_with_limits()`
+pub const MAX_NODE_COUNT: usize = NODE_PTR_IDX_MASK as usize + 1;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct NodePtr(u32);
+pub struct NodePtr(RawNodePtr);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum ObjectType {
@@ -29,7 +65,7 @@ impl NodePtr {
 
     const fn new(object_type: ObjectType, index: usize) -> Self {
         debug_assert!(index <= NODE_PTR_IDX_MASK as usize);
-        NodePtr(((object_type as u32) << NODE_PTR_IDX_BITS) | (index as u32))
+        NodePtr(((object_type as RawNodePtr) << NODE_PTR_IDX_BITS) | (index as RawNodePtr))
     }
 
     pub fn is_atom(self) -> bool {
@@ -52,8 +88,14 @@ impl NodePtr {
         }
     }
 
-    fn index(self) -> u32 {
-        self.0 & NODE_PTR_IDX_MASK
+    fn index(self) -> usize {
+        (self.0 & NODE_PTR_IDX_MASK) as usize
+    }
+
+    // the value of a SmallAtom
+    fn small_atom(self) -> u32 {
+        debug_assert!(self.object_type() == ObjectType::SmallAtom);
+        self.index() as u32
     }
 }
 
@@ -71,8 +113,8 @@ pub enum SExp {
 
 #[derive(Clone, Copy, Debug)]
 struct AtomBuf {
-    start: u32,
-    end: u32,
+    start: HeapIdx,
+    end: HeapIdx,
 }
 
 impl AtomBuf {
@@ -95,6 +137,85 @@ pub struct Checkpoint {
     pairs: usize,
     atoms: usize,
     small_atoms: usize,
+    tree_hashes: usize,
+}
+
+// the number of allocations avoided by interning, see
+// Allocator::enable_interning()
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InternSavings {
+    // the number of atoms that were found to already exist
+    pub atoms: usize,
+    // the number of pairs that were found to already exist
+    pub pairs: usize,
+    // the number of heap bytes that did not have to be allocated
+    pub bytes: usize,
+}
+
+#[derive(Debug, Default)]
+struct Interner {
+    hasher: RandomState,
+    // maps the hash of an atom's contents to the atoms with that hash
+    atoms: HashMap<u64, Vec<NodePtr>>,
+    pairs: HashMap<(NodePtr, NodePtr), NodePtr>,
+    // the keys added to the maps above, in the order they were added. These
+    // let truncate() undo the most recent additions without scanning the
+    // maps
+    atom_log: Vec<(u64, NodePtr)>,
+    pair_log: Vec<(NodePtr, NodePtr)>,
+    savings: InternSavings,
+}
+
+impl Interner {
+    fn find_atom(&self, u8_vec: &[u8], atom_vec: &[AtomBuf], buf: &[u8]) -> Option<NodePtr> {
+        let candidates = self.atoms.get(&self.hasher.hash_one(buf))?;
+        let node = candidates.iter().find(|node| {
+            let atom = atom_vec[node.index()];
+            &u8_vec[atom.start as usize..atom.end as usize] == buf
+        })?;
+        Some(*node)
+    }
+
+    fn add_atom(&mut self, u8_vec: &[u8], atom: AtomBuf, idx: usize) {
+        let hash = self
+            .hasher
+            .hash_one(&u8_vec[atom.start as usize..atom.end as usize]);
+        let node = NodePtr::new(ObjectType::Bytes, idx);
+        self.atoms.entry(hash).or_default().push(node);
+        self.atom_log.push((hash, node));
+    }
+
+    fn add_pair(&mut self, first: NodePtr, rest: NodePtr, node: NodePtr) {
+        if let Entry::Vacant(e) = self.pairs.entry((first, rest)) {
+            e.insert(node);
+            self.pair_log.push((first, rest));
+        }
+    }
+
+    // forget all atoms and pairs that no longer exist, after restoring a
+    // checkpoint. Nodes are added in the order they're allocated, so the ones
+    // to remove are the most recently added ones
+    fn truncate(&mut self, atoms: usize, pairs: usize) {
+        while let Some((hash, node)) = self.atom_log.last().copied() {
+            if node.index() < atoms {
+                break;
+            }
+            self.atom_log.pop();
+            let nodes = self.atoms.get_mut(&hash).expect("interned atom");
+            debug_assert_eq!(nodes.last(), Some(&node));
+            nodes.pop();
+            if nodes.is_empty() {
+                self.atoms.remove(&hash);
+            }
+        }
+        while let Some(key) = self.pair_log.last().copied() {
+            if self.pairs[&key].index() < pairs {
+                break;
+            }
+            self.pair_log.pop();
+            self.pairs.remove(&key);
+        }
+    }
 }
 
 pub enum NodeVisitor<'a> {
@@ -157,6 +278,17 @@ pub struct Allocator {
     // the number of small atoms we've allocated. We keep track of these to ensure the limit on the
     // number of atoms is identical to what it was before the small-atom optimization
     small_atoms: usize,
+
+    // the max number of atoms (including small atoms) and pairs we may
+    // allocate
+    max_atoms: usize,
+    max_pairs: usize,
+
+    // when set, identical atoms and pairs are only allocated once
+    interner: Option<Interner>,
+
+    // when set, tree hashes computed by tree_hash() are remembered
+    tree_hash_cache: Option<Mutex<TreeHashCache>>,
 }
 
 impl Default for Allocator {
@@ -207,13 +339,29 @@ pub fn len_for_value(val: u32) -> usize {
 }
 
 impl Allocator {
+    /// creates an allocator with the consensus limits: a 4 GiB heap and
+    /// `MAX_NUM_ATOMS` and `MAX_NUM_PAIRS`. This is the case even with the
+    /// "wide-heap" feature, use `new_with_limits()` to go beyond them
     pub fn new() -> Self {
         Self::new_limited(u32::MAX as usize)
     }
 
+    /// like `new()`, but with a custom heap limit
     pub fn new_limited(heap_limit: usize) -> Self {
-        // we have a maximum of 4 GiB heap, because pointers are 32 bit unsigned
-        assert!(heap_limit <= u32::MAX as usize);
+        Self::new_with_limits(heap_limit, MAX_NUM_ATOMS, MAX_NUM_PAIRS)
+    }
+
+    /// `heap_limit` must not exceed `MAX_HEAP_LIMIT` and `max_atoms` and
+    /// `max_pairs` must not exceed `MAX_NODE_COUNT`. With the "wide-heap"
+    /// feature, those are large enough for heaps beyond 4 GiB and more than
+    /// 2^26 atoms and pairs
+    pub fn new_with_limits(heap_limit: usize, max_atoms: usize, max_pairs: usize) -> Self {
+        // unless the "wide-heap" feature is enabled, we have a maximum of 4 GiB
+        // heap, because offsets into it are 32 bit unsigned
+        assert!(HeapIdx::try_from(heap_limit).is_ok());
+        // atoms and pairs are referred to by index, which is limited in size
+        assert!(max_atoms <= MAX_NODE_COUNT);
+        assert!(max_pairs <= MAX_NODE_COUNT);
 
         let mut r = Self {
             u8_vec: Vec::new(),
@@ -224,6 +372,10 @@ impl Allocator {
             // initialize this to 2 to behave as if we had allocated atoms for
             // nil() and one(), like we used to
             small_atoms: 2,
+            max_atoms,
+            max_pairs,
+            interner: None,
+            tree_hash_cache: None,
         };
         r.u8_vec.reserve(1024 * 1024);
         r.atom_vec.reserve(256);
@@ -231,6 +383,67 @@ impl Allocator {
         r
     }
 
+    // create an allocator in interning mode. See enable_interning()
+    pub fn new_interning() -> Self {
+        let mut r = Self::new();
+        r.enable_interning();
+        r
+    }
+
+    // in interning mode, allocating an atom or pair that's identical to an
+    // existing one returns the existing NodePtr instead of allocating new
+    // storage. Pairs are identical if they have the same first and rest
+    // pointers. Atoms that fit in a small atom are always returned as one.
+    // Since this affects the heap and atom/pair limits, interning must not be
+    // enabled when running in consensus mode. Atoms and pairs allocated before
+    // calling this function are also considered for deduplication.
+    pub fn enable_interning(&mut self) {
+        if self.interner.is_some() {
+            return;
+        }
+        let mut interner = Interner::default();
+        for (idx, atom) in self.atom_vec.iter().enumerate() {
+            interner.add_atom(&self.u8_vec, *atom, idx);
+        }
+        for (idx, pair) in self.pair_vec.iter().enumerate() {
+            interner.add_pair(pair.first, pair.rest, NodePtr::new(ObjectType::Pair, idx));
+        }
+        self.interner = Some(interner);
+    }
+
+    pub fn is_interning(&self) -> bool {
+        self.interner.is_some()
+    }
+
+    // returns the number of atoms, pairs and heap bytes interning has saved
+    // so far, or None if interning is not enabled
+    pub fn intern_savings(&self) -> Option<InternSavings> {
+        self.interner.as_ref().map(|i| i.savings)
+    }
+
+    // remember the tree hash of every node hashed by tree_hash(), so repeated
+    // calls on overlapping trees only need to hash the new parts
+    pub fn enable_tree_hash_cache(&mut self) {
+        if self.tree_hash_cache.is_none() {
+            self.tree_hash_cache = Some(Mutex::new(TreeHashCache::default()));
+        }
+    }
+
+    pub fn has_tree_hash_cache(&self) -> bool {
+        self.tree_hash_cache.is_some()
+    }
+
+    // compute the sha256 tree hash of node. If the tree hash cache is enabled,
+    // the hashes of all nodes in the tree are stored in it, and cached hashes
+    // are reused. Calls from multiple threads (on a FrozenAllocator) sharing a
+    // cache are serialized.
+    pub fn tree_hash(&self, node: NodePtr) -> TreeHash {
+        match &self.tree_hash_cache {
+            Some(cache) => tree_hash_cached(self, node, &mut cache.lock().unwrap()),
+            None => tree_hash_uncached(self, node),
+        }
+    }
+
     // create a checkpoint for the current state of the allocator. This can be
     // used to go back to an earlier allocator state by passing the Checkpoint
     // to restore_checkpoint().
@@ -240,6 +453,10 @@ impl Allocator {
             pairs: self.pair_vec.len(),
             atoms: self.atom_vec.len(),
             small_atoms: self.small_atoms,
+            tree_hashes: self
+                .tree_hash_cache
+                .as_ref()
+                .map_or(0, |cache| cache.lock().unwrap().len()),
         }
     }
 
@@ -255,28 +472,75 @@ impl Allocator {
         self.pair_vec.truncate(cp.pairs);
         self.atom_vec.truncate(cp.atoms);
         self.small_atoms = cp.small_atoms;
+        if let Some(interner) = &mut self.interner {
+            interner.truncate(cp.atoms, cp.pairs);
+        }
+        if let Some(cache) = &mut self.tree_hash_cache {
+            cache
+                .get_mut()
+                .unwrap()
+                .truncate(cp.tree_hashes, |node| match node.object_type() {
+                    ObjectType::Pair => node.index() < cp.pairs,
+                    ObjectType::Bytes => node.index() < cp.atoms,
+                    ObjectType::SmallAtom => true,
+                });
+        }
     }
 
     pub fn new_atom(&mut self, v: &[u8]) -> Result<NodePtr, EvalErr> {
-        let start = self.u8_vec.len() as u32;
+        // an interned atom that already exists doesn't allocate anything, so
+        // it can't exceed any limit
+        let small_atom = fits_in_small_atom(v);
+        if let (None, Some(interner)) = (small_atom, &mut self.interner) {
+            if let Some(node) = interner.find_atom(&self.u8_vec, &self.atom_vec, v) {
+                interner.savings.atoms += 1;
+                interner.savings.bytes += v.len();
+                return Ok(node);
+            }
+        }
+        let start = self.u8_vec.len() as HeapIdx;
         if (self.heap_limit - start as usize) < v.len() {
-            return err(self.nil(), "out of memory");
+            return err(self.nil(), EvalErrKind::OutOfMemory);
         }
         let idx = self.atom_vec.len();
         self.check_atom_limit()?;
-        if let Some(ret) = fits_in_small_atom(v) {
+        if let Some(ret) = small_atom {
             self.small_atoms += 1;
             Ok(NodePtr::new(ObjectType::SmallAtom, ret as usize))
         } else {
             self.u8_vec.extend_from_slice(v);
-            let end = self.u8_vec.len() as u32;
-            self.atom_vec.push(AtomBuf { start, end });
-            Ok(NodePtr::new(ObjectType::Bytes, idx))
+            let end = self.u8_vec.len() as HeapIdx;
+            Ok(self.push_atom(AtomBuf { start, end }, idx))
+        }
+    }
+
+    fn push_atom(&mut self, atom: AtomBuf, idx: usize) -> NodePtr {
+        debug_assert_eq!(idx, self.atom_vec.len());
+        self.atom_vec.push(atom);
+        if let Some(interner) = &mut self.interner {
+            interner.add_atom(&self.u8_vec, atom, idx);
         }
+        NodePtr::new(ObjectType::Bytes, idx)
+    }
+
+    // in interning mode, look for an existing atom with the same contents as
+    // u8_vec[start..end]. This is used for atoms whose contents are already
+    // on the heap.
+    fn find_interned(&mut self, start: usize, end: usize) -> Option<NodePtr> {
+        let interner = self.interner.as_mut()?;
+        let buf = &self.u8_vec[start..end];
+        if let Some(val) = fits_in_small_atom(buf) {
+            self.small_atoms += 1;
+            interner.savings.atoms += 1;
+            return Some(NodePtr::new(ObjectType::SmallAtom, val as usize));
+        }
+        let node = interner.find_atom(&self.u8_vec, &self.atom_vec, buf)?;
+        interner.savings.atoms += 1;
+        Some(node)
     }
 
     pub fn new_small_number(&mut self, v: u32) -> Result<NodePtr, EvalErr> {
-        debug_assert!(v <= NODE_PTR_IDX_MASK);
+        debug_assert!(v <= SMALL_ATOM_MAX);
         self.check_atom_limit()?;
         self.small_atoms += 1;
         Ok(NodePtr::new(ObjectType::SmallAtom, v as usize))
@@ -285,7 +549,7 @@ impl Allocator {
     pub fn new_number(&mut self, v: Number) -> Result<NodePtr, EvalErr> {
         use num_traits::ToPrimitive;
         if let Some(val) = v.to_u32() {
-            if val <= NODE_PTR_IDX_MASK {
+            if val <= SMALL_ATOM_MAX {
                 return self.new_small_number(val);
             }
         }
@@ -302,48 +566,62 @@ impl Allocator {
 
     pub fn new_pair(&mut self, first: NodePtr, rest: NodePtr) -> Result<NodePtr, EvalErr> {
         let idx = self.pair_vec.len();
-        if idx == MAX_NUM_PAIRS {
-            return err(self.nil(), "too many pairs");
+        if let Some(interner) = &mut self.interner {
+            if let Some(node) = interner.pairs.get(&(first, rest)) {
+                interner.savings.pairs += 1;
+                return Ok(*node);
+            }
+        }
+        if idx >= self.max_pairs {
+            return err(self.nil(), EvalErrKind::TooManyPairs);
         }
         self.pair_vec.push(IntPair { first, rest });
-        Ok(NodePtr::new(ObjectType::Pair, idx))
+        let node = NodePtr::new(ObjectType::Pair, idx);
+        if let Some(interner) = &mut self.interner {
+            interner.add_pair(first, rest, node);
+        }
+        Ok(node)
     }
 
     pub fn new_substr(&mut self, node: NodePtr, start: u32, end: u32) -> Result<NodePtr, EvalErr> {
         self.check_atom_limit()?;
 
-        fn bounds_check(node: NodePtr, start: u32, end: u32, len: u32) -> Result<(), EvalErr> {
+        fn bounds_check(node: NodePtr, start: u32, end: u32, len: usize) -> Result<(), EvalErr> {
+            let (start, end) = (start as usize, end as usize);
             if start > len {
-                return err(node, "substr start out of bounds");
+                return err(node, EvalErrKind::SubstrStartOutOfBounds);
             }
             if end > len {
-                return err(node, "substr end out of bounds");
+                return err(node, EvalErrKind::SubstrEndOutOfBounds);
             }
             if end < start {
-                return err(node, "substr invalid bounds");
+                return err(node, EvalErrKind::SubstrInvalidBounds);
             }
             Ok(())
         }
 
         match node.object_type() {
-            ObjectType::Pair => err(node, "(internal error) substr expected atom, got pair"),
+            ObjectType::Pair => err(
+                node,
+                EvalErrKind::InternalError("substr expected atom, got pair"),
+            ),
             ObjectType::Bytes => {
-                let atom = self.atom_vec[node.index() as usize];
-                let atom_len = atom.end - atom.start;
-                bounds_check(node, start, end, atom_len)?;
+                let atom = self.atom_vec[node.index()];
+                bounds_check(node, start, end, atom.len())?;
+                let start = atom.start + start as HeapIdx;
+                let end = atom.start + end as HeapIdx;
+                if let Some(node) = self.find_interned(start as usize, end as usize) {
+                    return Ok(node);
+                }
                 let idx = self.atom_vec.len();
-                self.atom_vec.push(AtomBuf {
-                    start: atom.start + start,
-                    end: atom.start + end,
-                });
-                Ok(NodePtr::new(ObjectType::Bytes, idx))
+                Ok(self.push_atom(AtomBuf { start, end }, idx))
             }
             ObjectType::SmallAtom => {
-                let val = node.index();
-                let len = len_for_value(val) as u32;
+                let val = node.small_atom();
+                let len = len_for_value(val);
                 bounds_check(node, start, end, len)?;
                 let buf: [u8; 4] = val.to_be_bytes();
-                let buf = &buf[4 - len as usize..];
+                let buf = &buf[4 - len..];
                 let substr = &buf[start as usize..end as usize];
                 if let Some(new_val) = fits_in_small_atom(substr) {
                     self.small_atoms += 1;
@@ -352,12 +630,17 @@ impl Allocator {
                     let start = self.u8_vec.len();
                     let end = start + substr.len();
                     self.u8_vec.extend_from_slice(substr);
+                    if let Some(node) = self.find_interned(start, end) {
+                        self.u8_vec.truncate(start);
+                        self.interner.as_mut().unwrap().savings.bytes += end - start;
+                        return Ok(node);
+                    }
                     let idx = self.atom_vec.len();
-                    self.atom_vec.push(AtomBuf {
-                        start: start as u32,
-                        end: end as u32,
-                    });
-                    Ok(NodePtr::new(ObjectType::Bytes, idx))
+                    let atom = AtomBuf {
+                        start: start as HeapIdx,
+                        end: end as HeapIdx,
+                    };
+                    Ok(self.push_atom(atom, idx))
                 }
             }
         }
@@ -367,7 +650,7 @@ impl Allocator {
         self.check_atom_limit()?;
         let start = self.u8_vec.len();
         if self.heap_limit - start < new_size {
-            return err(self.nil(), "out of memory");
+            return err(self.nil(), EvalErrKind::OutOfMemory);
         }
         // TODO: maybe it would make sense to have a special case where
         // nodes.len() == 1. We can just return the same node
@@ -379,25 +662,31 @@ impl Allocator {
             match node.object_type() {
                 ObjectType::Pair => {
                     self.u8_vec.truncate(start);
-                    return err(*node, "(internal error) concat expected atom, got pair");
+                    return err(
+                        *node,
+                        EvalErrKind::InternalError("concat expected atom, got pair"),
+                    );
                 }
                 ObjectType::Bytes => {
-                    let term = self.atom_vec[node.index() as usize];
+                    let term = self.atom_vec[node.index()];
                     if counter + term.len() > new_size {
                         self.u8_vec.truncate(start);
-                        return err(*node, "(internal error) concat passed invalid new_size");
+                        return err(
+                            *node,
+                            EvalErrKind::InternalError("concat passed invalid new_size"),
+                        );
                     }
                     self.u8_vec
                         .extend_from_within(term.start as usize..term.end as usize);
                     counter += term.len();
                 }
                 ObjectType::SmallAtom => {
-                    let val = node.index();
-                    let len = len_for_value(val) as u32;
+                    let val = node.small_atom();
+                    let len = len_for_value(val);
                     let buf: [u8; 4] = val.to_be_bytes();
-                    let buf = &buf[4 - len as usize..];
+                    let buf = &buf[4 - len..];
                     self.u8_vec.extend_from_slice(buf);
-                    counter += len as usize;
+                    counter += len;
                 }
             }
         }
@@ -405,16 +694,21 @@ impl Allocator {
             self.u8_vec.truncate(start);
             return err(
                 self.nil(),
-                "(internal error) concat passed invalid new_size",
+                EvalErrKind::InternalError("concat passed invalid new_size"),
             );
         }
-        let end = self.u8_vec.len() as u32;
+        let end = self.u8_vec.len();
+        if let Some(node) = self.find_interned(start, end) {
+            self.u8_vec.truncate(start);
+            self.interner.as_mut().unwrap().savings.bytes += new_size;
+            return Ok(node);
+        }
         let idx = self.atom_vec.len();
-        self.atom_vec.push(AtomBuf {
-            start: (start as u32),
-            end,
-        });
-        Ok(NodePtr::new(ObjectType::Bytes, idx))
+        let atom = AtomBuf {
+            start: start as HeapIdx,
+            end: end as HeapIdx,
+        };
+        Ok(self.push_atom(atom, idx))
     }
 
     pub fn atom_eq(&self, lhs: NodePtr, rhs: NodePtr) -> bool {
@@ -426,24 +720,23 @@ impl Allocator {
                 panic!("atom_eq() called on pair");
             }
             (ObjectType::Bytes, ObjectType::Bytes) => {
-                let lhs = self.atom_vec[lhs.index() as usize];
-                let rhs = self.atom_vec[rhs.index() as usize];
+                let lhs = self.atom_vec[lhs.index()];
+                let rhs = self.atom_vec[rhs.index()];
                 self.u8_vec[lhs.start as usize..lhs.end as usize]
                     == self.u8_vec[rhs.start as usize..rhs.end as usize]
             }
-            (ObjectType::SmallAtom, ObjectType::SmallAtom) => lhs.index() == rhs.index(),
+            (ObjectType::SmallAtom, ObjectType::SmallAtom) => lhs == rhs,
             (ObjectType::SmallAtom, ObjectType::Bytes) => {
-                self.bytes_eq_int(self.atom_vec[rhs.index() as usize], lhs.index())
+                self.bytes_eq_int(self.atom_vec[rhs.index()], lhs.small_atom())
             }
             (ObjectType::Bytes, ObjectType::SmallAtom) => {
-                self.bytes_eq_int(self.atom_vec[lhs.index() as usize], rhs.index())
+                self.bytes_eq_int(self.atom_vec[lhs.index()], rhs.small_atom())
             }
         }
     }
 
     fn bytes_eq_int(&self, atom: AtomBuf, val: u32) -> bool {
-        let len = len_for_value(val) as u32;
-        if (atom.end - atom.start) != len {
+        if atom.len() != len_for_value(val) {
             return false;
         }
         if val == 0 {
@@ -467,16 +760,15 @@ impl Allocator {
     }
 
     pub fn atom(&self, node: NodePtr) -> Atom {
-        let index = node.index();
-
         match node.object_type() {
             ObjectType::Bytes => {
-                let atom = self.atom_vec[index as usize];
+                let atom = self.atom_vec[node.index()];
                 Atom::Borrowed(&self.u8_vec[atom.start as usize..atom.end as usize])
             }
             ObjectType::SmallAtom => {
-                let len = len_for_value(index);
-                let bytes = index.to_be_bytes();
+                let val = node.small_atom();
+                let len = len_for_value(val);
+                let bytes = val.to_be_bytes();
                 Atom::U32(bytes, len)
             }
             _ => panic!("expected atom, got pair"),
@@ -484,14 +776,9 @@ impl Allocator {
     }
 
     pub fn atom_len(&self, node: NodePtr) -> usize {
-        let index = node.index();
-
         match node.object_type() {
-            ObjectType::Bytes => {
-                let atom = self.atom_vec[index as usize];
-                (atom.end - atom.start) as usize
-            }
-            ObjectType::SmallAtom => len_for_value(index),
+            ObjectType::Bytes => self.atom_vec[node.index()].len(),
+            ObjectType::SmallAtom => len_for_value(node.small_atom()),
             _ => {
                 panic!("expected atom, got pair");
             }
@@ -500,9 +787,9 @@ impl Allocator {
 
     pub fn small_number(&self, node: NodePtr) -> Option<u32> {
         match node.object_type() {
-            ObjectType::SmallAtom => Some(node.index()),
+            ObjectType::SmallAtom => Some(node.small_atom()),
             ObjectType::Bytes => {
-                let atom = self.atom_vec[node.index() as usize];
+                let atom = self.atom_vec[node.index()];
                 let buf = &self.u8_vec[atom.start as usize..atom.end as usize];
                 fits_in_small_atom(buf)
             }
@@ -511,14 +798,12 @@ impl Allocator {
     }
 
     pub fn number(&self, node: NodePtr) -> Number {
-        let index = node.index();
-
         match node.object_type() {
             ObjectType::Bytes => {
-                let atom = self.atom_vec[index as usize];
+                let atom = self.atom_vec[node.index()];
                 number_from_u8(&self.u8_vec[atom.start as usize..atom.end as usize])
             }
-            ObjectType::SmallAtom => Number::from(index),
+            ObjectType::SmallAtom => Number::from(node.small_atom()),
             _ => {
                 panic!("number() calld on pair");
             }
@@ -529,60 +814,56 @@ impl Allocator {
         let idx = match node.object_type() {
             ObjectType::Bytes => node.index(),
             ObjectType::SmallAtom => {
-                return err(node, "atom is not G1 size, 48 bytes");
+                return err(node, EvalErrKind::G1WrongSize);
             }
             ObjectType::Pair => {
-                return err(node, "pair found, expected G1 point");
+                return err(node, EvalErrKind::G1ExpectedAtom);
             }
         };
-        let atom = self.atom_vec[idx as usize];
+        let atom = self.atom_vec[idx];
         if atom.end - atom.start != 48 {
-            return err(node, "atom is not G1 size, 48 bytes");
+            return err(node, EvalErrKind::G1WrongSize);
         }
 
         let array: &[u8; 48] = &self.u8_vec[atom.start as usize..atom.end as usize]
             .try_into()
             .expect("atom size is not 48 bytes");
-        G1Element::from_bytes(array)
-            .map_err(|_| EvalErr(node, "atom is not a G1 point".to_string()))
+        G1Element::from_bytes(array).map_err(|_| EvalErr(node, EvalErrKind::NotG1Point))
     }
 
     pub fn g2(&self, node: NodePtr) -> Result<G2Element, EvalErr> {
         let idx = match node.object_type() {
             ObjectType::Bytes => node.index(),
             ObjectType::SmallAtom => {
-                return err(node, "atom is not G2 size, 96 bytes");
+                return err(node, EvalErrKind::G2WrongSize);
             }
             ObjectType::Pair => {
-                return err(node, "pair found, expected G2 point");
+                return err(node, EvalErrKind::G2ExpectedAtom);
             }
         };
 
-        let atom = self.atom_vec[idx as usize];
+        let atom = self.atom_vec[idx];
         if atom.end - atom.start != 96 {
-            return err(node, "atom is not G2 size, 96 bytes");
+            return err(node, EvalErrKind::G2WrongSize);
         }
 
         let array: &[u8; 96] = &self.u8_vec[atom.start as usize..atom.end as usize]
             .try_into()
             .expect("atom size is not 96 bytes");
 
-        G2Element::from_bytes(array)
-            .map_err(|_| EvalErr(node, "atom is not a G2 point".to_string()))
+        G2Element::from_bytes(array).map_err(|_| EvalErr(node, EvalErrKind::NotG2Point))
     }
 
     pub fn node(&self, node: NodePtr) -> NodeVisitor {
-        let index = node.index();
-
         match node.object_type() {
             ObjectType::Bytes => {
-                let atom = self.atom_vec[index as usize];
+                let atom = self.atom_vec[node.index()];
                 let buf = &self.u8_vec[atom.start as usize..atom.end as usize];
                 NodeVisitor::Buffer(buf)
             }
-            ObjectType::SmallAtom => NodeVisitor::U32(index),
+            ObjectType::SmallAtom => NodeVisitor::U32(node.small_atom()),
             ObjectType::Pair => {
-                let pair = self.pair_vec[index as usize];
+                let pair = self.pair_vec[node.index()];
                 NodeVisitor::Pair(pair.first, pair.rest)
             }
         }
@@ -592,7 +873,7 @@ impl Allocator {
         match node.object_type() {
             ObjectType::Bytes | ObjectType::SmallAtom => SExp::Atom,
             ObjectType::Pair => {
-                let pair = self.pair_vec[node.index() as usize];
+                let pair = self.pair_vec[node.index()];
                 SExp::Pair(pair.first, pair.rest)
             }
         }
@@ -610,6 +891,26 @@ impl Allocator {
         }
     }
 
+    /// iterate over the items of the proper list `list`. See `ListIter`
+    pub fn list_iter(&self, list: NodePtr) -> ListIter<'_> {
+        ListIter::new(self, list)
+    }
+
+    /// visit every node in the tree, parents first. See `PreOrderIter`
+    pub fn pre_order(&self, root: NodePtr) -> PreOrderIter<'_> {
+        PreOrderIter::new(self, root)
+    }
+
+    /// visit every node in the tree, children first. See `PostOrderIter`
+    pub fn post_order(&self, root: NodePtr) -> PostOrderIter<'_> {
+        PostOrderIter::new(self, root)
+    }
+
+    /// visit every node in the tree along with its path. See `PathIter`
+    pub fn path_iter(&self, root: NodePtr) -> PathIter<'_> {
+        PathIter::new(self, root)
+    }
+
     pub fn nil(&self) -> NodePtr {
         NodePtr::new(ObjectType::SmallAtom, 0)
     }
@@ -618,10 +919,88 @@ impl Allocator {
         NodePtr::new(ObjectType::SmallAtom, 1)
     }
 
+    // copy the tree rooted at `node` in the `src` allocator into this
+    // allocator, returning the new root. Subtrees that are shared in `src` are
+    // only copied once, and atoms with identical bytes are only stored once.
+    pub fn copy_from(&mut self, src: &Allocator, node: NodePtr) -> Result<NodePtr, EvalErr> {
+        Ok(self.copy_many_from(src, &[node])?[0])
+    }
+
+    // like copy_from(), but copies several roots at once. Any structure shared
+    // between the roots is also shared in the copy.
+    pub fn copy_many_from(
+        &mut self,
+        src: &Allocator,
+        roots: &[NodePtr],
+    ) -> Result<Vec<NodePtr>, EvalErr> {
+        let mut nodes = HashMap::<NodePtr, NodePtr>::new();
+        let mut atoms = HashMap::<&[u8], NodePtr>::new();
+        let mut ret = Vec::with_capacity(roots.len());
+
+        // the bool indicates whether the children of a pair have already been
+        // copied
+        let mut stack = Vec::<(NodePtr, bool)>::new();
+        for root in roots {
+            stack.push((*root, false));
+            while let Some((node, children_done)) = stack.pop() {
+                if nodes.contains_key(&node) {
+                    continue;
+                }
+                let new_node = match src.node(node) {
+                    NodeVisitor::U32(val) => self.new_small_number(val)?,
+                    NodeVisitor::Buffer(buf) => {
+                        if let Some(n) = atoms.get(buf) {
+                            *n
+                        } else {
+                            let n = self.new_atom(buf)?;
+                            atoms.insert(buf, n);
+                            n
+                        }
+                    }
+                    NodeVisitor::Pair(first, rest) => {
+                        if !children_done {
+                            stack.push((node, true));
+                            stack.push((rest, false));
+                            stack.push((first, false));
+                            continue;
+                        }
+                        self.new_pair(nodes[&first], nodes[&rest])?
+                    }
+                };
+                nodes.insert(node, new_node);
+            }
+            ret.push(nodes[root]);
+        }
+        Ok(ret)
+    }
+
+    // create a new allocator, with the same limits as this one, holding only
+    // the trees reachable from `roots`. This allows a long-lived process to
+    // keep the values it needs and drop everything else. The returned vector
+    // holds the new roots, in the same order.
+    pub fn compact(&self, roots: &[NodePtr]) -> Result<(Allocator, Vec<NodePtr>), EvalErr> {
+        let mut a = Allocator::new_with_limits(self.heap_limit + 1, self.max_atoms, self.max_pairs);
+        let new_roots = a.copy_many_from(self, roots)?;
+        Ok((a, new_roots))
+    }
+
+    // the heap, atom and pair limits, in the form new_with_limits() takes them
+    pub(crate) fn limits(&self) -> (usize, usize, usize) {
+        (self.heap_limit + 1, self.max_atoms, self.max_pairs)
+    }
+
+    // the heap limit can't be lowered below what's already allocated, since
+    // the remaining space is computed by subtracting it
+    pub(crate) fn set_limits(&mut self, heap_limit: usize, max_atoms: usize, max_pairs: usize) {
+        self.heap_limit = heap_limit.saturating_sub(1).max(self.u8_vec.len());
+        self.max_atoms = max_atoms;
+        self.max_pairs = max_pairs;
+    }
+
     #[inline]
     fn check_atom_limit(&self) -> Result<(), EvalErr> {
-        if self.atom_vec.len() + self.small_atoms == MAX_NUM_ATOMS {
-            err(self.nil(), "too many atoms")
+        if self.atom_vec.len() + self.small_atoms >= self.max_atoms {
+            err(self.nil(), EvalErrKind::TooManyAtoms)
         } else {
             Ok(())
         }
@@ -646,6 +1025,55 @@ impl Allocator {
     pub fn heap_size(&self) -> usize {
         self.u8_vec.len()
     }
+
+    // turn this allocator into a read-only FrozenAllocator. This releases the
+    // interning tables, since no more nodes can be allocated. Spare capacity
+    // is only released when it's at least as large as the memory in use, to
+    // avoid copying the whole heap for little gain
+    pub fn freeze(mut self) -> FrozenAllocator {
+        self.interner = None;
+        shrink_if_mostly_spare(&mut self.u8_vec);
+        shrink_if_mostly_spare(&mut self.pair_vec);
+        shrink_if_mostly_spare(&mut self.atom_vec);
+        FrozenAllocator(self)
+    }
+}
+
+fn shrink_if_mostly_spare<T>(v: &mut Vec<T>) {
+    if v.capacity() - v.len() >= v.len() {
+        v.shrink_to_fit();
+    }
+}
+
+// a read-only allocator. It can no longer be modified, which makes it safe to
+// share between threads (e.g. in an Arc). All the accessors of Allocator that
+// take &self (like atom(), sexp() and number()) are available, and it can be
+// passed to the serializers, via Deref.
+#[derive(Debug)]
+pub struct FrozenAllocator(Allocator);
+
+impl From<Allocator> for FrozenAllocator {
+    fn from(a: Allocator) -> Self {
+        a.freeze()
+    }
+}
+
+impl std::ops::Deref for FrozenAllocator {
+    type Target = Allocator;
+
+    fn deref(&self) -> &Allocator {
+        &self.0
+    }
+}
+
+impl FrozenAllocator {
+    // turn this back into an allocator that can be modified. Existing NodePtr
+    // values remain valid. Interning is not restored, since freeze() released
+    // the interning tables. Call enable_interning() on the returned allocator
+    // to rebuild them from the existing nodes
+    pub fn thaw(self) -> Allocator {
+        self.0
+    }
 }
 
 #[test]
@@ -834,7 +1262,7 @@ fn test_invalid_node_ptr_type() {
 #[test]
 #[should_panic]
 fn test_node_ptr_overflow() {
-    NodePtr::new(ObjectType::Bytes, NODE_PTR_IDX_MASK + 1);
+    NodePtr::new(ObjectType::Bytes, NODE_PTR_IDX_MASK as usize + 1);
 }
 
 #[cfg(dbg)]
@@ -842,7 +1270,7 @@ fn test_node_ptr_overflow() {
 #[should_panic]
 fn test_invalid_small_number() {
     let mut a = Allocator::new();
-    a.new_small_number(NODE_PTR_IDX_MASK + 1);
+    a.new_small_number(SMALL_ATOM_MAX + 1);
 }
 
 #[cfg(test)]
@@ -902,7 +1330,10 @@ fn test_allocate_pair() {
 fn test_allocate_heap_limit() {
     let mut a = Allocator::new_limited(6);
     // we can't allocate 6 bytes
-    assert_eq!(a.new_atom(b"foobar").unwrap_err().1, "out of memory");
+    assert_eq!(
+        a.new_atom(b"foobar").unwrap_err().1,
+        EvalErrKind::OutOfMemory
+    );
     // but 5 is OK
     let _atom = a.new_atom(b"fooba").unwrap();
 }
@@ -915,7 +1346,10 @@ fn test_allocate_atom_limit() {
         // exhaust the number of atoms allowed to be allocated
         let _ = a.new_atom(b"foo").unwrap();
     }
-    assert_eq!(a.new_atom(b"foobar").unwrap_err().1, "too many atoms");
+    assert_eq!(
+        a.new_atom(b"foobar").unwrap_err().1,
+        EvalErrKind::TooManyAtoms
+    );
     assert_eq!(a.u8_vec.len(), 0);
     assert_eq!(a.small_atoms, MAX_NUM_ATOMS);
 }
@@ -928,7 +1362,10 @@ fn test_allocate_small_number_limit() {
         // exhaust the number of atoms allowed to be allocated
         let _ = a.new_atom(b"foo").unwrap();
     }
-    assert_eq!(a.new_small_number(3).unwrap_err().1, "too many atoms");
+    assert_eq!(
+        a.new_small_number(3).unwrap_err().1,
+        EvalErrKind::TooManyAtoms
+    );
     assert_eq!(a.u8_vec.len(), 0);
     assert_eq!(a.small_atoms, MAX_NUM_ATOMS);
 }
@@ -942,7 +1379,10 @@ fn test_allocate_substr_limit() {
         let _ = a.new_atom(b"foo").unwrap();
     }
     let atom = a.new_atom(b"foo").unwrap();
-    assert_eq!(a.new_substr(atom, 1, 2).unwrap_err().1, "too many atoms");
+    assert_eq!(
+        a.new_substr(atom, 1, 2).unwrap_err().1,
+        EvalErrKind::TooManyAtoms
+    );
     assert_eq!(a.u8_vec.len(), 0);
     assert_eq!(a.small_atoms, MAX_NUM_ATOMS);
 }
@@ -956,7 +1396,10 @@ fn test_allocate_concat_limit() {
         let _ = a.new_atom(b"foo").unwrap();
     }
     let atom = a.new_atom(b"foo").unwrap();
-    assert_eq!(a.new_concat(3, &[atom]).unwrap_err().1, "too many atoms");
+    assert_eq!(
+        a.new_concat(3, &[atom]).unwrap_err().1,
+        EvalErrKind::TooManyAtoms
+    );
     assert_eq!(a.u8_vec.len(), 0);
     assert_eq!(a.small_atoms, MAX_NUM_ATOMS);
 }
@@ -972,7 +1415,34 @@ fn test_allocate_pair_limit() {
         let _ = a.new_pair(atom, atom).unwrap();
     }
 
-    assert_eq!(a.new_pair(atom, atom).unwrap_err().1, "too many pairs");
+    assert_eq!(
+        a.new_pair(atom, atom).unwrap_err().1,
+        EvalErrKind::TooManyPairs
+    );
+}
+
+#[test]
+fn test_custom_limits() {
+    let mut a = Allocator::new_with_limits(10, 5, 2);
+    // nil and one count towards the atom limit
+    let atom = a.new_atom(b"foo").unwrap();
+    let _ = a.new_small_number(1).unwrap();
+    let _ = a.new_atom(b"bar").unwrap();
+    assert_eq!(a.new_atom(b"").unwrap_err().1, EvalErrKind::TooManyAtoms);
+
+    let _ = a.new_pair(atom, atom).unwrap();
+    let _ = a.new_pair(atom, atom).unwrap();
+    assert_eq!(
+        a.new_pair(atom, atom).unwrap_err().1,
+        EvalErrKind::TooManyPairs
+    );
+
+    let mut a = Allocator::new_with_limits(10, 5, 2);
+    let _ = a.new_atom(b"foobar").unwrap();
+    assert_eq!(
+        a.new_atom(b"foobar").unwrap_err().1,
+        EvalErrKind::OutOfMemory
+    );
 }
 
 #[test]
@@ -992,23 +1462,23 @@ fn test_substr() {
 
     assert_eq!(
         a.new_substr(atom, 1, 0).unwrap_err().1,
-        "substr invalid bounds"
+        EvalErrKind::SubstrInvalidBounds
     );
     assert_eq!(
         a.new_substr(atom, 7, 7).unwrap_err().1,
-        "substr start out of bounds"
+        EvalErrKind::SubstrStartOutOfBounds
     );
     assert_eq!(
         a.new_substr(atom, 0, 7).unwrap_err().1,
-        "substr end out of bounds"
+        EvalErrKind::SubstrEndOutOfBounds
     );
     assert_eq!(
         a.new_substr(atom, u32::MAX, 4).unwrap_err().1,
-        "substr start out of bounds"
+        EvalErrKind::SubstrStartOutOfBounds
     );
     assert_eq!(
         a.new_substr(pair, 0, 0).unwrap_err().1,
-        "(internal error) substr expected atom, got pair"
+        EvalErrKind::InternalError("substr expected atom, got pair")
     );
 }
 
@@ -1031,19 +1501,19 @@ fn test_substr_small_number() {
 
     assert_eq!(
         a.new_substr(atom, 1, 0).unwrap_err().1,
-        "substr invalid bounds"
+        EvalErrKind::SubstrInvalidBounds
     );
     assert_eq!(
         a.new_substr(atom, 3, 3).unwrap_err().1,
-        "substr start out of bounds"
+        EvalErrKind::SubstrStartOutOfBounds
     );
     assert_eq!(
         a.new_substr(atom, 0, 3).unwrap_err().1,
-        "substr end out of bounds"
+        EvalErrKind::SubstrEndOutOfBounds
     );
     assert_eq!(
         a.new_substr(atom, u32::MAX, 2).unwrap_err().1,
-        "substr start out of bounds"
+        EvalErrKind::SubstrStartOutOfBounds
     );
 }
 
@@ -1088,25 +1558,25 @@ fn test_concat() {
 
     assert_eq!(
         a.new_concat(11, &[cat, cat]).unwrap_err().1,
-        "(internal error) concat passed invalid new_size"
+        EvalErrKind::InternalError("concat passed invalid new_size")
     );
     assert_eq!(
         a.new_concat(13, &[cat, cat]).unwrap_err().1,
-        "(internal error) concat passed invalid new_size"
+        EvalErrKind::InternalError("concat passed invalid new_size")
     );
     assert_eq!(
         a.new_concat(12, &[atom3, pair]).unwrap_err().1,
-        "(internal error) concat expected atom, got pair"
+        EvalErrKind::InternalError("concat expected atom, got pair")
     );
 
     assert_eq!(
         a.new_concat(4, &[atom1, atom2, atom3]).unwrap_err().1,
-        "(internal error) concat passed invalid new_size"
+        EvalErrKind::InternalError("concat passed invalid new_size")
     );
 
     assert_eq!(
         a.new_concat(2, &[atom1, atom2, atom3]).unwrap_err().1,
-        "(internal error) concat passed invalid new_size"
+        EvalErrKind::InternalError("concat passed invalid new_size")
     );
 }
 
@@ -1125,25 +1595,25 @@ fn test_concat_large() {
 
     assert_eq!(
         a.new_concat(11, &[cat, cat]).unwrap_err().1,
-        "(internal error) concat passed invalid new_size"
+        EvalErrKind::InternalError("concat passed invalid new_size")
     );
     assert_eq!(
         a.new_concat(13, &[cat, cat]).unwrap_err().1,
-        "(internal error) concat passed invalid new_size"
+        EvalErrKind::InternalError("concat passed invalid new_size")
     );
     assert_eq!(
         a.new_concat(12, &[atom1, pair]).unwrap_err().1,
-        "(internal error) concat expected atom, got pair"
+        EvalErrKind::InternalError("concat expected atom, got pair")
     );
 
     assert_eq!(
         a.new_concat(4, &[atom1, atom2]).unwrap_err().1,
-        "(internal error) concat passed invalid new_size"
+        EvalErrKind::InternalError("concat passed invalid new_size")
     );
 
     assert_eq!(
         a.new_concat(2, &[atom1, atom2]).unwrap_err().1,
-        "(internal error) concat passed invalid new_size"
+        EvalErrKind::InternalError("concat passed invalid new_size")
     );
 }
 
@@ -1174,7 +1644,7 @@ fn test_concat_limit() {
         a.new_concat(6, &[atom1, atom2, atom3, atom4, atom5, atom6])
             .unwrap_err()
             .1,
-        "out of memory"
+        EvalErrKind::OutOfMemory
     );
     let cat = a.new_concat(2, &[atom1, atom2]).unwrap();
     assert_eq!(a.atom(cat).as_ref(), b"fo");
@@ -1254,36 +1724,36 @@ type TestFun = fn(&Allocator, NodePtr) -> EvalErr;
 
 #[cfg(test)]
 #[rstest]
-#[case(test_g1, 0, "atom is not G1 size, 48 bytes")]
-#[case(test_g1, 3, "atom is not G1 size, 48 bytes")]
-#[case(test_g1, 47, "atom is not G1 size, 48 bytes")]
-#[case(test_g1, 49, "atom is not G1 size, 48 bytes")]
-#[case(test_g1, 48, "atom is not a G1 point")]
-#[case(test_g2, 0, "atom is not G2 size, 96 bytes")]
-#[case(test_g2, 3, "atom is not G2 size, 96 bytes")]
-#[case(test_g2, 95, "atom is not G2 size, 96 bytes")]
-#[case(test_g2, 97, "atom is not G2 size, 96 bytes")]
-#[case(test_g2, 96, "atom is not a G2 point")]
-fn test_point_size_error(#[case] fun: TestFun, #[case] size: usize, #[case] expected: &str) {
+#[case(test_g1, 0, EvalErrKind::G1WrongSize)]
+#[case(test_g1, 3, EvalErrKind::G1WrongSize)]
+#[case(test_g1, 47, EvalErrKind::G1WrongSize)]
+#[case(test_g1, 49, EvalErrKind::G1WrongSize)]
+#[case(test_g1, 48, EvalErrKind::NotG1Point)]
+#[case(test_g2, 0, EvalErrKind::G2WrongSize)]
+#[case(test_g2, 3, EvalErrKind::G2WrongSize)]
+#[case(test_g2, 95, EvalErrKind::G2WrongSize)]
+#[case(test_g2, 97, EvalErrKind::G2WrongSize)]
+#[case(test_g2, 96, EvalErrKind::NotG2Point)]
+fn test_point_size_error(#[case] fun: TestFun, #[case] size: usize, #[case] expected: EvalErrKind) {
     let mut a = Allocator::new();
     let mut buf = Vec::<u8>::new();
     buf.resize(size, 0xcc);
     let n = a.new_atom(&buf).unwrap();
     let r = fun(&a, n);
     assert_eq!(r.0, n);
-    assert_eq!(r.1, expected.to_string());
+    assert_eq!(r.1, expected);
 }
 
 #[cfg(test)]
 #[rstest]
-#[case(test_g1, "pair found, expected G1 point")]
-#[case(test_g2, "pair found, expected G2 point")]
-fn test_point_atom_pair(#[case] fun: TestFun, #[case] expected: &str) {
+#[case(test_g1, EvalErrKind::G1ExpectedAtom)]
+#[case(test_g2, EvalErrKind::G2ExpectedAtom)]
+fn test_point_atom_pair(#[case] fun: TestFun, #[case] expected: EvalErrKind) {
     let mut a = Allocator::new();
     let n = a.new_pair(a.nil(), a.one()).unwrap();
     let r = fun(&a, n);
     assert_eq!(r.0, n);
-    assert_eq!(r.1, expected.to_string());
+    assert_eq!(r.1, expected);
 }
 
 #[cfg(test)]
@@ -1311,11 +1781,8 @@ fn test_g1_roundtrip(#[case] atom: &str) {
     assert_eq!(hex::encode(g1_atom), atom);
 
     // try interpreting the point as G1
-    assert_eq!(a.g2(n).unwrap_err().1, "atom is not G2 size, 96 bytes");
-    assert_eq!(
-        a.g2(g1_copy).unwrap_err().1,
-        "atom is not G2 size, 96 bytes"
-    );
+    assert_eq!(a.g2(n).unwrap_err().1, EvalErrKind::G2WrongSize);
+    assert_eq!(a.g2(g1_copy).unwrap_err().1, EvalErrKind::G2WrongSize);
 
     // try interpreting the point as number
     assert_eq!(a.number(n), number_from_u8(&hex::decode(atom).unwrap()));
@@ -1356,11 +1823,8 @@ fn test_g2_roundtrip(#[case] atom: &str) {
     assert_eq!(hex::encode(g2_atom), atom);
 
     // try interpreting the point as G1
-    assert_eq!(a.g1(n).unwrap_err().1, "atom is not G1 size, 48 bytes");
-    assert_eq!(
-        a.g1(g2_copy).unwrap_err().1,
-        "atom is not G1 size, 48 bytes"
-    );
+    assert_eq!(a.g1(n).unwrap_err().1, EvalErrKind::G1WrongSize);
+    assert_eq!(a.g1(g2_copy).unwrap_err().1, EvalErrKind::G1WrongSize);
 
     // try interpreting the point as number
     assert_eq!(a.number(n), number_from_u8(&hex::decode(atom).unwrap()));
@@ -1833,3 +2297,395 @@ fn test_auto_small_number_from_buf(#[case] buf: &[u8], #[case] expect_small: boo
 fn test_fits_in_small_atom(#[case] buf: &[u8], #[case] expected: Option<u32>) {
     assert_eq!(fits_in_small_atom(buf), expected);
 }
+
+#[cfg(test)]
+use crate::serde::node_to_bytes;
+
+#[test]
+fn test_copy_from() {
+    let mut a = Allocator::new();
+    let foo1 = a.new_atom(b"foobar").unwrap();
+    let foo2 = a.new_atom(b"foobar").unwrap();
+    let num = a.new_small_number(1337).unwrap();
+    let shared = a.new_pair(foo1, num).unwrap();
+    let left = a.new_pair(shared, foo2).unwrap();
+    let root = a.new_pair(left, shared).unwrap();
+
+    let mut b = Allocator::new();
+    let copy = b.copy_from(&a, root).unwrap();
+    assert_eq!(
+        node_to_bytes(&a, root).unwrap(),
+        node_to_bytes(&b, copy).unwrap()
+    );
+
+    // the shared subtree was only copied once
+    let (left, right) = b.next(copy).unwrap();
+    let (shared, foo2) = b.next(left).unwrap();
+    assert_eq!(shared, right);
+    assert_eq!(b.pair_vec.len(), 3);
+
+    // the two "foobar" atoms were deduplicated
+    let (foo1, _) = b.next(shared).unwrap();
+    assert_eq!(foo1, foo2);
+    assert_eq!(b.atom_vec.len(), 1);
+    assert_eq!(b.u8_vec.len(), 6);
+}
+
+#[test]
+fn test_copy_many_from() {
+    let mut a = Allocator::new();
+    let foo = a.new_atom(b"foobar").unwrap();
+    let one = a.one();
+    let shared = a.new_pair(foo, one).unwrap();
+    let root1 = a.new_pair(shared, one).unwrap();
+    let root2 = a.new_pair(one, shared).unwrap();
+
+    let mut b = Allocator::new();
+    let roots = b.copy_many_from(&a, &[root1, root2, shared]).unwrap();
+    assert_eq!(roots.len(), 3);
+    assert_eq!(b.next(roots[0]).unwrap().0, roots[2]);
+    assert_eq!(b.next(roots[1]).unwrap().1, roots[2]);
+    assert_eq!(b.pair_vec.len(), 3);
+    assert_eq!(b.atom_vec.len(), 1);
+}
+
+#[test]
+fn test_compact() {
+    let mut a = Allocator::new_with_limits(1000, 100, 100);
+    let mut garbage = a.nil();
+    for i in 0..20 {
+        let atom = a.new_atom(&[i; 10]).unwrap();
+        garbage = a.new_pair(atom, garbage).unwrap();
+    }
+    let keep = a.new_atom(b"keep me").unwrap();
+    let keep = a.new_pair(keep, a.nil()).unwrap();
+    assert!(a.next(garbage).is_some());
+    assert_eq!(a.u8_vec.len(), 207);
+    assert_eq!(a.pair_vec.len(), 21);
+
+    let (b, roots) = a.compact(&[keep]).unwrap();
+    assert_eq!(
+        node_to_bytes(&a, keep).unwrap(),
+        node_to_bytes(&b, roots[0]).unwrap()
+    );
+    assert_eq!(b.u8_vec.len(), 7);
+    assert_eq!(b.pair_vec.len(), 1);
+    assert_eq!(b.heap_limit, a.heap_limit);
+    assert_eq!(b.max_atoms, a.max_atoms);
+    assert_eq!(b.max_pairs, a.max_pairs);
+}
+
+#[test]
+fn test_copy_from_deep_tree() {
+    // make sure we don't use the stack to copy deep trees
+    let mut a = Allocator::new();
+    let mut top = a.nil();
+    for _ in 0..200_000 {
+        let atom = a.one();
+        top = a.new_pair(atom, top).unwrap();
+    }
+    let mut b = Allocator::new();
+    let copy = b.copy_from(&a, top).unwrap();
+    assert_eq!(
+        node_to_bytes(&a, top).unwrap(),
+        node_to_bytes(&b, copy).unwrap()
+    );
+}
+
+#[test]
+fn test_copy_from_limits() {
+    let mut a = Allocator::new();
+    let atom = a.new_atom(&[0xff; 100]).unwrap();
+
+    let mut b = Allocator::new_limited(50);
+    assert_eq!(
+        b.copy_from(&a, atom).unwrap_err().1,
+        EvalErrKind::OutOfMemory
+    );
+
+    let root = a.new_pair(atom, atom).unwrap();
+    let mut b = Allocator::new_with_limits(1000, 10, 0);
+    assert_eq!(
+        b.copy_from(&a, root).unwrap_err().1,
+        EvalErrKind::TooManyPairs
+    );
+}
+
+#[test]
+fn test_no_interning() {
+    let mut a = Allocator::new();
+    assert!(!a.is_interning());
+    assert_eq!(a.intern_savings(), None);
+    let atom1 = a.new_atom(b"foobar").unwrap();
+    let atom2 = a.new_atom(b"foobar").unwrap();
+    assert!(atom1 != atom2);
+    let pair1 = a.new_pair(atom1, atom1).unwrap();
+    let pair2 = a.new_pair(atom1, atom1).unwrap();
+    assert!(pair1 != pair2);
+    assert_eq!(a.u8_vec.len(), 12);
+}
+
+#[test]
+fn test_interning() {
+    let mut a = Allocator::new_interning();
+    assert!(a.is_interning());
+    assert_eq!(a.intern_savings(), Some(InternSavings::default()));
+
+    let atom1 = a.new_atom(b"foobar").unwrap();
+    let atom2 = a.new_atom(b"foobar").unwrap();
+    let atom3 = a.new_atom(b"foobaz").unwrap();
+    assert_eq!(atom1, atom2);
+    assert!(atom1 != atom3);
+
+    let pair1 = a.new_pair(atom1, atom3).unwrap();
+    let pair2 = a.new_pair(atom2, atom3).unwrap();
+    let pair3 = a.new_pair(atom3, atom1).unwrap();
+    assert_eq!(pair1, pair2);
+    assert!(pair1 != pair3);
+
+    assert_eq!(a.u8_vec.len(), 12);
+    assert_eq!(a.atom_vec.len(), 2);
+    assert_eq!(a.pair_vec.len(), 2);
+    assert_eq!(
+        a.intern_savings(),
+        Some(InternSavings {
+            atoms: 1,
+            pairs: 1,
+            bytes: 6
+        })
+    );
+}
+
+#[test]
+fn test_interning_substr_concat() {
+    let mut a = Allocator::new_interning();
+    let hello = a.new_atom(b"hello").unwrap();
+    let world = a.new_atom(b"world").unwrap();
+    let hello_world = a.new_atom(b"helloworld").unwrap();
+
+    assert_eq!(a.new_concat(10, &[hello, world]).unwrap(), hello_world);
+    assert_eq!(a.new_substr(hello_world, 5, 10).unwrap(), world);
+    assert_eq!(a.u8_vec.len(), 20);
+    assert_eq!(a.atom_vec.len(), 3);
+
+    // atoms that fit in a small atom are always returned as one
+    let one = a.one();
+    let buf = a.new_atom(&[0xff, 1]).unwrap();
+    assert_eq!(a.new_substr(buf, 1, 2).unwrap(), one);
+    assert_eq!(a.new_concat(1, &[one]).unwrap(), one);
+
+    // the result of a substr of a small atom is also interned
+    let num = a.new_small_number(0x3ff_ffff).unwrap();
+    let ff = a.new_atom(&[0xff, 0xff]).unwrap();
+    assert_eq!(a.new_substr(num, 2, 4).unwrap(), ff);
+
+    assert_eq!(
+        a.intern_savings(),
+        Some(InternSavings {
+            atoms: 5,
+            pairs: 0,
+            bytes: 10 + 1 + 2
+        })
+    );
+}
+
+#[test]
+fn test_interning_checkpoint() {
+    let mut a = Allocator::new_interning();
+    let hello = a.new_atom(b"hello").unwrap();
+    let pair1 = a.new_pair(hello, hello).unwrap();
+    let cp = a.checkpoint();
+    let world = a.new_atom(b"world").unwrap();
+    let pair2 = a.new_pair(world, hello).unwrap();
+    a.restore_checkpoint(&cp);
+
+    // the freed atom slot is reused
+    let other = a.new_atom(b"other").unwrap();
+    assert_eq!(other, world);
+    assert!(a.new_atom(b"world").unwrap() != other);
+
+    // the freed pair slot is reused by a different pair. Looking up the pair
+    // that was freed must not return it
+    let pair3 = a.new_pair(hello, pair1).unwrap();
+    assert_eq!(pair3, pair2);
+    let pair4 = a.new_pair(other, hello).unwrap();
+    assert!(pair4 != pair3);
+    assert_eq!(a.next(pair4), Some((other, hello)));
+
+    // nodes allocated before the checkpoint are still interned
+    assert_eq!(a.new_atom(b"hello").unwrap(), hello);
+    assert_eq!(a.new_pair(hello, hello).unwrap(), pair1);
+}
+
+#[test]
+fn test_interning_limits() {
+    let mut a = Allocator::new_limited(12);
+    a.enable_interning();
+    let hello = a.new_atom(b"hello").unwrap();
+    a.new_atom(b"world").unwrap();
+    assert_eq!(
+        a.new_atom(b"hello!").unwrap_err().1,
+        EvalErrKind::OutOfMemory
+    );
+    // the heap is full, but finding an existing atom doesn't allocate
+    assert_eq!(a.new_atom(b"hello").unwrap(), hello);
+}
+
+#[test]
+fn test_enable_interning() {
+    let mut a = Allocator::new();
+    let hello = a.new_atom(b"hello").unwrap();
+    let pair = a.new_pair(hello, hello).unwrap();
+    a.enable_interning();
+    assert_eq!(a.new_atom(b"hello").unwrap(), hello);
+    assert_eq!(a.new_pair(hello, hello).unwrap(), pair);
+    assert_eq!(
+        a.intern_savings(),
+        Some(InternSavings {
+            atoms: 1,
+            pairs: 1,
+            bytes: 5
+        })
+    );
+}
+
+#[test]
+fn test_interning_deserialize() {
+    use crate::serde::node_from_bytes;
+
+    // (("hello" "world") ("hello" "world")), without back references
+    let mut a = Allocator::new();
+    let hello = a.new_atom(b"hello").unwrap();
+    let world = a.new_atom(b"world").unwrap();
+    let nil = a.nil();
+    let mut list = [nil, nil];
+    for item in &mut list {
+        let tail = a.new_pair(world, nil).unwrap();
+        *item = a.new_pair(hello, tail).unwrap();
+    }
+    let tail = a.new_pair(list[1], nil).unwrap();
+    let root = a.new_pair(list[0], tail).unwrap();
+    let buf = node_to_bytes(&a, root).unwrap();
+
+    let mut a = Allocator::new_interning();
+    let root = node_from_bytes(&mut a, &buf).unwrap();
+    let (first, tail) = a.next(root).unwrap();
+    let (second, _) = a.next(tail).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(a.u8_vec.len(), 10);
+    assert_eq!(
+        a.intern_savings(),
+        Some(InternSavings {
+            atoms: 2,
+            pairs: 2,
+            bytes: 10
+        })
+    );
+}
+
+#[test]
+fn test_frozen_allocator() {
+    use crate::serde::node_from_bytes;
+    use std::sync::Arc;
+
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let mut a = Allocator::new_interning();
+    let hello = a.new_atom(b"hello").unwrap();
+    let num = a.new_small_number(1337).unwrap();
+    let root = a.new_pair(hello, num).unwrap();
+    let buf = node_to_bytes(&a, root).unwrap();
+
+    let frozen = Arc::new(a.freeze());
+    assert_send_sync(&frozen);
+    assert!(!frozen.is_interning());
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let frozen = frozen.clone();
+            std::thread::spawn(move || {
+                let SExp::Pair(first, rest) = frozen.sexp(root) else {
+                    panic!("expected pair");
+                };
+                assert_eq!(frozen.atom(first).as_ref(), b"hello");
+                assert_eq!(frozen.number(rest), 1337.into());
+                node_to_bytes(&frozen, root).unwrap()
+            })
+        })
+        .collect();
+    for h in handles {
+        assert_eq!(h.join().unwrap(), buf);
+    }
+
+    let mut a = Arc::into_inner(frozen).unwrap().thaw();
+    let new_root = node_from_bytes(&mut a, &buf).unwrap();
+    assert!(!a.is_interning());
+    assert_eq!(a.next(new_root).unwrap().0.index(), hello.index() + 1);
+    assert_eq!(a.atom(hello).as_ref(), b"hello");
+
+    // re-enabling interning finds the nodes allocated before freezing
+    a.enable_interning();
+    assert_eq!(a.new_atom(b"hello").unwrap(), hello);
+    assert_eq!(a.new_pair(hello, num).unwrap(), root);
+}
+
+#[cfg(not(feature = "wide-heap"))]
+#[test]
+#[should_panic]
+fn test_heap_limit_too_large() {
+    Allocator::new_limited(u32::MAX as usize + 1);
+}
+
+#[cfg(feature = "wide-heap")]
+#[test]
+fn test_wide_heap() {
+    let big = 1_usize << 32;
+    let mut a = Allocator::new_with_limits(big * 2, big, big);
+    let atom = a.new_atom(b"hello").unwrap();
+    let pair = a.new_pair(atom, atom).unwrap();
+    assert_eq!(a.next(pair), Some((atom, atom)));
+
+    for (t, idx) in [
+        (ObjectType::Pair, big),
+        (ObjectType::Bytes, big + 1),
+        (ObjectType::SmallAtom, 1337),
+    ] {
+        let node = NodePtr::new(t, idx);
+        assert_eq!(node.object_type(), t);
+        assert_eq!(node.index(), idx);
+    }
+}
+
+#[cfg(feature = "wide-heap")]
+#[test]
+fn test_wide_heap_many_pairs() {
+    // more pairs than fit in the 26 bit index of the compact representation
+    let count = (1_usize << 26) + 1;
+    let mut a = Allocator::new_with_limits(MAX_HEAP_LIMIT, MAX_NODE_COUNT, MAX_NODE_COUNT);
+    a.pair_vec.reserve_exact(count);
+    let atom = a.new_atom(b"hello").unwrap();
+    let mut last = a.nil();
+    for _ in 0..count {
+        last = a.new_pair(atom, last).unwrap();
+    }
+    assert_eq!(a.pair_vec.len(), count);
+    assert_eq!(last.index(), count - 1);
+    let (first, rest) = a.next(last).unwrap();
+    assert_eq!(first, atom);
+    assert_eq!(rest.index(), count - 2);
+}
+
+#[test]
+fn test_shrink_if_mostly_spare() {
+    // little spare capacity is kept, to avoid reallocating
+    let mut v = Vec::<u8>::with_capacity(100);
+    v.resize(60, 0);
+    shrink_if_mostly_spare(&mut v);
+    assert_eq!(v.capacity(), 100);
+
+    // but if most of the capacity is unused, it's released
+    let mut v = Vec::<u8>::with_capacity(100);
+    v.resize(50, 0);
+    shrink_if_mostly_spare(&mut v);
+    assert_eq!(v.capacity(), 50);
+}
@@ -1,21 +1,28 @@
 use crate::allocator::{Allocator, NodePtr};
 use crate::bls_ops::{
-    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g2_add, op_bls_g2_multiply,
-    op_bls_g2_negate, op_bls_g2_subtract, op_bls_map_to_g1, op_bls_map_to_g2,
-    op_bls_pairing_identity, op_bls_verify,
+    op_bls_g1_multiply_with_costs, op_bls_g1_negate_with_costs, op_bls_g1_subtract_with_costs,
+    op_bls_g2_add_with_costs, op_bls_g2_multiply_with_costs, op_bls_g2_negate_with_costs,
+    op_bls_g2_subtract_with_costs, op_bls_map_to_g1_with_costs, op_bls_map_to_g2_with_costs,
+    op_bls_pairing_identity_with_costs, op_bls_verify_with_costs,
 };
-use crate::core_ops::{op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest};
-use crate::cost::Cost;
+use crate::core_ops::{
+    op_cons_with_costs, op_eq_with_costs, op_first_with_costs, op_if_with_costs,
+    op_listp_with_costs, op_raise_with_costs, op_rest_with_costs,
+};
+use crate::cost::{Cost, CostSchedule, CostScheduleError};
 use crate::dialect::{Dialect, OperatorSet};
 use crate::err_utils::err;
 use crate::more_ops::{
-    op_add, op_all, op_any, op_ash, op_coinid, op_concat, op_div, op_div_fixed, op_divmod, op_gr,
-    op_gr_bytes, op_logand, op_logior, op_lognot, op_logxor, op_lsh, op_mod, op_modpow,
-    op_multiply, op_not, op_point_add, op_pubkey_for_exp, op_sha256, op_strlen, op_substr,
-    op_subtract, op_unknown,
+    op_add_with_costs, op_all_with_costs, op_any_with_costs, op_ash_with_costs,
+    op_coinid_with_costs, op_concat_with_costs, op_div_fixed_with_costs, op_div_with_costs,
+    op_divmod_with_costs, op_gr_bytes_with_costs, op_gr_with_costs, op_logand_with_costs,
+    op_logior_with_costs, op_lognot_with_costs, op_logxor_with_costs, op_lsh_with_costs,
+    op_mod_with_costs, op_modpow_with_costs, op_multiply_with_costs, op_not_with_costs,
+    op_point_add_with_costs, op_pubkey_for_exp_with_costs, op_sha256_with_costs,
+    op_strlen_with_costs, op_substr_with_costs, op_subtract_with_costs, op_unknown_with_costs,
 };
-use crate::reduction::Response;
-use crate::secp_ops::{op_secp256k1_verify, op_secp256r1_verify};
+use crate::reduction::{EvalErrKind, Response};
+use crate::secp_ops::{op_secp256k1_verify_with_costs, op_secp256r1_verify_with_costs};
 
 // unknown operators are disallowed
 // (otherwise they are no-ops with well defined cost)
@@ -43,21 +50,35 @@ fn unknown_operator(
     args: NodePtr,
     flags: u32,
     max_cost: Cost,
+    costs: &CostSchedule,
 ) -> Response {
     if (flags & NO_UNKNOWN_OPS) != 0 {
-        err(o, "unimplemented operator")
+        err(o, EvalErrKind::UnimplementedOperator)
     } else {
-        op_unknown(allocator, o, args, max_cost)
+        op_unknown_with_costs(allocator, o, args, max_cost, costs)
     }
 }
 
 pub struct ChiaDialect {
     flags: u32,
+    costs: CostSchedule,
 }
 
 impl ChiaDialect {
     pub fn new(flags: u32) -> ChiaDialect {
-        ChiaDialect { flags }
+        ChiaDialect {
+            flags,
+            costs: CostSchedule::DEFAULT,
+        }
+    }
+
+    /// use an alternative cost schedule, e.g. to evaluate proposed changes
+    /// to the costs. Note that this isn't compatible with consensus. Fails if
+    /// the schedule fails `CostSchedule::validate()`
+    pub fn with_cost_schedule(mut self, costs: CostSchedule) -> Result<Self, CostScheduleError> {
+        costs.validate()?;
+        self.costs = costs;
+        Ok(self)
     }
 }
 
@@ -90,81 +111,102 @@ impl Dialect for ChiaDialect {
             // which makes the multiplier 0x1c3a8f and 0x0cf84f (there is an
             // implied +1) and cost function 0
             let f = match opcode {
-                0x13d61f00 => op_secp256k1_verify,
-                0x1c3a8f00 => op_secp256r1_verify,
+                0x13d61f00 => op_secp256k1_verify_with_costs,
+                0x1c3a8f00 => op_secp256r1_verify_with_costs,
                 _ => {
-                    return unknown_operator(allocator, o, argument_list, self.flags, max_cost);
+                    return unknown_operator(
+                        allocator,
+                        o,
+                        argument_list,
+                        self.flags,
+                        max_cost,
+                        &self.costs,
+                    );
                 }
             };
-            return f(allocator, argument_list, max_cost);
+            return f(allocator, argument_list, max_cost, &self.costs);
         }
         if op_len != 1 {
-            return unknown_operator(allocator, o, argument_list, self.flags, max_cost);
+            return unknown_operator(
+                allocator,
+                o,
+                argument_list,
+                self.flags,
+                max_cost,
+                &self.costs,
+            );
         }
         let Some(op) = allocator.small_number(o) else {
-            return unknown_operator(allocator, o, argument_list, self.flags, max_cost);
+            return unknown_operator(
+                allocator,
+                o,
+                argument_list,
+                self.flags,
+                max_cost,
+                &self.costs,
+            );
         };
         let f = match op {
             // 1 = quote
             // 2 = apply
-            3 => op_if,
-            4 => op_cons,
-            5 => op_first,
-            6 => op_rest,
-            7 => op_listp,
-            8 => op_raise,
-            9 => op_eq,
-            10 => op_gr_bytes,
-            11 => op_sha256,
-            12 => op_substr,
-            13 => op_strlen,
-            14 => op_concat,
+            3 => op_if_with_costs,
+            4 => op_cons_with_costs,
+            5 => op_first_with_costs,
+            6 => op_rest_with_costs,
+            7 => op_listp_with_costs,
+            8 => op_raise_with_costs,
+            9 => op_eq_with_costs,
+            10 => op_gr_bytes_with_costs,
+            11 => op_sha256_with_costs,
+            12 => op_substr_with_costs,
+            13 => op_strlen_with_costs,
+            14 => op_concat_with_costs,
             // 15 ---
-            16 => op_add,
-            17 => op_subtract,
-            18 => op_multiply,
+            16 => op_add_with_costs,
+            17 => op_subtract_with_costs,
+            18 => op_multiply_with_costs,
             19 => {
                 if (self.flags & ENABLE_FIXED_DIV) != 0 {
-                    op_div_fixed
+                    op_div_fixed_with_costs
                 } else {
-                    op_div
+                    op_div_with_costs
                 }
             }
-            20 => op_divmod,
-            21 => op_gr,
-            22 => op_ash,
-            23 => op_lsh,
-            24 => op_logand,
-            25 => op_logior,
-            26 => op_logxor,
-            27 => op_lognot,
+            20 => op_divmod_with_costs,
+            21 => op_gr_with_costs,
+            22 => op_ash_with_costs,
+            23 => op_lsh_with_costs,
+            24 => op_logand_with_costs,
+            25 => op_logior_with_costs,
+            26 => op_logxor_with_costs,
+            27 => op_lognot_with_costs,
             // 28 ---
-            29 => op_point_add,
-            30 => op_pubkey_for_exp,
+            29 => op_point_add_with_costs,
+            30 => op_pubkey_for_exp_with_costs,
             // 31 ---
-            32 => op_not,
-            33 => op_any,
-            34 => op_all,
+            32 => op_not_with_costs,
+            33 => op_any_with_costs,
+            34 => op_all_with_costs,
             // 35 ---
             // 36 = softfork
             _ => {
                 if extension == OperatorSet::BLS || (self.flags & ENABLE_BLS_OPS_OUTSIDE_GUARD) != 0
                 {
                     match op {
-                        48 => op_coinid,
-                        49 => op_bls_g1_subtract,
-                        50 => op_bls_g1_multiply,
-                        51 => op_bls_g1_negate,
-                        52 => op_bls_g2_add,
-                        53 => op_bls_g2_subtract,
-                        54 => op_bls_g2_multiply,
-                        55 => op_bls_g2_negate,
-                        56 => op_bls_map_to_g1,
-                        57 => op_bls_map_to_g2,
-                        58 => op_bls_pairing_identity,
-                        59 => op_bls_verify,
-                        60 => op_modpow,
-                        61 => op_mod,
+                        48 => op_coinid_with_costs,
+                        49 => op_bls_g1_subtract_with_costs,
+                        50 => op_bls_g1_multiply_with_costs,
+                        51 => op_bls_g1_negate_with_costs,
+                        52 => op_bls_g2_add_with_costs,
+                        53 => op_bls_g2_subtract_with_costs,
+                        54 => op_bls_g2_multiply_with_costs,
+                        55 => op_bls_g2_negate_with_costs,
+                        56 => op_bls_map_to_g1_with_costs,
+                        57 => op_bls_map_to_g2_with_costs,
+                        58 => op_bls_pairing_identity_with_costs,
+                        59 => op_bls_verify_with_costs,
+                        60 => op_modpow_with_costs,
+                        61 => op_mod_with_costs,
                         _ => {
                             return unknown_operator(
                                 allocator,
@@ -172,15 +214,23 @@ impl Dialect for ChiaDialect {
                                 argument_list,
                                 self.flags,
                                 max_cost,
+                                &self.costs,
                             );
                         }
                     }
                 } else {
-                    return unknown_operator(allocator, o, argument_list, self.flags, max_cost);
+                    return unknown_operator(
+                        allocator,
+                        o,
+                        argument_list,
+                        self.flags,
+                        max_cost,
+                        &self.costs,
+                    );
                 }
             }
         };
-        f(allocator, argument_list, max_cost)
+        f(allocator, argument_list, max_cost, &self.costs)
     }
 
     fn quote_kw(&self) -> u32 {
@@ -206,4 +256,8 @@ impl Dialect for ChiaDialect {
     fn allow_unknown_ops(&self) -> bool {
         (self.flags & NO_UNKNOWN_OPS) == 0
     }
+
+    fn cost_schedule(&self) -> &CostSchedule {
+        &self.costs
+    }
 }
@@ -1,51 +1,80 @@
 use crate::allocator::{Allocator, NodePtr, SExp};
-use crate::cost::Cost;
+use crate::cost::{default_cost_ops, Cost, CostSchedule};
 use crate::err_utils::err;
 use crate::op_utils::{first, get_args, nilp, rest};
-use crate::reduction::{EvalErr, Reduction, Response};
+use crate::reduction::{EvalErr, EvalErrKind, Reduction, Response};
 
-const FIRST_COST: Cost = 30;
-const IF_COST: Cost = 33;
-// Cons cost lowered from 245. It only allocates a pair, which is small
-const CONS_COST: Cost = 50;
-// Rest cost lowered from 77 since it doesn't allocate anything and it should be
-// the same as first
-const REST_COST: Cost = 30;
-const LISTP_COST: Cost = 19;
-const EQ_BASE_COST: Cost = 117;
-const EQ_COST_PER_BYTE: Cost = 1;
+default_cost_ops! {
+    op_if => op_if_with_costs,
+    op_cons => op_cons_with_costs,
+    op_first => op_first_with_costs,
+    op_rest => op_rest_with_costs,
+    op_listp => op_listp_with_costs,
+    op_raise => op_raise_with_costs,
+    op_eq => op_eq_with_costs,
+}
 
-pub fn op_if(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_if_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [cond, affirmative, negative] = get_args::<3>(a, input, "i")?;
     let chosen_node = if nilp(a, cond) { negative } else { affirmative };
-    Ok(Reduction(IF_COST, chosen_node))
+    Ok(Reduction(costs.if_cost, chosen_node))
 }
 
-pub fn op_cons(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_cons_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n1, n2] = get_args::<2>(a, input, "c")?;
     let r = a.new_pair(n1, n2)?;
-    Ok(Reduction(CONS_COST, r))
+    Ok(Reduction(costs.cons_cost, r))
 }
 
-pub fn op_first(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_first_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n] = get_args::<1>(a, input, "f")?;
-    Ok(Reduction(FIRST_COST, first(a, n)?))
+    Ok(Reduction(costs.first_cost, first(a, n)?))
 }
 
-pub fn op_rest(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_rest_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n] = get_args::<1>(a, input, "r")?;
-    Ok(Reduction(REST_COST, rest(a, n)?))
+    Ok(Reduction(costs.rest_cost, rest(a, n)?))
 }
 
-pub fn op_listp(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_listp_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n] = get_args::<1>(a, input, "l")?;
     match a.sexp(n) {
-        SExp::Pair(_, _) => Ok(Reduction(LISTP_COST, a.one())),
-        _ => Ok(Reduction(LISTP_COST, a.nil())),
+        SExp::Pair(_, _) => Ok(Reduction(costs.listp_cost, a.one())),
+        _ => Ok(Reduction(costs.listp_cost, a.nil())),
     }
 }
 
-pub fn op_raise(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_raise_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    _costs: &CostSchedule,
+) -> Response {
     // if given a single argument we should raise the single argument rather
     // than the full list of arguments. brun also used to behave this way.
     // if the single argument here is a pair then don't throw it unwrapped
@@ -59,22 +88,31 @@ pub fn op_raise(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
         input
     };
 
-    err(throw_value, "clvm raise")
+    err(throw_value, EvalErrKind::Raise)
 }
 
-fn ensure_atom(a: &Allocator, n: NodePtr, op: &str) -> Result<(), EvalErr> {
+fn ensure_atom(a: &Allocator, n: NodePtr, op: &'static str) -> Result<(), EvalErr> {
     if let SExp::Atom = a.sexp(n) {
         Ok(())
     } else {
-        Err(EvalErr(n, format!("{op} on list")))
+        err(n, EvalErrKind::OnList(op))
     }
 }
 
-pub fn op_eq(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_eq_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [s0, s1] = get_args::<2>(a, input, "=")?;
     ensure_atom(a, s0, "=")?;
     ensure_atom(a, s1, "=")?;
     let eq = a.atom_eq(s0, s1);
-    let cost = EQ_BASE_COST + (a.atom_len(s0) as Cost + a.atom_len(s1) as Cost) * EQ_COST_PER_BYTE;
+    let cost = costs.eq_base_cost.saturating_add(
+        costs
+            .eq_cost_per_byte
+            .saturating_mul(a.atom_len(s0) as Cost + a.atom_len(s1) as Cost),
+    );
     Ok(Reduction(cost, if eq { a.one() } else { a.nil() }))
 }
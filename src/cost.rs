@@ -1,12 +1,424 @@
 use crate::allocator::Allocator;
-use crate::reduction::EvalErr;
+use crate::reduction::{EvalErr, EvalErrKind};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
 
 pub type Cost = u64;
 
 pub fn check_cost(a: &Allocator, cost: Cost, max_cost: Cost) -> Result<(), EvalErr> {
     if cost > max_cost {
-        Err(EvalErr(a.nil(), "cost exceeded".into()))
+        Err(EvalErr(a.nil(), EvalErrKind::CostExceeded))
     } else {
         Ok(())
     }
 }
+
+// defines each operator `$op` with the signature operators had before cost
+// schedules were introduced, forwarding to `$with_costs` with the default
+// (consensus) costs
+macro_rules! default_cost_ops {
+    ($($op:ident => $with_costs:ident,)*) => {
+        $(
+            #[doc = concat!("`", stringify!($with_costs), "()` with `CostSchedule::DEFAULT`")]
+            pub fn $op(
+                a: &mut $crate::allocator::Allocator,
+                input: $crate::allocator::NodePtr,
+                max_cost: $crate::cost::Cost,
+            ) -> $crate::reduction::Response {
+                $with_costs(a, input, max_cost, &$crate::cost::CostSchedule::DEFAULT)
+            }
+        )*
+    };
+}
+
+pub(crate) use default_cost_ops;
+
+// defines the CostSchedule struct, its default values and the mapping between
+// field names and the names used in schedule files
+macro_rules! cost_schedule {
+    ($($(#[$attr:meta])* $name:ident: $value:expr,)*) => {
+        /// All the costs charged by the interpreter and the built-in operators.
+        /// `CostSchedule::DEFAULT` holds the costs used in consensus. A dialect
+        /// can carry an alternative schedule (see `Dialect::cost_schedule()`),
+        /// e.g. one loaded from a file with `CostSchedule::from_file()`, to
+        /// evaluate proposed changes to the costs.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct CostSchedule {
+            /// identifies the schedule. The default (consensus) schedule is
+            /// version 1
+            pub version: u32,
+            $($(#[$attr])* pub $name: Cost,)*
+        }
+
+        impl CostSchedule {
+            pub const DEFAULT: CostSchedule = CostSchedule {
+                version: 1,
+                $($name: $value,)*
+            };
+
+            /// returns all costs in the schedule, along with their names
+            pub fn costs(&self) -> Vec<(&'static str, Cost)> {
+                vec![$((stringify!($name), self.$name),)*]
+            }
+
+            /// look up a cost by its name, as used in schedule files
+            pub fn cost_mut(&mut self, name: &str) -> Option<&mut Cost> {
+                match name {
+                    $(stringify!($name) => Some(&mut self.$name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+cost_schedule! {
+    // the interpreter
+
+    /// lowered from 46
+    quote_cost: 20,
+    /// lowered from 138
+    apply_cost: 90,
+    /// the cost of entering a softfork guard
+    guard_cost: 140,
+    /// mandatory base cost for every operator we execute
+    op_cost: 1,
+    /// lowered from measured 147 per bit. It doesn't seem to take this long
+    /// in practice
+    traverse_base_cost: 40,
+    traverse_cost_per_zero_byte: 4,
+    traverse_cost_per_bit: 4,
+    /// charged per byte of atoms allocated by operators
+    malloc_cost_per_byte: 10,
+
+    // core operators
+
+    first_cost: 30,
+    if_cost: 33,
+    /// lowered from 245. It only allocates a pair, which is small
+    cons_cost: 50,
+    /// lowered from 77 since it doesn't allocate anything and it should be
+    /// the same as first
+    rest_cost: 30,
+    listp_cost: 19,
+    eq_base_cost: 117,
+    eq_cost_per_byte: 1,
+
+    // more operators
+
+    arith_base_cost: 99,
+    arith_cost_per_arg: 320,
+    arith_cost_per_byte: 3,
+
+    log_base_cost: 100,
+    log_cost_per_arg: 264,
+    log_cost_per_byte: 3,
+
+    lognot_base_cost: 331,
+    lognot_cost_per_byte: 3,
+
+    mul_base_cost: 92,
+    mul_cost_per_op: 885,
+    mul_linear_cost_per_byte: 6,
+    /// must not be zero
+    mul_square_cost_per_byte_divider: 128,
+
+    gr_base_cost: 498,
+    gr_cost_per_byte: 2,
+
+    grs_base_cost: 117,
+    grs_cost_per_byte: 1,
+
+    strlen_base_cost: 173,
+    strlen_cost_per_byte: 1,
+
+    concat_base_cost: 142,
+    concat_cost_per_arg: 135,
+    concat_cost_per_byte: 3,
+
+    divmod_base_cost: 1116,
+    divmod_cost_per_byte: 6,
+
+    div_base_cost: 988,
+    div_cost_per_byte: 4,
+
+    sha256_base_cost: 87,
+    sha256_cost_per_arg: 134,
+    sha256_cost_per_byte: 2,
+
+    ashift_base_cost: 596,
+    ashift_cost_per_byte: 3,
+
+    lshift_base_cost: 277,
+    lshift_cost_per_byte: 3,
+
+    bool_base_cost: 200,
+    bool_cost_per_arg: 300,
+
+    // Raspberry PI 4 is about 7.679960 / 1.201742 = 6.39 times slower
+    // in the point_add benchmark
+
+    /// increased from 31592 to better model Raspberry PI
+    point_add_base_cost: 101094,
+    /// increased from 419994 to better model Raspberry PI
+    point_add_cost_per_arg: 1343980,
+
+    // Raspberry PI 4 is about 2.833543 / 0.447859 = 6.32686 times slower
+    // in the pubkey benchmark
+
+    /// increased from 419535 to better model Raspberry PI
+    pubkey_base_cost: 1325730,
+    /// increased from 12 to closer model Raspberry PI
+    pubkey_cost_per_byte: 38,
+
+    /// the cost of the coinid operator. It's the cost of sha256 of the same
+    /// input, minus 153 as a discount, to incentivize using this operator
+    /// rather than "naked" sha256
+    coinid_cost: 87 + 134 * 3 + 2 * (32 + 32 + 8) - 153,
+
+    modpow_base_cost: 17000,
+    modpow_cost_per_byte_base_value: 38,
+    /// the cost for exponent and modulus scale by the square of the size of
+    /// the respective operands
+    modpow_cost_per_byte_exponent: 3,
+    modpow_cost_per_byte_mod: 21,
+
+    // BLS operators
+
+    /// the same cost as point_add (aka g1_add)
+    bls_g1_subtract_base_cost: 101094,
+    bls_g1_subtract_cost_per_arg: 1343980,
+
+    bls_g1_multiply_base_cost: 705500,
+    bls_g1_multiply_cost_per_byte: 10,
+
+    /// this is the same cost as XORing the top bit (minus the heap allocation
+    /// of the return value, which the operator is adding back)
+    bls_g1_negate_base_cost: 1396 - 480,
+
+    /// g2_add and g2_subtract have the same cost
+    bls_g2_add_base_cost: 80000,
+    bls_g2_add_cost_per_arg: 1950000,
+    bls_g2_subtract_base_cost: 80000,
+    bls_g2_subtract_cost_per_arg: 1950000,
+
+    bls_g2_multiply_base_cost: 2100000,
+    bls_g2_multiply_cost_per_byte: 5,
+
+    /// this is the same cost as XORing the top bit (minus the heap allocation
+    /// of the return value, which the operator is adding back)
+    bls_g2_negate_base_cost: 2164 - 960,
+
+    bls_map_to_g1_base_cost: 195000,
+    bls_map_to_g1_cost_per_byte: 4,
+    bls_map_to_g1_cost_per_dst_byte: 4,
+
+    bls_map_to_g2_base_cost: 815000,
+    bls_map_to_g2_cost_per_byte: 4,
+    bls_map_to_g2_cost_per_dst_byte: 4,
+
+    bls_pairing_base_cost: 3000000,
+    bls_pairing_cost_per_arg: 1200000,
+
+    // secp operators
+
+    secp256r1_verify_cost: 1850000,
+    secp256k1_verify_cost: 1300000,
+}
+
+impl Default for CostSchedule {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[derive(Debug)]
+pub enum CostScheduleError {
+    Io(io::Error),
+    MissingVersion,
+    /// the line (1-based) isn't a name followed by a value
+    InvalidLine(usize),
+    /// the value on the line (1-based) isn't a valid integer
+    InvalidValue(usize),
+    UnknownCost(String),
+    ZeroDivider,
+}
+
+impl fmt::Display for CostScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read cost schedule: {e}"),
+            Self::MissingVersion => write!(f, "cost schedule is missing version"),
+            Self::InvalidLine(line) => write!(f, "invalid cost schedule entry on line {line}"),
+            Self::InvalidValue(line) => write!(f, "invalid cost on line {line}"),
+            Self::UnknownCost(name) => write!(f, "unknown cost \"{name}\""),
+            Self::ZeroDivider => write!(f, "mul_square_cost_per_byte_divider must not be 0"),
+        }
+    }
+}
+
+impl std::error::Error for CostScheduleError {}
+
+impl From<io::Error> for CostScheduleError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl CostSchedule {
+    /// load a cost schedule from a file. See `from_str()` for the format
+    pub fn from_file(path: impl AsRef<Path>) -> Result<CostSchedule, CostScheduleError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// checks that the operators can use these costs. The only restriction
+    /// is that `mul_square_cost_per_byte_divider` is used as a divisor, so it
+    /// can't be 0
+    pub fn validate(&self) -> Result<(), CostScheduleError> {
+        if self.mul_square_cost_per_byte_divider == 0 {
+            return Err(CostScheduleError::ZeroDivider);
+        }
+        Ok(())
+    }
+}
+
+/// The schedule file format is one cost per line, as the name followed by the
+/// value, separated by whitespace. A `version` line is required. Costs that
+/// aren't listed keep their default value. Empty lines and lines starting with
+/// `;` are ignored. This is the format produced by the `Display` implementation.
+impl FromStr for CostSchedule {
+    type Err = CostScheduleError;
+
+    fn from_str(s: &str) -> Result<CostSchedule, CostScheduleError> {
+        let mut ret = CostSchedule::DEFAULT;
+        let mut version = None;
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let Some((name, value)) = line.split_once(char::is_whitespace) else {
+                return Err(CostScheduleError::InvalidLine(idx + 1));
+            };
+            let value = value.trim();
+            if name == "version" {
+                let v = value
+                    .parse()
+                    .map_err(|_| CostScheduleError::InvalidValue(idx + 1))?;
+                version = Some(v);
+                continue;
+            }
+            let Some(cost) = ret.cost_mut(name) else {
+                return Err(CostScheduleError::UnknownCost(name.to_string()));
+            };
+            *cost = value
+                .parse()
+                .map_err(|_| CostScheduleError::InvalidValue(idx + 1))?;
+        }
+        ret.version = version.ok_or(CostScheduleError::MissingVersion)?;
+        ret.validate()?;
+        Ok(ret)
+    }
+}
+
+impl fmt::Display for CostSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "version {}", self.version)?;
+        for (name, cost) in self.costs() {
+            writeln!(f, "{name} {cost}")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_default_schedule() {
+    let costs = CostSchedule::default();
+    assert_eq!(costs, CostSchedule::DEFAULT);
+    assert_eq!(costs.version, 1);
+    assert_eq!(costs.coinid_cost, 480);
+    assert_eq!(costs.bls_g1_negate_base_cost, 916);
+    assert_eq!(costs.bls_g2_negate_base_cost, 1204);
+    assert_eq!(costs.costs().len(), 80);
+}
+
+#[test]
+fn test_schedule_round_trip() {
+    let mut costs = CostSchedule::DEFAULT;
+    costs.version = 2;
+    costs.sha256_cost_per_byte = 3;
+    costs.secp256k1_verify_cost = 1000000;
+    let text = costs.to_string();
+    assert!(text.starts_with("version 2\nquote_cost 20\n"));
+    assert_eq!(text.parse::<CostSchedule>().unwrap(), costs);
+}
+
+#[test]
+fn test_parse_schedule() {
+    let costs: CostSchedule = "; a cheaper cons\n\nversion 3\n  cons_cost   40\n"
+        .parse()
+        .unwrap();
+    assert_eq!(costs.version, 3);
+    assert_eq!(costs.cons_cost, 40);
+    // everything else has the default value
+    assert_eq!(
+        CostSchedule {
+            version: 1,
+            cons_cost: 50,
+            ..costs
+        },
+        CostSchedule::DEFAULT
+    );
+}
+
+#[cfg(test)]
+use rstest::rstest;
+
+#[cfg(test)]
+#[rstest]
+#[case("", "cost schedule is missing version")]
+#[case("cons_cost 40", "cost schedule is missing version")]
+#[case("version 1\ncons_cost", "invalid cost schedule entry on line 2")]
+#[case("version 1\ncons_cost -1", "invalid cost on line 2")]
+#[case("version one", "invalid cost on line 1")]
+#[case("version 1\nfoobar_cost 1", "unknown cost \"foobar_cost\"")]
+#[case(
+    "version 1\nmul_square_cost_per_byte_divider 0",
+    "mul_square_cost_per_byte_divider must not be 0"
+)]
+fn test_parse_schedule_errors(#[case] text: &str, #[case] expected: &str) {
+    let e = text.parse::<CostSchedule>().unwrap_err();
+    assert_eq!(e.to_string(), expected);
+}
+
+#[test]
+fn test_validate_schedule() {
+    assert!(CostSchedule::DEFAULT.validate().is_ok());
+    let costs = CostSchedule {
+        mul_square_cost_per_byte_divider: 0,
+        ..CostSchedule::DEFAULT
+    };
+    assert_eq!(
+        costs.validate().unwrap_err().to_string(),
+        "mul_square_cost_per_byte_divider must not be 0"
+    );
+}
+
+#[test]
+fn test_dialect_invalid_schedule() {
+    use crate::chia_dialect::ChiaDialect;
+    use crate::runtime_dialect::RuntimeDialect;
+    let costs = CostSchedule {
+        mul_square_cost_per_byte_divider: 0,
+        ..CostSchedule::DEFAULT
+    };
+    assert!(ChiaDialect::new(0)
+        .with_cost_schedule(costs.clone())
+        .is_err());
+    assert!(RuntimeDialect::chia(0).with_cost_schedule(costs).is_err());
+    assert!(ChiaDialect::new(0)
+        .with_cost_schedule(CostSchedule::DEFAULT)
+        .is_ok());
+}
@@ -0,0 +1,216 @@
+// Currying binds arguments to a program (the "mod"), producing a new program
+// that, when run, calls the mod with the curried arguments followed by the
+// arguments it was called with:
+//
+//   (a (q . MOD) (c (q . ARG1) (c (q . ARG2) ... 1)))
+//
+// This uses the chia dialect's operators: a = 2, q = 1 and c = 4.
+
+use crate::allocator::{Allocator, NodePtr};
+use crate::reduction::EvalErr;
+use crate::tree_hash::{hash_pair, hash_small_atom, TreeHash};
+
+const Q_KW: u32 = 1;
+const A_KW: u32 = 2;
+const C_KW: u32 = 4;
+
+// make a proper list of the given items
+fn new_list(a: &mut Allocator, items: &[NodePtr]) -> Result<NodePtr, EvalErr> {
+    let mut ret = a.nil();
+    for item in items.iter().rev() {
+        ret = a.new_pair(*item, ret)?;
+    }
+    Ok(ret)
+}
+
+/// Build `(c (q . value) rest)`, i.e. prepend `value` to the curried
+/// arguments `rest`. The curried arguments are terminated by `1`
+pub fn new_curried_arg(
+    a: &mut Allocator,
+    value: NodePtr,
+    rest: NodePtr,
+) -> Result<NodePtr, EvalErr> {
+    let q = a.new_small_number(Q_KW)?;
+    let c = a.new_small_number(C_KW)?;
+    let quoted_value = a.new_pair(q, value)?;
+    new_list(a, &[c, quoted_value, rest])
+}
+
+/// Curry `args` into `program`, returning `(a (q . program) (c (q . arg1) ... 1))`
+pub fn curry(a: &mut Allocator, program: NodePtr, args: &[NodePtr]) -> Result<NodePtr, EvalErr> {
+    let mut fixed_args = a.one();
+    for arg in args.iter().rev() {
+        fixed_args = new_curried_arg(a, *arg, fixed_args)?;
+    }
+    let q = a.new_small_number(Q_KW)?;
+    let apply = a.new_small_number(A_KW)?;
+    let quoted_program = a.new_pair(q, program)?;
+    new_list(a, &[apply, quoted_program, fixed_args])
+}
+
+// match (OP (q . VALUE) REST) and return VALUE and REST. `op` is the expected
+// operator
+fn match_quoted_call(a: &Allocator, node: NodePtr, op: u32) -> Option<(NodePtr, NodePtr)> {
+    let (first, rest) = a.next(node)?;
+    if a.small_number(first) != Some(op) {
+        return None;
+    }
+    let (quoted, rest) = a.next(rest)?;
+    let (q, value) = a.next(quoted)?;
+    if a.small_number(q) != Some(Q_KW) {
+        return None;
+    }
+    let (rest, terminator) = a.next(rest)?;
+    if a.small_number(terminator) != Some(0) {
+        return None;
+    }
+    Some((value, rest))
+}
+
+/// The inverse of `new_curried_arg()`. If `node` is `(c (q . value) rest)`,
+/// return `value` and `rest`
+pub fn match_curried_arg(a: &Allocator, node: NodePtr) -> Option<(NodePtr, NodePtr)> {
+    match_quoted_call(a, node, C_KW)
+}
+
+/// The inverse of `curry()`. If `program` is a curried program, return the
+/// mod and the curried arguments. Otherwise return `None`. A curried program
+/// with no arguments returns an empty list of arguments.
+pub fn uncurry(a: &Allocator, program: NodePtr) -> Option<(NodePtr, Vec<NodePtr>)> {
+    let (mod_node, mut fixed_args) = match_quoted_call(a, program, A_KW)?;
+    let mut args = Vec::new();
+    while a.small_number(fixed_args) != Some(1) {
+        let (arg, rest) = match_curried_arg(a, fixed_args)?;
+        args.push(arg);
+        fixed_args = rest;
+    }
+    Some((mod_node, args))
+}
+
+/// Compute the tree hash of the curried arguments, i.e. the tree hash of
+/// `(c (q . arg1) (c (q . arg2) ... 1))`, from the tree hashes of the
+/// arguments.
+pub fn curried_values_tree_hash(arg_hashes: &[TreeHash]) -> TreeHash {
+    let q_hash = hash_small_atom(Q_KW);
+    let c_hash = hash_small_atom(C_KW);
+    let nil_hash = hash_small_atom(0);
+
+    let mut ret = hash_small_atom(1);
+    for arg_hash in arg_hashes.iter().rev() {
+        let quoted_arg = hash_pair(&q_hash, arg_hash);
+        let rest = hash_pair(&ret, &nil_hash);
+        ret = hash_pair(&c_hash, &hash_pair(&quoted_arg, &rest));
+    }
+    ret
+}
+
+/// Compute the tree hash of a curried program, from the tree hash of the mod
+/// and the tree hashes of the arguments, without building the tree. This is
+/// the same as `tree_hash(a, curry(a, mod, args))`.
+pub fn curry_tree_hash(mod_hash: &TreeHash, arg_hashes: &[TreeHash]) -> TreeHash {
+    let nil_hash = hash_small_atom(0);
+    let quoted_mod = hash_pair(&hash_small_atom(Q_KW), mod_hash);
+    let curried_values = curried_values_tree_hash(arg_hashes);
+    let rest = hash_pair(&curried_values, &nil_hash);
+    hash_pair(&hash_small_atom(A_KW), &hash_pair(&quoted_mod, &rest))
+}
+
+#[cfg(test)]
+use crate::serde::node_to_bytes;
+
+#[cfg(test)]
+use crate::tree_hash::tree_hash;
+
+#[cfg(test)]
+use crate::test_ops::{from_hex, node_eq};
+
+#[test]
+fn test_curry() {
+    let mut a = Allocator::new();
+    // (+ 2 5)
+    let program = from_hex(&mut a, "ff10ff02ff0580");
+    let one = a.one();
+    let curried = curry(&mut a, program, &[one]).unwrap();
+    // (a (q . (+ 2 5)) (c (q . 1) 1))
+    assert_eq!(
+        hex::encode(node_to_bytes(&a, curried).unwrap()),
+        "ff02ffff01ff10ff02ff0580ffff04ffff0101ff018080"
+    );
+
+    // currying no arguments
+    let curried = curry(&mut a, program, &[]).unwrap();
+    // (a (q . (+ 2 5)) 1)
+    assert_eq!(
+        hex::encode(node_to_bytes(&a, curried).unwrap()),
+        "ff02ffff01ff10ff02ff0580ff0180"
+    );
+}
+
+#[test]
+fn test_curry_uncurry() {
+    let mut a = Allocator::new();
+    let program = from_hex(&mut a, "ff10ff02ff0580");
+    for num_args in 0..5 {
+        let args: Vec<NodePtr> = (0..num_args)
+            .map(|i| {
+                let atom = a.new_atom(&[0xff, i]).unwrap();
+                a.new_pair(atom, atom).unwrap()
+            })
+            .collect();
+        let curried = curry(&mut a, program, &args).unwrap();
+        let (mod_node, uncurried_args) = uncurry(&a, curried).unwrap();
+        assert!(node_eq(&a, mod_node, program));
+        assert_eq!(uncurried_args.len(), args.len());
+        for (lhs, rhs) in args.iter().zip(uncurried_args) {
+            assert!(node_eq(&a, *lhs, rhs));
+        }
+
+        let arg_hashes: Vec<TreeHash> = args.iter().map(|arg| tree_hash(&a, *arg)).collect();
+        assert_eq!(
+            curry_tree_hash(&tree_hash(&a, program), &arg_hashes),
+            tree_hash(&a, curried)
+        );
+    }
+}
+
+#[test]
+fn test_curry_tree_hash() {
+    // these values are from the python curry_and_treehash tests
+    let mut a = Allocator::new();
+    let program = from_hex(&mut a, "ff10ff02ff0580");
+    let mod_hash = tree_hash(&a, program);
+    let q = a.one();
+    let quoted_mod = a.new_pair(q, program).unwrap();
+    assert_eq!(
+        hex::encode(tree_hash(&a, quoted_mod)),
+        "9f487f9078d4b215e0cbe2cbdd21215ad6ed8e894ae00d616751e0efdccb25a9"
+    );
+    assert_eq!(curried_values_tree_hash(&[]), hash_small_atom(1));
+    let curried = curry(&mut a, program, &[]).unwrap();
+    assert_eq!(curry_tree_hash(&mod_hash, &[]), tree_hash(&a, curried));
+}
+
+#[cfg(test)]
+use rstest::rstest;
+
+#[cfg(test)]
+#[rstest]
+// (+ 2 5)
+#[case("ff10ff02ff0580")]
+// 1
+#[case("01")]
+// (a (q . 1) (c (q . 1) (q . 1)) (q . 0x1337))
+#[case("ff02ffff0101ffff04ffff0101ffff010180ffff0182133780")]
+// (a 1 (c (q . 1) (q . 1)))
+#[case("ff02ff01ffff04ffff0101ffff01018080")]
+// (a (q . 1) (c (q . 1) (q . 1) (q . 4919)))
+#[case("ff02ffff0101ffff04ffff0101ffff0101ffff018213378080")]
+// (a (q . 1) (c (q . 1) 2))
+#[case("ff02ffff0101ffff04ffff0101ff028080")]
+// (0x0002 (q . 1) 1)
+#[case("ff820002ffff0101ff0180")]
+fn test_uncurry_not_curried(#[case] program: &str) {
+    let mut a = Allocator::new();
+    let program = from_hex(&mut a, program);
+    assert_eq!(uncurry(&a, program), None);
+}
@@ -1,12 +1,34 @@
 use crate::allocator::{Allocator, NodePtr};
-use crate::cost::Cost;
+use crate::cost::{Cost, CostSchedule};
 use crate::reduction::Response;
 
-#[repr(u32)]
-#[derive(Clone, Copy, Eq, PartialEq)]
+/// The set of operators enabled inside a softfork guard, as returned by
+/// `Dialect::softfork_extension()`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum OperatorSet {
     Default,
     BLS,
+    /// An extension defined outside of this crate, by its extension id. See
+    /// `ExtensionDialect`
+    Extension(u32),
+}
+
+/// A native operator. This is implemented for all functions and closures with
+/// the same signature as the built-in operators, i.e. taking the allocator,
+/// the argument list and the max cost and returning the cost and result.
+/// Operators defined outside of this crate fail with
+/// `EvalErrKind::OperatorFailed`.
+pub trait Operator {
+    fn invoke(&self, allocator: &mut Allocator, args: NodePtr, max_cost: Cost) -> Response;
+}
+
+impl<F> Operator for F
+where
+    F: Fn(&mut Allocator, NodePtr, Cost) -> Response,
+{
+    fn invoke(&self, allocator: &mut Allocator, args: NodePtr, max_cost: Cost) -> Response {
+        self(allocator, args, max_cost)
+    }
 }
 
 pub trait Dialect {
@@ -23,4 +45,9 @@ pub trait Dialect {
         extensions: OperatorSet,
     ) -> Response;
     fn allow_unknown_ops(&self) -> bool;
+
+    /// the costs charged by the interpreter and the built-in operators
+    fn cost_schedule(&self) -> &CostSchedule {
+        &CostSchedule::DEFAULT
+    }
 }
@@ -1,6 +1,6 @@
 use crate::allocator::NodePtr;
-use crate::reduction::EvalErr;
+use crate::reduction::{EvalErr, EvalErrKind};
 
-pub fn err<T>(node: NodePtr, msg: &str) -> Result<T, EvalErr> {
-    Err(EvalErr(node, msg.into()))
+pub fn err<T>(node: NodePtr, kind: EvalErrKind) -> Result<T, EvalErr> {
+    Err(EvalErr(node, kind))
 }
@@ -0,0 +1,194 @@
+use crate::allocator::{Allocator, NodePtr};
+use crate::cost::{Cost, CostSchedule};
+use crate::dialect::{Dialect, Operator, OperatorSet};
+use crate::reduction::Response;
+use std::collections::HashMap;
+
+/// A dialect that adds softfork extensions on top of a base dialect. Each
+/// extension id has its own table of operators, which are available inside
+/// softfork guards specifying that extension id. Operators not in the
+/// extension's table are passed on to the base dialect, with the operator set
+/// the base dialect assigns to the extension id (if any).
+///
+/// This makes it possible to prototype new softfork extensions without
+/// modifying the base dialect.
+pub struct ExtensionDialect<D> {
+    base: D,
+    extensions: HashMap<u32, HashMap<Vec<u8>, Box<dyn Operator>>>,
+}
+
+impl<D: Dialect> ExtensionDialect<D> {
+    pub fn new(base: D) -> Self {
+        Self {
+            base,
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// declare the extension id `ext`, without adding any operators to it
+    pub fn add_extension(&mut self, ext: u32) {
+        self.extensions.entry(ext).or_default();
+    }
+
+    /// add the operator `opcode` to the extension `ext`. This replaces any
+    /// existing operator with the same opcode in the extension
+    pub fn add_operator(&mut self, ext: u32, opcode: &[u8], op: impl Operator + 'static) {
+        self.extensions
+            .entry(ext)
+            .or_default()
+            .insert(opcode.to_vec(), Box::new(op));
+    }
+
+    pub fn with_operator(mut self, ext: u32, opcode: &[u8], op: impl Operator + 'static) -> Self {
+        self.add_operator(ext, opcode, op);
+        self
+    }
+
+    pub fn base(&self) -> &D {
+        &self.base
+    }
+}
+
+impl<D: Dialect> Dialect for ExtensionDialect<D> {
+    fn op(
+        &self,
+        allocator: &mut Allocator,
+        o: NodePtr,
+        argument_list: NodePtr,
+        max_cost: Cost,
+        extension: OperatorSet,
+    ) -> Response {
+        let OperatorSet::Extension(ext) = extension else {
+            return self
+                .base
+                .op(allocator, o, argument_list, max_cost, extension);
+        };
+        if let Some(op) = self
+            .extensions
+            .get(&ext)
+            .and_then(|ops| ops.get(allocator.atom(o).as_ref()))
+        {
+            return op.invoke(allocator, argument_list, max_cost);
+        }
+        let extension = self.base.softfork_extension(ext);
+        self.base
+            .op(allocator, o, argument_list, max_cost, extension)
+    }
+
+    fn quote_kw(&self) -> u32 {
+        self.base.quote_kw()
+    }
+
+    fn apply_kw(&self) -> u32 {
+        self.base.apply_kw()
+    }
+
+    fn softfork_kw(&self) -> u32 {
+        self.base.softfork_kw()
+    }
+
+    fn softfork_extension(&self, ext: u32) -> OperatorSet {
+        if self.extensions.contains_key(&ext) {
+            OperatorSet::Extension(ext)
+        } else {
+            self.base.softfork_extension(ext)
+        }
+    }
+
+    fn allow_unknown_ops(&self) -> bool {
+        self.base.allow_unknown_ops()
+    }
+
+    fn cost_schedule(&self) -> &CostSchedule {
+        self.base.cost_schedule()
+    }
+}
+
+#[cfg(test)]
+use crate::chia_dialect::{ChiaDialect, NO_UNKNOWN_OPS};
+#[cfg(test)]
+use crate::reduction::{EvalErrKind, Reduction};
+#[cfg(test)]
+use crate::run_program::run_program;
+#[cfg(test)]
+use crate::test_ops::parse_exp;
+
+// returns 42
+#[cfg(test)]
+fn op_answer(a: &mut Allocator, _args: NodePtr, _max_cost: Cost) -> Response {
+    Ok(Reduction(100, a.new_small_number(42)?))
+}
+
+// returns its first argument, plus one
+#[cfg(test)]
+fn op_inc(a: &mut Allocator, args: NodePtr, _max_cost: Cost) -> Response {
+    let n = crate::op_utils::int_atom(a, crate::op_utils::first(a, args)?, "inc")?;
+    Ok(Reduction(200, a.new_number(n.0 + 1)?))
+}
+
+#[cfg(test)]
+fn dialect() -> ExtensionDialect<ChiaDialect> {
+    ExtensionDialect::new(ChiaDialect::new(NO_UNKNOWN_OPS))
+        .with_operator(1, &[0x40], op_answer)
+        .with_operator(2, &[0x41], op_inc)
+}
+
+#[cfg(test)]
+fn run(prg: &str) -> Response {
+    let mut a = Allocator::new();
+    let program = parse_exp(&mut a, prg).0;
+    let args = a.nil();
+    run_program(&mut a, &dialect(), program, args, 0)
+}
+
+#[test]
+fn test_softfork_extension() {
+    let d = dialect();
+    assert_eq!(d.softfork_extension(0), OperatorSet::BLS);
+    assert_eq!(d.softfork_extension(1), OperatorSet::Extension(1));
+    assert_eq!(d.softfork_extension(2), OperatorSet::Extension(2));
+    assert_eq!(d.softfork_extension(3), OperatorSet::Default);
+}
+
+#[test]
+fn test_extension_operator() {
+    // the operator is available inside the guard
+    let r = run("(softfork (q . 610) (q . 1) (q . (a (i (= (0x40) (q . 42)) (q . 0) (q x)) (q . ()))) (q . ()))");
+    assert_eq!(r.unwrap().0, 691);
+
+    // but not outside of it, nor in other extensions
+    let r = run("(0x40)");
+    assert_eq!(r.unwrap_err().1, EvalErrKind::UnimplementedOperator);
+    let r = run("(softfork (q . 1000) (q . 2) (q . (0x40)) (q . ()))");
+    assert_eq!(r.unwrap_err().1, EvalErrKind::UnimplementedOperator);
+}
+
+#[test]
+fn test_nested_guards() {
+    // the inner guard enables extension 2, the outer one extension 1. Once
+    // the inner guard exits, extension 1 is in effect again
+    let r = run("(softfork (q . 1503) (q . 1) (q . (a (i (= (r (c (softfork (q . 730) (q . 2) (q . (a (i (= (0x41 (q . 1)) (q . 2)) (q . 0) (q x)) (q . ()))) (q . ())) (0x40))) (q . 42)) (q . 0) (q x)) (q . ()))) (q . ()))");
+    assert_eq!(r.unwrap().0, 1584);
+
+    // extension 1 operators are not available in the inner guard
+    let r = run("(softfork (q . 100000) (q . 1) (q . (softfork (q . 1000) (q . 2) (q . (0x40)) (q . ()))) (q . ()))");
+    assert_eq!(r.unwrap_err().1, EvalErrKind::UnimplementedOperator);
+}
+
+#[test]
+fn test_extend_base_extension() {
+    // adding operators to extension 0 keeps the BLS operators of the base
+    // dialect available
+    let d = dialect().with_operator(0, &[0x40], op_answer);
+    assert_eq!(d.softfork_extension(0), OperatorSet::Extension(0));
+
+    let mut a = Allocator::new();
+    let program = parse_exp(
+        &mut a,
+        "(softfork (q . 17425) (q . 0) (q . (c (0x40) (0x3c (q . 2) (q . 3) (q . 5)))) (q . ()))",
+    )
+    .0;
+    let args = a.nil();
+    let r = run_program(&mut a, &d, program, args, 0);
+    assert_eq!(r.unwrap().0, 17506);
+}
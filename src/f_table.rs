@@ -1,91 +1,266 @@
 use std::collections::HashMap;
+use std::fmt;
 
 use crate::allocator::{Allocator, NodePtr};
 use crate::bls_ops::{
-    op_bls_g1_multiply, op_bls_g1_negate, op_bls_g1_subtract, op_bls_g2_add, op_bls_g2_multiply,
-    op_bls_g2_negate, op_bls_g2_subtract, op_bls_map_to_g1, op_bls_map_to_g2,
-    op_bls_pairing_identity, op_bls_verify,
+    op_bls_g1_multiply, op_bls_g1_multiply_with_costs, op_bls_g1_negate,
+    op_bls_g1_negate_with_costs, op_bls_g1_subtract, op_bls_g1_subtract_with_costs, op_bls_g2_add,
+    op_bls_g2_add_with_costs, op_bls_g2_multiply, op_bls_g2_multiply_with_costs, op_bls_g2_negate,
+    op_bls_g2_negate_with_costs, op_bls_g2_subtract, op_bls_g2_subtract_with_costs,
+    op_bls_map_to_g1, op_bls_map_to_g1_with_costs, op_bls_map_to_g2, op_bls_map_to_g2_with_costs,
+    op_bls_pairing_identity, op_bls_pairing_identity_with_costs, op_bls_verify,
+    op_bls_verify_with_costs,
 };
-use crate::core_ops::{op_cons, op_eq, op_first, op_if, op_listp, op_raise, op_rest};
-use crate::cost::Cost;
+use crate::core_ops::{
+    op_cons, op_cons_with_costs, op_eq, op_eq_with_costs, op_first, op_first_with_costs, op_if,
+    op_if_with_costs, op_listp, op_listp_with_costs, op_raise, op_raise_with_costs, op_rest,
+    op_rest_with_costs,
+};
+use crate::cost::{Cost, CostSchedule};
 use crate::more_ops::{
-    op_add, op_all, op_any, op_ash, op_concat, op_div, op_divmod, op_gr, op_gr_bytes, op_logand,
-    op_logior, op_lognot, op_logxor, op_lsh, op_mod, op_modpow, op_multiply, op_not, op_point_add,
-    op_pubkey_for_exp, op_sha256, op_strlen, op_substr, op_subtract,
+    op_add, op_add_with_costs, op_all, op_all_with_costs, op_any, op_any_with_costs, op_ash,
+    op_ash_with_costs, op_coinid, op_coinid_with_costs, op_concat, op_concat_with_costs, op_div,
+    op_div_fixed, op_div_fixed_with_costs, op_div_with_costs, op_divmod, op_divmod_with_costs,
+    op_gr, op_gr_bytes, op_gr_bytes_with_costs, op_gr_with_costs, op_logand, op_logand_with_costs,
+    op_logior, op_logior_with_costs, op_lognot, op_lognot_with_costs, op_logxor,
+    op_logxor_with_costs, op_lsh, op_lsh_with_costs, op_mod, op_mod_with_costs, op_modpow,
+    op_modpow_with_costs, op_multiply, op_multiply_with_costs, op_not, op_not_with_costs,
+    op_point_add, op_point_add_with_costs, op_pubkey_for_exp, op_pubkey_for_exp_with_costs,
+    op_sha256, op_sha256_with_costs, op_strlen, op_strlen_with_costs, op_substr,
+    op_substr_with_costs, op_subtract, op_subtract_with_costs,
 };
 use crate::reduction::Response;
-use crate::secp_ops::{op_secp256k1_verify, op_secp256r1_verify};
+use crate::secp_ops::{
+    op_secp256k1_verify, op_secp256k1_verify_with_costs, op_secp256r1_verify,
+    op_secp256r1_verify_with_costs,
+};
 
-type OpFn = fn(&mut Allocator, NodePtr, Cost) -> Response;
+/// A native operator, charging the default (consensus) costs
+pub type OpFn = fn(&mut Allocator, NodePtr, Cost) -> Response;
+
+/// A native operator that charges the costs from a `CostSchedule`, as used
+/// by the dialects
+pub type OpFnWithCosts = fn(&mut Allocator, NodePtr, Cost, &CostSchedule) -> Response;
 
 pub type FLookup = [Option<OpFn>; 256];
 
+// the native operators by name, both with the default costs and with a
+// `CostSchedule`
+const NATIVE_OPS: [(&str, OpFn, OpFnWithCosts); 46] = [
+    ("op_if", op_if, op_if_with_costs),
+    ("op_cons", op_cons, op_cons_with_costs),
+    ("op_first", op_first, op_first_with_costs),
+    ("op_rest", op_rest, op_rest_with_costs),
+    ("op_listp", op_listp, op_listp_with_costs),
+    ("op_raise", op_raise, op_raise_with_costs),
+    ("op_eq", op_eq, op_eq_with_costs),
+    ("op_sha256", op_sha256, op_sha256_with_costs),
+    ("op_add", op_add, op_add_with_costs),
+    ("op_subtract", op_subtract, op_subtract_with_costs),
+    ("op_multiply", op_multiply, op_multiply_with_costs),
+    ("op_modpow", op_modpow, op_modpow_with_costs),
+    ("op_divmod", op_divmod, op_divmod_with_costs),
+    ("op_mod", op_mod, op_mod_with_costs),
+    ("op_substr", op_substr, op_substr_with_costs),
+    ("op_strlen", op_strlen, op_strlen_with_costs),
+    ("op_point_add", op_point_add, op_point_add_with_costs),
+    (
+        "op_pubkey_for_exp",
+        op_pubkey_for_exp,
+        op_pubkey_for_exp_with_costs,
+    ),
+    ("op_concat", op_concat, op_concat_with_costs),
+    ("op_gr", op_gr, op_gr_with_costs),
+    ("op_gr_bytes", op_gr_bytes, op_gr_bytes_with_costs),
+    ("op_logand", op_logand, op_logand_with_costs),
+    ("op_logior", op_logior, op_logior_with_costs),
+    ("op_logxor", op_logxor, op_logxor_with_costs),
+    ("op_lognot", op_lognot, op_lognot_with_costs),
+    ("op_ash", op_ash, op_ash_with_costs),
+    ("op_lsh", op_lsh, op_lsh_with_costs),
+    ("op_not", op_not, op_not_with_costs),
+    ("op_any", op_any, op_any_with_costs),
+    ("op_all", op_all, op_all_with_costs),
+    ("op_div", op_div, op_div_with_costs),
+    ("op_div_fixed", op_div_fixed, op_div_fixed_with_costs),
+    ("op_coinid", op_coinid, op_coinid_with_costs),
+    (
+        "op_g1_subtract",
+        op_bls_g1_subtract,
+        op_bls_g1_subtract_with_costs,
+    ),
+    (
+        "op_g1_multiply",
+        op_bls_g1_multiply,
+        op_bls_g1_multiply_with_costs,
+    ),
+    (
+        "op_g1_negate",
+        op_bls_g1_negate,
+        op_bls_g1_negate_with_costs,
+    ),
+    ("op_g2_add", op_bls_g2_add, op_bls_g2_add_with_costs),
+    (
+        "op_g2_subtract",
+        op_bls_g2_subtract,
+        op_bls_g2_subtract_with_costs,
+    ),
+    (
+        "op_g2_multiply",
+        op_bls_g2_multiply,
+        op_bls_g2_multiply_with_costs,
+    ),
+    (
+        "op_g2_negate",
+        op_bls_g2_negate,
+        op_bls_g2_negate_with_costs,
+    ),
+    ("op_g1_map", op_bls_map_to_g1, op_bls_map_to_g1_with_costs),
+    ("op_g2_map", op_bls_map_to_g2, op_bls_map_to_g2_with_costs),
+    (
+        "op_bls_pairing_identity",
+        op_bls_pairing_identity,
+        op_bls_pairing_identity_with_costs,
+    ),
+    ("op_bls_verify", op_bls_verify, op_bls_verify_with_costs),
+    (
+        "op_secp256k1_verify",
+        op_secp256k1_verify,
+        op_secp256k1_verify_with_costs,
+    ),
+    (
+        "op_secp256r1_verify",
+        op_secp256r1_verify,
+        op_secp256r1_verify_with_costs,
+    ),
+];
+
 pub fn opcode_by_name(name: &str) -> Option<OpFn> {
-    let opcode_lookup: [(OpFn, &str); 44] = [
-        (op_if, "op_if"),
-        (op_cons, "op_cons"),
-        (op_first, "op_first"),
-        (op_rest, "op_rest"),
-        (op_listp, "op_listp"),
-        (op_raise, "op_raise"),
-        (op_eq, "op_eq"),
-        (op_sha256, "op_sha256"),
-        (op_add, "op_add"),
-        (op_subtract, "op_subtract"),
-        (op_multiply, "op_multiply"),
-        (op_modpow, "op_modpow"),
-        (op_divmod, "op_divmod"),
-        (op_mod, "op_mod"),
-        (op_substr, "op_substr"),
-        (op_strlen, "op_strlen"),
-        (op_point_add, "op_point_add"),
-        (op_pubkey_for_exp, "op_pubkey_for_exp"),
-        (op_concat, "op_concat"),
-        (op_gr, "op_gr"),
-        (op_gr_bytes, "op_gr_bytes"),
-        (op_logand, "op_logand"),
-        (op_logior, "op_logior"),
-        (op_logxor, "op_logxor"),
-        (op_lognot, "op_lognot"),
-        (op_ash, "op_ash"),
-        (op_lsh, "op_lsh"),
-        (op_not, "op_not"),
-        (op_any, "op_any"),
-        (op_all, "op_all"),
-        (op_div, "op_div"),
-        (op_bls_g1_subtract, "op_g1_subtract"),
-        (op_bls_g1_multiply, "op_g1_multiply"),
-        (op_bls_g1_negate, "op_g1_negate"),
-        (op_bls_g2_add, "op_g2_add"),
-        (op_bls_g2_subtract, "op_g2_subtract"),
-        (op_bls_g2_multiply, "op_g2_multiply"),
-        (op_bls_g2_negate, "op_g2_negate"),
-        (op_bls_map_to_g1, "op_g1_map"),
-        (op_bls_map_to_g2, "op_g2_map"),
-        (op_bls_pairing_identity, "op_bls_pairing_identity"),
-        (op_bls_verify, "op_bls_verify"),
-        (op_secp256k1_verify, "op_secp256k1_verify"),
-        (op_secp256r1_verify, "op_secp256r1_verify"),
-    ];
-    let name: &[u8] = name.as_ref();
-    for (f, op) in opcode_lookup.iter() {
-        let pu8: &[u8] = op.as_ref();
-        if pu8 == name {
-            return Some(*f);
-        }
+    NATIVE_OPS
+        .iter()
+        .find(|(op, _, _)| *op == name)
+        .map(|(_, f, _)| *f)
+}
+
+pub fn opcode_with_costs_by_name(name: &str) -> Option<OpFnWithCosts> {
+    NATIVE_OPS
+        .iter()
+        .find(|(op, _, _)| *op == name)
+        .map(|(_, _, f)| *f)
+}
+
+/// The error returned when an operator name isn't known by `opcode_by_name()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownOperator(pub String);
+
+impl fmt::Display for UnknownOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "can't find native operator {}", self.0)
     }
-    None
 }
 
+impl std::error::Error for UnknownOperator {}
+
+/// Panics if an operator name isn't known by `opcode_by_name()`. Multi-byte
+/// opcodes can't be represented in an `FLookup`, and are left out.
+#[deprecated(note = "use OpTable::from_hashmap(), which supports multi-byte opcodes")]
 pub fn f_lookup_for_hashmap(opcode_lookup_by_name: HashMap<String, Vec<u8>>) -> FLookup {
     let mut f_lookup = [None; 256];
     for (name, idx) in opcode_lookup_by_name.iter() {
+        let Some(op) = opcode_by_name(name) else {
+            panic!("{}", UnknownOperator(name.clone()));
+        };
         if idx.len() == 1 {
-            let index = idx[0];
-            let op = opcode_by_name(name);
-            assert!(op.is_some(), "can't find native operator {name}");
-            f_lookup[index as usize] = op;
+            f_lookup[idx[0] as usize] = Some(op);
         }
     }
     f_lookup
 }
+
+/// Native operators by opcode. Unlike `FLookup`, this also supports
+/// multi-byte opcodes, like the ones used by the secp operators.
+#[derive(Clone)]
+pub struct OpTable {
+    f_lookup: [Option<OpFnWithCosts>; 256],
+    multi_byte: HashMap<Vec<u8>, OpFnWithCosts>,
+}
+
+impl OpTable {
+    pub fn from_hashmap(
+        opcode_lookup_by_name: HashMap<String, Vec<u8>>,
+    ) -> Result<OpTable, UnknownOperator> {
+        let mut ret = OpTable {
+            f_lookup: [None; 256],
+            multi_byte: HashMap::new(),
+        };
+        for (name, idx) in opcode_lookup_by_name.iter() {
+            let Some(op) = opcode_with_costs_by_name(name) else {
+                return Err(UnknownOperator(name.clone()));
+            };
+            if idx.len() == 1 {
+                ret.f_lookup[idx[0] as usize] = Some(op);
+            } else {
+                ret.multi_byte.insert(idx.clone(), op);
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn get(&self, opcode: &[u8]) -> Option<OpFnWithCosts> {
+        if opcode.len() == 1 {
+            self.f_lookup[opcode[0] as usize]
+        } else {
+            self.multi_byte.get(opcode).copied()
+        }
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_f_lookup_for_hashmap() {
+    let mut ops = HashMap::new();
+    ops.insert("op_cons".to_string(), vec![4]);
+    ops.insert(
+        "op_secp256k1_verify".to_string(),
+        vec![0x13, 0xd6, 0x1f, 0x00],
+    );
+    let f_lookup = f_lookup_for_hashmap(ops);
+    assert!(f_lookup[4].is_some_and(|op| std::ptr::fn_addr_eq(op, op_cons as OpFn)));
+    assert_eq!(f_lookup.iter().filter(|op| op.is_some()).count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "can't find native operator op_foobar")]
+#[allow(deprecated)]
+fn test_f_lookup_for_hashmap_unknown() {
+    let mut ops = HashMap::new();
+    ops.insert("op_foobar".to_string(), vec![4]);
+    f_lookup_for_hashmap(ops);
+}
+
+#[test]
+fn test_extreme_cost_schedule() {
+    use crate::cost::{Cost, CostSchedule};
+    use crate::reduction::Reduction;
+
+    let mut costs = CostSchedule::DEFAULT;
+    for (name, _) in CostSchedule::DEFAULT.costs() {
+        *costs.cost_mut(name).unwrap() = Cost::MAX;
+    }
+
+    let mut a = Allocator::new();
+    let arg = a.new_atom(&[0x7f; 64]).unwrap();
+    let mut args = a.nil();
+    for _ in 0..3 {
+        args = a.new_pair(arg, args).unwrap();
+    }
+    for (name, _, op) in NATIVE_OPS {
+        for max_cost in [Cost::MAX, 11_000_000_000] {
+            // most operators will reject these arguments. The point is that
+            // computing the cost doesn't overflow
+            if let Ok(Reduction(cost, _)) = op(&mut a, args, max_cost, &costs) {
+                assert_eq!(cost, Cost::MAX, "{name}");
+            }
+        }
+    }
+}
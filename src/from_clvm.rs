@@ -0,0 +1,364 @@
+use std::fmt;
+
+use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::curry::match_curried_arg;
+use crate::number::Number;
+
+pub use clvm_derive::FromClvm;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FromClvmError {
+    ExpectedAtom(NodePtr),
+    ExpectedPair(NodePtr),
+    // the end of a list
+    ExpectedNil(NodePtr),
+    // (c (q . value) rest)
+    ExpectedCurriedArg(NodePtr),
+    // the 1 terminating curried arguments
+    ExpectedCurriedArgsEnd(NodePtr),
+    WrongAtomLength {
+        node: NodePtr,
+        expected: usize,
+        found: usize,
+    },
+    IntegerOutOfRange(NodePtr),
+}
+
+impl fmt::Display for FromClvmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ExpectedAtom(_) => write!(f, "expected atom"),
+            Self::ExpectedPair(_) => write!(f, "expected pair"),
+            Self::ExpectedNil(_) => write!(f, "expected nil"),
+            Self::ExpectedCurriedArg(_) => write!(f, "expected curried argument"),
+            Self::ExpectedCurriedArgsEnd(_) => write!(f, "expected end of curried arguments"),
+            Self::WrongAtomLength {
+                expected, found, ..
+            } => write!(f, "expected atom of length {expected}, found {found}"),
+            Self::IntegerOutOfRange(_) => write!(f, "integer out of range"),
+        }
+    }
+}
+
+impl std::error::Error for FromClvmError {}
+
+/// Convert a clvm tree into a value. This is the inverse of `ToClvm`. Atoms
+/// are converted into integers, `Number` and `[u8; N]` regardless of whether
+/// they're in the canonical representation.
+///
+/// Structs can derive this trait, see the `clvm-derive` crate.
+pub trait FromClvm: Sized {
+    fn from_clvm(a: &Allocator, node: NodePtr) -> Result<Self, FromClvmError>;
+}
+
+/// return the first and rest of a pair, or an error if `node` is an atom
+pub fn expect_pair(a: &Allocator, node: NodePtr) -> Result<(NodePtr, NodePtr), FromClvmError> {
+    a.next(node).ok_or(FromClvmError::ExpectedPair(node))
+}
+
+/// return an error if `node` is not nil
+pub fn expect_nil(a: &Allocator, node: NodePtr) -> Result<(), FromClvmError> {
+    match a.sexp(node) {
+        SExp::Atom if a.atom_len(node) == 0 => Ok(()),
+        _ => Err(FromClvmError::ExpectedNil(node)),
+    }
+}
+
+/// return the value and rest of a curried argument, `(c (q . value) rest)`
+pub fn expect_curried_arg(
+    a: &Allocator,
+    node: NodePtr,
+) -> Result<(NodePtr, NodePtr), FromClvmError> {
+    match_curried_arg(a, node).ok_or(FromClvmError::ExpectedCurriedArg(node))
+}
+
+/// return an error if `node` is not the 1 terminating curried arguments
+pub fn expect_curried_args_end(a: &Allocator, node: NodePtr) -> Result<(), FromClvmError> {
+    match a.sexp(node) {
+        SExp::Atom if a.atom(node).as_ref() == [1] => Ok(()),
+        _ => Err(FromClvmError::ExpectedCurriedArgsEnd(node)),
+    }
+}
+
+fn expect_atom(a: &Allocator, node: NodePtr) -> Result<(), FromClvmError> {
+    match a.sexp(node) {
+        SExp::Atom => Ok(()),
+        SExp::Pair(..) => Err(FromClvmError::ExpectedAtom(node)),
+    }
+}
+
+impl FromClvm for NodePtr {
+    fn from_clvm(_a: &Allocator, node: NodePtr) -> Result<Self, FromClvmError> {
+        Ok(node)
+    }
+}
+
+impl<T: FromClvm> FromClvm for Box<T> {
+    fn from_clvm(a: &Allocator, node: NodePtr) -> Result<Self, FromClvmError> {
+        T::from_clvm(a, node).map(Box::new)
+    }
+}
+
+macro_rules! int_from_clvm {
+    ($($t:ty),*) => {
+        $(
+            impl FromClvm for $t {
+                fn from_clvm(a: &Allocator, node: NodePtr) -> Result<Self, FromClvmError> {
+                    expect_atom(a, node)?;
+                    <$t>::try_from(&a.number(node))
+                        .map_err(|_| FromClvmError::IntegerOutOfRange(node))
+                }
+            }
+        )*
+    };
+}
+
+int_from_clvm!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, usize, isize);
+
+impl FromClvm for Number {
+    fn from_clvm(a: &Allocator, node: NodePtr) -> Result<Self, FromClvmError> {
+        expect_atom(a, node)?;
+        Ok(a.number(node))
+    }
+}
+
+impl<const N: usize> FromClvm for [u8; N] {
+    fn from_clvm(a: &Allocator, node: NodePtr) -> Result<Self, FromClvmError> {
+        expect_atom(a, node)?;
+        let atom = a.atom(node);
+        atom.as_ref()
+            .try_into()
+            .map_err(|_| FromClvmError::WrongAtomLength {
+                node,
+                expected: N,
+                found: atom.as_ref().len(),
+            })
+    }
+}
+
+impl FromClvm for () {
+    fn from_clvm(a: &Allocator, node: NodePtr) -> Result<Self, FromClvmError> {
+        expect_nil(a, node)
+    }
+}
+
+impl<A: FromClvm, B: FromClvm> FromClvm for (A, B) {
+    fn from_clvm(a: &Allocator, node: NodePtr) -> Result<Self, FromClvmError> {
+        let (first, rest) = expect_pair(a, node)?;
+        Ok((A::from_clvm(a, first)?, B::from_clvm(a, rest)?))
+    }
+}
+
+impl<T: FromClvm> FromClvm for Vec<T> {
+    fn from_clvm(a: &Allocator, mut node: NodePtr) -> Result<Self, FromClvmError> {
+        let mut ret = Vec::new();
+        while let Some((first, rest)) = a.next(node) {
+            ret.push(T::from_clvm(a, first)?);
+            node = rest;
+        }
+        expect_nil(a, node)?;
+        Ok(ret)
+    }
+}
+
+// nil is always None. This means Some(value) where value is represented as
+// nil (e.g. 0 or an empty list) cannot be round-tripped
+impl<T: FromClvm> FromClvm for Option<T> {
+    fn from_clvm(a: &Allocator, node: NodePtr) -> Result<Self, FromClvmError> {
+        if expect_nil(a, node).is_ok() {
+            Ok(None)
+        } else {
+            T::from_clvm(a, node).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::curry::curry;
+
+#[cfg(test)]
+use crate::serde::{node_from_bytes, node_to_bytes};
+
+#[cfg(test)]
+use crate::to_clvm::ToClvm;
+
+#[cfg(test)]
+fn from_hex<T: FromClvm>(hex: &str) -> Result<T, FromClvmError> {
+    let mut a = Allocator::new();
+    let node = node_from_bytes(&mut a, &hex::decode(hex).unwrap()).unwrap();
+    T::from_clvm(&a, node)
+}
+
+#[cfg(test)]
+fn round_trip<T: ToClvm + FromClvm + PartialEq + fmt::Debug>(value: T) {
+    let mut a = Allocator::new();
+    let node = value.to_clvm(&mut a).unwrap();
+    assert_eq!(T::from_clvm(&a, node).unwrap(), value);
+}
+
+#[test]
+fn test_from_clvm_primitives() {
+    assert_eq!(from_hex::<u8>("80"), Ok(0));
+    assert_eq!(from_hex::<i8>("81ff"), Ok(-1));
+    assert_eq!(from_hex::<u64>("8900ffffffffffffffff"), Ok(u64::MAX));
+    // non-canonical representations are accepted
+    assert_eq!(from_hex::<u32>("820001"), Ok(1));
+    assert_eq!(from_hex::<Number>("820539"), Ok(Number::from(1337)));
+    assert_eq!(from_hex::<[u8; 3]>("83ff0001"), Ok([0xff, 0, 1]));
+    assert_eq!(from_hex::<()>("80"), Ok(()));
+}
+
+#[test]
+fn test_from_clvm_compound() {
+    assert_eq!(from_hex::<(u8, u8)>("ff0102"), Ok((1, 2)));
+    assert_eq!(from_hex::<Vec<u8>>("ff01ff02ff0380"), Ok(vec![1, 2, 3]));
+    assert_eq!(from_hex::<Vec<u8>>("80"), Ok(vec![]));
+    assert_eq!(from_hex::<Option<u8>>("05"), Ok(Some(5)));
+    assert_eq!(from_hex::<Option<u8>>("80"), Ok(None));
+    assert_eq!(from_hex::<Box<u8>>("05"), Ok(Box::new(5)));
+    assert_eq!(
+        from_hex::<((u8, u8), (Vec<u8>, ()))>("ffff0102ffff03ff048080"),
+        Ok(((1, 2), (vec![3, 4], ())))
+    );
+}
+
+#[test]
+fn test_from_clvm_errors() {
+    let mut a = Allocator::new();
+    let pair = node_from_bytes(&mut a, &hex::decode("ff0102").unwrap()).unwrap();
+    let (one, two) = a.next(pair).unwrap();
+    assert_eq!(
+        u8::from_clvm(&a, pair),
+        Err(FromClvmError::ExpectedAtom(pair))
+    );
+    assert_eq!(
+        <[u8; 2]>::from_clvm(&a, one),
+        Err(FromClvmError::WrongAtomLength {
+            node: one,
+            expected: 2,
+            found: 1
+        })
+    );
+    assert_eq!(
+        <(u8, u8)>::from_clvm(&a, one),
+        Err(FromClvmError::ExpectedPair(one))
+    );
+    assert_eq!(
+        <()>::from_clvm(&a, one),
+        Err(FromClvmError::ExpectedNil(one))
+    );
+    // (1 . 2) is not a proper list
+    assert_eq!(
+        Vec::<u8>::from_clvm(&a, pair),
+        Err(FromClvmError::ExpectedNil(two))
+    );
+
+    assert_eq!(
+        from_hex::<u8>("820100").unwrap_err().to_string(),
+        "integer out of range"
+    );
+    assert!(from_hex::<u8>("81ff").is_err());
+    assert_eq!(from_hex::<i8>("81ff"), Ok(-1));
+    assert!(from_hex::<u64>("8901ffffffffffffffff").is_err());
+}
+
+#[test]
+fn test_round_trip() {
+    round_trip(0_u8);
+    round_trip(-1337_i32);
+    round_trip(u128::MAX);
+    round_trip(isize::MIN);
+    round_trip(Number::from(-1));
+    round_trip([1_u8; 32]);
+    round_trip(((), (1_u8, vec![[2_u8; 4]])));
+    round_trip(vec![vec![1_u8, 2], vec![], vec![3]]);
+    round_trip(Some(-1_i64));
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, ToClvm, FromClvm)]
+#[clvm(list)]
+struct Coin {
+    parent_coin_info: [u8; 32],
+    puzzle_hash: [u8; 32],
+    amount: u64,
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, ToClvm, FromClvm)]
+#[clvm(curry)]
+struct CurriedArgs(u8, Vec<u8>);
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, ToClvm, FromClvm)]
+#[clvm(list)]
+struct Generic<T> {
+    value: T,
+    rest: Option<Box<Generic<T>>>,
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, ToClvm, FromClvm)]
+#[clvm(list)]
+struct Unit;
+
+#[test]
+fn test_derive_list() {
+    let coin = Coin {
+        parent_coin_info: [1; 32],
+        puzzle_hash: [2; 32],
+        amount: 1337,
+    };
+    let mut a = Allocator::new();
+    let node = coin.to_clvm(&mut a).unwrap();
+    let expected = format!("ffa0{}ffa0{}ff82053980", "01".repeat(32), "02".repeat(32));
+    assert_eq!(hex::encode(node_to_bytes(&a, node).unwrap()), expected);
+    assert_eq!(Coin::from_clvm(&a, node).unwrap(), coin);
+
+    // the list must be terminated
+    let unterminated = format!("ffa0{}ffa0{}ff82053901", "01".repeat(32), "02".repeat(32));
+    assert!(matches!(
+        from_hex::<Coin>(&unterminated),
+        Err(FromClvmError::ExpectedNil(_))
+    ));
+    assert!(from_hex::<Coin>("ff0102").is_err());
+
+    round_trip(Unit);
+    round_trip(Generic {
+        value: 1_u8,
+        rest: Some(Box::new(Generic {
+            value: 2,
+            rest: None,
+        })),
+    });
+}
+
+#[test]
+fn test_derive_curry() {
+    let value = CurriedArgs(5, vec![1, 2]);
+    let mut a = Allocator::new();
+    let node = value.to_clvm(&mut a).unwrap();
+    assert_eq!(CurriedArgs::from_clvm(&a, node).unwrap(), value);
+
+    // the curried arguments are the same as curry() produces
+    let program = a.nil();
+    let first = 5_u8.to_clvm(&mut a).unwrap();
+    let second = vec![1_u8, 2].to_clvm(&mut a).unwrap();
+    let curried = curry(&mut a, program, &[first, second]).unwrap();
+    // (a (q . program) fixed_args)
+    let (_, rest) = a.next(curried).unwrap();
+    let (_, rest) = a.next(rest).unwrap();
+    let (fixed_args, _) = a.next(rest).unwrap();
+    assert_eq!(
+        node_to_bytes(&a, fixed_args).unwrap(),
+        node_to_bytes(&a, node).unwrap()
+    );
+    assert_eq!(CurriedArgs::from_clvm(&a, fixed_args).unwrap(), value);
+
+    // a list is not curried arguments
+    let list = (5_u8, (vec![1_u8, 2], ())).to_clvm(&mut a).unwrap();
+    assert_eq!(
+        CurriedArgs::from_clvm(&a, list),
+        Err(FromClvmError::ExpectedCurriedArg(list))
+    );
+}
@@ -0,0 +1,324 @@
+// Iterators over clvm structures. None of them recurse, so they're safe to use
+// on arbitrarily deep trees.
+
+use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::reduction::{EvalErr, EvalErrKind};
+
+/// Iterates over the items of a proper list. If the list is terminated by
+/// anything other than nil, the last item is an `ImproperList` error
+/// referring to the terminator.
+pub struct ListIter<'a> {
+    a: &'a Allocator,
+    node: Option<NodePtr>,
+}
+
+impl<'a> ListIter<'a> {
+    pub fn new(a: &'a Allocator, list: NodePtr) -> Self {
+        Self {
+            a,
+            node: Some(list),
+        }
+    }
+}
+
+impl Iterator for ListIter<'_> {
+    type Item = Result<NodePtr, EvalErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.node?;
+        match self.a.sexp(node) {
+            SExp::Pair(first, rest) => {
+                self.node = Some(rest);
+                Some(Ok(first))
+            }
+            SExp::Atom => {
+                self.node = None;
+                if self.a.atom_len(node) == 0 {
+                    None
+                } else {
+                    Some(Err(EvalErr(node, EvalErrKind::ImproperList)))
+                }
+            }
+        }
+    }
+}
+
+/// Visits every node of a tree, parents before their children and first
+/// before rest. Shared sub trees are visited once per reference.
+pub struct PreOrderIter<'a> {
+    a: &'a Allocator,
+    stack: Vec<NodePtr>,
+}
+
+impl<'a> PreOrderIter<'a> {
+    pub fn new(a: &'a Allocator, root: NodePtr) -> Self {
+        Self {
+            a,
+            stack: vec![root],
+        }
+    }
+}
+
+impl Iterator for PreOrderIter<'_> {
+    type Item = NodePtr;
+
+    fn next(&mut self) -> Option<NodePtr> {
+        let node = self.stack.pop()?;
+        if let SExp::Pair(first, rest) = self.a.sexp(node) {
+            self.stack.push(rest);
+            self.stack.push(first);
+        }
+        Some(node)
+    }
+}
+
+/// Visits every node of a tree, children before their parents and first
+/// before rest. Shared sub trees are visited once per reference.
+pub struct PostOrderIter<'a> {
+    a: &'a Allocator,
+    // the bool is set once the node's children have been pushed
+    stack: Vec<(NodePtr, bool)>,
+}
+
+impl<'a> PostOrderIter<'a> {
+    pub fn new(a: &'a Allocator, root: NodePtr) -> Self {
+        Self {
+            a,
+            stack: vec![(root, false)],
+        }
+    }
+}
+
+impl Iterator for PostOrderIter<'_> {
+    type Item = NodePtr;
+
+    fn next(&mut self) -> Option<NodePtr> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            match self.a.sexp(node) {
+                SExp::Pair(first, rest) if !expanded => {
+                    self.stack.push((node, true));
+                    self.stack.push((rest, false));
+                    self.stack.push((first, false));
+                }
+                _ => {
+                    return Some(node);
+                }
+            }
+        }
+    }
+}
+
+// set bit `bit` (counting from the least significant bit) of the big-endian
+// integer in `buf`
+fn set_bit(buf: &mut [u8], bit: usize) {
+    let idx = buf.len() - 1 - bit / 8;
+    buf[idx] |= 1 << (bit % 8);
+}
+
+/// Visits every node of a tree in the same order as `PreOrderIter`, along
+/// with its path from the root. The path is the atom that, passed to
+/// `traverse_path`, would return the node. The root's path is `1`.
+pub struct PathIter<'a> {
+    a: &'a Allocator,
+    // (node, depth, is_rest)
+    stack: Vec<(NodePtr, usize, bool)>,
+    // the steps from the root to the most recently visited node, one bit per
+    // step, least significant bit first. 0 means first and 1 means rest
+    path: Vec<u8>,
+}
+
+impl<'a> PathIter<'a> {
+    pub fn new(a: &'a Allocator, root: NodePtr) -> Self {
+        Self {
+            a,
+            stack: vec![(root, 0, false)],
+            path: Vec::new(),
+        }
+    }
+
+    // the path of the node `depth` steps from the root, in the canonical
+    // integer representation expected by `traverse_path`. The steps are
+    // terminated by the most significant set bit, and we need room for the
+    // sign bit too
+    fn current_path(&self, depth: usize) -> Vec<u8> {
+        let len = (depth + 9) / 8;
+        let mut ret = vec![0; len];
+        let steps = &mut ret[len - self.path.len()..];
+        steps.copy_from_slice(&self.path);
+        steps.reverse();
+        set_bit(&mut ret, depth);
+        ret
+    }
+}
+
+impl Iterator for PathIter<'_> {
+    type Item = (NodePtr, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth, is_rest) = self.stack.pop()?;
+        // in pre-order, the previously visited node shares all steps but the
+        // last with this one, so we only need to drop the deeper steps and
+        // record the last one
+        if depth == 0 {
+            self.path.clear();
+        } else {
+            let step = depth - 1;
+            self.path.resize(step / 8 + 1, 0);
+            let last = self.path.last_mut().unwrap();
+            *last &= (1 << (step % 8)) - 1;
+            if is_rest {
+                *last |= 1 << (step % 8);
+            }
+        }
+        if let SExp::Pair(first, rest) = self.a.sexp(node) {
+            self.stack.push((rest, depth + 1, true));
+            self.stack.push((first, depth + 1, false));
+        }
+        Some((node, self.current_path(depth)))
+    }
+}
+
+#[cfg(test)]
+use crate::test_ops::from_hex;
+
+#[cfg(test)]
+use crate::traverse_path::traverse_path;
+
+#[cfg(test)]
+fn atoms(a: &Allocator, nodes: impl Iterator<Item = NodePtr>) -> Vec<String> {
+    nodes
+        .map(|n| match a.sexp(n) {
+            SExp::Atom => hex::encode(a.atom(n)),
+            SExp::Pair(..) => "pair".to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_list_iter() {
+    let mut a = Allocator::new();
+    // (1 2 3)
+    let list = from_hex(&mut a, "ff01ff02ff0380");
+    let items: Vec<NodePtr> = a.list_iter(list).map(Result::unwrap).collect();
+    assert_eq!(atoms(&a, items.into_iter()), ["01", "02", "03"]);
+
+    // nil is the empty list
+    let nil = a.nil();
+    assert_eq!(a.list_iter(nil).count(), 0);
+
+    // (1 2 . 3)
+    let list = from_hex(&mut a, "ff01ff0203");
+    let mut iter = a.list_iter(list);
+    assert!(iter.next().unwrap().is_ok());
+    assert!(iter.next().unwrap().is_ok());
+    let err = iter.next().unwrap().unwrap_err();
+    assert_eq!(err.1, EvalErrKind::ImproperList);
+    assert_eq!(hex::encode(a.atom(err.0)), "03");
+    assert!(iter.next().is_none());
+
+    // an atom is not a list
+    let atom = a.new_atom(b"foobar").unwrap();
+    let mut iter = a.list_iter(atom);
+    assert_eq!(iter.next().unwrap().unwrap_err().0, atom);
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_pre_order_post_order() {
+    let mut a = Allocator::new();
+    // ((1 . 2) . (3 . 4))
+    let tree = from_hex(&mut a, "ffff0102ff0304");
+    assert_eq!(
+        atoms(&a, a.pre_order(tree)),
+        ["pair", "pair", "01", "02", "pair", "03", "04"]
+    );
+    assert_eq!(
+        atoms(&a, a.post_order(tree)),
+        ["01", "02", "pair", "03", "04", "pair", "pair"]
+    );
+    let (first, _) = a.next(tree).unwrap();
+    assert_eq!(a.pre_order(tree).nth(1), Some(first));
+    assert_eq!(a.post_order(tree).nth(2), Some(first));
+    assert_eq!(a.post_order(tree).last(), Some(tree));
+
+    let atom = a.one();
+    assert_eq!(a.pre_order(atom).collect::<Vec<_>>(), [atom]);
+    assert_eq!(a.post_order(atom).collect::<Vec<_>>(), [atom]);
+}
+
+#[test]
+fn test_deep_tree() {
+    let mut a = Allocator::new();
+    let mut list = a.nil();
+    for _ in 0..200_000 {
+        let one = a.one();
+        list = a.new_pair(list, one).unwrap();
+    }
+    assert_eq!(a.pre_order(list).count(), 400_001);
+    assert_eq!(a.post_order(list).count(), 400_001);
+    assert_eq!(a.path_iter(list).count(), 400_001);
+}
+
+#[test]
+fn test_path_iter() {
+    let mut a = Allocator::new();
+    // (1 (2 3) ((4 . 5) 6 7 8 9 10 11))
+    let tree = from_hex(
+        &mut a,
+        "ff01ffff02ff0380ffffff0405ff06ff07ff08ff09ff0aff0b8080",
+    );
+    let paths: Vec<(NodePtr, Vec<u8>)> = a.path_iter(tree).collect();
+    assert_eq!(
+        paths.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+        a.pre_order(tree).collect::<Vec<_>>()
+    );
+    assert_eq!(paths[0].1, [1]);
+    assert_eq!(paths[1].1, [2]);
+    assert_eq!(paths[2].1, [3]);
+
+    for (node, path) in paths {
+        let found = traverse_path(&a, &path, tree).unwrap().1;
+        assert_eq!(found, node);
+        // the paths are canonical, positive integers
+        assert!(path[0] & 0x80 == 0);
+        assert!(path[0] != 0 || path[1] & 0x80 != 0);
+    }
+}
+
+#[test]
+fn test_path_iter_deep() {
+    let mut a = Allocator::new();
+    // nest 50000 levels deep, through first
+    let mut tree = a.nil();
+    for _ in 0..50_000 {
+        let one = a.one();
+        tree = a.new_pair(tree, one).unwrap();
+    }
+    // the pending nodes and the shared path both stay proportional to the
+    // depth, rather than to the sum of the paths they lead to
+    let mut iter = a.path_iter(tree);
+    let mut count = 0;
+    while let Some((_, path)) = iter.next() {
+        count += 1;
+        assert!(iter.stack.len() <= 50_001);
+        assert!(iter.path.len() <= 50_000 / 8 + 1);
+        assert!(path.len() <= 50_001 / 8 + 1);
+    }
+    assert_eq!(count, 100_001);
+
+    // the paths are correct across byte boundaries, in both directions
+    let mut tree = a.nil();
+    for i in 0..20 {
+        let one = a.one();
+        tree = if i % 3 == 0 {
+            a.new_pair(one, tree).unwrap()
+        } else {
+            a.new_pair(tree, one).unwrap()
+        };
+    }
+    for (node, path) in a.path_iter(tree) {
+        let found = traverse_path(&a, &path, tree).unwrap().1;
+        assert_eq!(found, node);
+    }
+}
@@ -1,11 +1,18 @@
+// lets the derive macros refer to ::clvmr from within this crate
+extern crate self as clvmr;
+
 pub mod allocator;
 pub mod bls_ops;
 pub mod chia_dialect;
 pub mod core_ops;
 pub mod cost;
+pub mod curry;
 pub mod dialect;
 pub mod err_utils;
+pub mod extension_dialect;
 pub mod f_table;
+pub mod from_clvm;
+pub mod iter;
 pub mod more_ops;
 pub mod number;
 pub mod op_utils;
@@ -15,11 +22,17 @@ pub mod runtime_dialect;
 pub mod secp_ops;
 pub mod serde;
 pub mod sha2;
+pub mod text;
+pub mod to_clvm;
 pub mod traverse_path;
+pub mod tree_hash;
 
-pub use allocator::{Allocator, Atom, NodePtr, SExp};
+pub use allocator::{Allocator, Atom, FrozenAllocator, NodePtr, SExp};
 pub use chia_dialect::ChiaDialect;
+pub use from_clvm::FromClvm;
 pub use run_program::run_program;
+pub use to_clvm::ToClvm;
+pub use tree_hash::tree_hash;
 
 pub use chia_dialect::{
     ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_FIXED_DIV, LIMIT_HEAP, MEMPOOL_MODE, NO_UNKNOWN_OPS,
@@ -31,6 +44,9 @@ pub use run_program::run_program_with_counters;
 #[cfg(feature = "pre-eval")]
 pub use run_program::run_program_with_pre_eval;
 
+#[cfg(feature = "profiler")]
+pub use run_program::{run_program_with_profile, OpProfile, Profile};
+
 #[cfg(feature = "counters")]
 pub use run_program::Counters;
 
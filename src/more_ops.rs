@@ -6,93 +6,45 @@ use std::ops::BitOrAssign;
 use std::ops::BitXorAssign;
 
 use crate::allocator::{len_for_value, Allocator, NodePtr, NodeVisitor, SExp};
-use crate::cost::{check_cost, Cost};
+use crate::cost::{check_cost, default_cost_ops, Cost, CostSchedule};
 use crate::err_utils::err;
 use crate::number::Number;
 use crate::op_utils::{
     atom, atom_len, get_args, get_varargs, i32_atom, int_atom, match_args, mod_group_order,
-    new_atom_and_cost, nilp, u32_from_u8, MALLOC_COST_PER_BYTE,
+    new_atom_and_cost_with_costs, nilp, u32_from_u8,
 };
-use crate::reduction::{Reduction, Response};
+use crate::reduction::{EvalErrKind, Reduction, Response};
 use crate::sha2::{Digest, Sha256};
 use chia_bls::G1Element;
 
-const ARITH_BASE_COST: Cost = 99;
-const ARITH_COST_PER_ARG: Cost = 320;
-const ARITH_COST_PER_BYTE: Cost = 3;
-
-const LOG_BASE_COST: Cost = 100;
-const LOG_COST_PER_ARG: Cost = 264;
-const LOG_COST_PER_BYTE: Cost = 3;
-
-const LOGNOT_BASE_COST: Cost = 331;
-const LOGNOT_COST_PER_BYTE: Cost = 3;
-
-const MUL_BASE_COST: Cost = 92;
-const MUL_COST_PER_OP: Cost = 885;
-const MUL_LINEAR_COST_PER_BYTE: Cost = 6;
-const MUL_SQUARE_COST_PER_BYTE_DIVIDER: Cost = 128;
-
-const GR_BASE_COST: Cost = 498;
-const GR_COST_PER_BYTE: Cost = 2;
-
-const GRS_BASE_COST: Cost = 117;
-const GRS_COST_PER_BYTE: Cost = 1;
-
-const STRLEN_BASE_COST: Cost = 173;
-const STRLEN_COST_PER_BYTE: Cost = 1;
-
-const CONCAT_BASE_COST: Cost = 142;
-const CONCAT_COST_PER_ARG: Cost = 135;
-const CONCAT_COST_PER_BYTE: Cost = 3;
-
-const DIVMOD_BASE_COST: Cost = 1116;
-const DIVMOD_COST_PER_BYTE: Cost = 6;
-
-const DIV_BASE_COST: Cost = 988;
-const DIV_COST_PER_BYTE: Cost = 4;
-
-const SHA256_BASE_COST: Cost = 87;
-const SHA256_COST_PER_ARG: Cost = 134;
-const SHA256_COST_PER_BYTE: Cost = 2;
-
-const ASHIFT_BASE_COST: Cost = 596;
-const ASHIFT_COST_PER_BYTE: Cost = 3;
-
-const LSHIFT_BASE_COST: Cost = 277;
-const LSHIFT_COST_PER_BYTE: Cost = 3;
-
-const BOOL_BASE_COST: Cost = 200;
-const BOOL_COST_PER_ARG: Cost = 300;
-
-// Raspberry PI 4 is about 7.679960 / 1.201742 = 6.39 times slower
-// in the point_add benchmark
-
-// increased from 31592 to better model Raspberry PI
-const POINT_ADD_BASE_COST: Cost = 101094;
-// increased from 419994 to better model Raspberry PI
-const POINT_ADD_COST_PER_ARG: Cost = 1343980;
-
-// Raspberry PI 4 is about 2.833543 / 0.447859 = 6.32686 times slower
-// in the pubkey benchmark
-
-// increased from 419535 to better model Raspberry PI
-const PUBKEY_BASE_COST: Cost = 1325730;
-// increased from 12 to closer model Raspberry PI
-const PUBKEY_COST_PER_BYTE: Cost = 38;
-
-// the new coinid operator
-// we subtract 153 cost as a discount, to incentive using this operator rather
-// than "naked" sha256
-const COINID_COST: Cost =
-    SHA256_BASE_COST + SHA256_COST_PER_ARG * 3 + SHA256_COST_PER_BYTE * (32 + 32 + 8) - 153;
-
-const MODPOW_BASE_COST: Cost = 17000;
-const MODPOW_COST_PER_BYTE_BASE_VALUE: Cost = 38;
-// the cost for exponent and modular scale by the square of the size of the
-// respective operands
-const MODPOW_COST_PER_BYTE_EXPONENT: Cost = 3;
-const MODPOW_COST_PER_BYTE_MOD: Cost = 21;
+default_cost_ops! {
+    op_sha256 => op_sha256_with_costs,
+    op_add => op_add_with_costs,
+    op_subtract => op_subtract_with_costs,
+    op_multiply => op_multiply_with_costs,
+    op_div => op_div_with_costs,
+    op_div_fixed => op_div_fixed_with_costs,
+    op_divmod => op_divmod_with_costs,
+    op_mod => op_mod_with_costs,
+    op_gr => op_gr_with_costs,
+    op_gr_bytes => op_gr_bytes_with_costs,
+    op_strlen => op_strlen_with_costs,
+    op_substr => op_substr_with_costs,
+    op_concat => op_concat_with_costs,
+    op_ash => op_ash_with_costs,
+    op_lsh => op_lsh_with_costs,
+    op_logand => op_logand_with_costs,
+    op_logior => op_logior_with_costs,
+    op_logxor => op_logxor_with_costs,
+    op_lognot => op_lognot_with_costs,
+    op_not => op_not_with_costs,
+    op_any => op_any_with_costs,
+    op_all => op_all_with_costs,
+    op_pubkey_for_exp => op_pubkey_for_exp_with_costs,
+    op_point_add => op_point_add_with_costs,
+    op_coinid => op_coinid_with_costs,
+    op_modpow => op_modpow_with_costs,
+}
 
 fn limbs_for_int(v: &Number) -> usize {
     ((v.bits() + 7) / 8) as usize
@@ -149,16 +101,29 @@ fn test_limbs_for_int() {
     limb_test_helper(&[0x80, 0, 0, 0, 0, 0, 0, 0]);
 }
 
-fn malloc_cost(a: &Allocator, cost: Cost, ptr: NodePtr) -> Reduction {
-    let c = a.atom_len(ptr) as Cost * MALLOC_COST_PER_BYTE;
-    Reduction(cost + c, ptr)
+fn malloc_cost(a: &Allocator, cost: Cost, ptr: NodePtr, costs: &CostSchedule) -> Reduction {
+    let c = costs
+        .malloc_cost_per_byte
+        .saturating_mul(a.atom_len(ptr) as Cost);
+    Reduction(cost.saturating_add(c), ptr)
 }
 
+/// `op_unknown_with_costs()` with `CostSchedule::DEFAULT`
 pub fn op_unknown(
+    allocator: &mut Allocator,
+    o: NodePtr,
+    args: NodePtr,
+    max_cost: Cost,
+) -> Response {
+    op_unknown_with_costs(allocator, o, args, max_cost, &CostSchedule::DEFAULT)
+}
+
+pub fn op_unknown_with_costs(
     allocator: &mut Allocator,
     o: NodePtr,
     mut args: NodePtr,
     max_cost: Cost,
+    costs: &CostSchedule,
 ) -> Response {
     // unknown opcode in lenient mode
     // unknown ops are reserved if they start with 0xffff
@@ -192,37 +157,39 @@ pub fn op_unknown(
     let op = op_atom.as_ref();
 
     if op.is_empty() || (op.len() >= 2 && op[0] == 0xff && op[1] == 0xff) {
-        return err(o, "reserved operator");
+        return err(o, EvalErrKind::ReservedOperator);
     }
 
     let cost_function = (op[op.len() - 1] & 0b11000000) >> 6;
     let cost_multiplier: u64 = match u32_from_u8(&op[0..op.len() - 1]) {
         Some(v) => v as u64,
         None => {
-            return err(o, "invalid operator");
+            return err(o, EvalErrKind::InvalidOperator);
         }
     };
 
     let mut cost = match cost_function {
         0 => 1,
         1 => {
-            let mut cost = ARITH_BASE_COST;
+            let mut cost = costs.arith_base_cost;
             let mut byte_count: u64 = 0;
             while let Some((arg, rest)) = allocator.next(args) {
                 args = rest;
-                cost += ARITH_COST_PER_ARG;
+                cost = cost.saturating_add(costs.arith_cost_per_arg);
                 let len = atom_len(allocator, arg, "unknown op")?;
                 byte_count += len as u64;
                 check_cost(
                     allocator,
-                    cost + (byte_count as Cost * ARITH_COST_PER_BYTE),
+                    cost.saturating_add(
+                        costs.arith_cost_per_byte.saturating_mul(byte_count as Cost),
+                    ),
                     max_cost,
                 )?;
             }
-            cost + (byte_count * ARITH_COST_PER_BYTE)
+            cost.saturating_add(costs.arith_cost_per_byte.saturating_mul(byte_count))
         }
         2 => {
-            let mut cost = MUL_BASE_COST;
+            let mut cost = costs.mul_base_cost;
             let mut first_iter: bool = true;
             let mut l0: u64 = 0;
             while let Some((arg, rest)) = allocator.next(args) {
@@ -234,29 +201,33 @@ pub fn op_unknown(
                     continue;
                 }
                 let l1 = len as u64;
-                cost += MUL_COST_PER_OP;
-                cost += (l0 + l1) * MUL_LINEAR_COST_PER_BYTE;
-                cost += (l0 * l1) / MUL_SQUARE_COST_PER_BYTE_DIVIDER;
+                cost = cost.saturating_add(costs.mul_cost_per_op);
+                cost = cost.saturating_add(costs.mul_linear_cost_per_byte.saturating_mul(l0 + l1));
+                cost = cost.saturating_add((l0 * l1) / costs.mul_square_cost_per_byte_divider);
                 l0 += l1;
                 check_cost(allocator, cost, max_cost)?;
             }
             cost
         }
         3 => {
-            let mut cost = CONCAT_BASE_COST;
+            let mut cost = costs.concat_base_cost;
             let mut total_size: u64 = 0;
             while let Some((arg, rest)) = allocator.next(args) {
                 args = rest;
-                cost += CONCAT_COST_PER_ARG;
+                cost = cost.saturating_add(costs.concat_cost_per_arg);
                 let len = atom_len(allocator, arg, "unknown op")?;
                 total_size += len as u64;
                 check_cost(
                     allocator,
-                    cost + total_size as Cost * CONCAT_COST_PER_BYTE,
+                    cost.saturating_add(
+                        costs
+                            .concat_cost_per_byte
+                            .saturating_mul(total_size as Cost),
+                    ),
                     max_cost,
                 )?;
             }
-            cost + total_size * CONCAT_COST_PER_BYTE
+            cost.saturating_add(costs.concat_cost_per_byte.saturating_mul(total_size))
         }
         _ => 1,
     };
@@ -264,9 +235,9 @@ pub fn op_unknown(
     assert!(cost > 0);
 
     check_cost(allocator, cost, max_cost)?;
-    cost *= cost_multiplier + 1;
+    cost = cost.saturating_mul(cost_multiplier + 1);
     if cost > u32::MAX as u64 {
-        err(o, "invalid operator")
+        err(o, EvalErrKind::InvalidOperator)
     } else {
         Ok(Reduction(cost as Cost, allocator.nil()))
     }
@@ -380,8 +351,13 @@ pub const PRECOMPUTED_HASHES: [[u8; 32]; 37] = [
     hex!("3f2d2a889d22530bd1abdc40ff1cbb23ca53ae3f1983e58c70d46a15c120e780"),
 ];
 
-pub fn op_sha256(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = SHA256_BASE_COST;
+pub fn op_sha256_with_costs(
+    a: &mut Allocator,
+    mut input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let mut cost = costs.sha256_base_cost;
 
     if let Some([v0, v1]) = match_args::<2>(a, input) {
         if a.small_number(v0) == Some(1) {
@@ -390,8 +366,15 @@ pub fn op_sha256(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Respo
                 // integer, we may have a pre-computed hash for this
                 if (val as usize) < PRECOMPUTED_HASHES.len() {
                     let num_bytes = if val > 0 { 2 } else { 1 };
-                    cost += num_bytes * SHA256_COST_PER_BYTE + 2 as Cost * SHA256_COST_PER_ARG;
-                    return new_atom_and_cost(a, cost, &PRECOMPUTED_HASHES[val as usize]);
+                    cost = cost
+                        .saturating_add(costs.sha256_cost_per_byte.saturating_mul(num_bytes))
+                        .saturating_add(costs.sha256_cost_per_arg.saturating_mul(2));
+                    return new_atom_and_cost_with_costs(
+                        a,
+                        cost,
+                        &PRECOMPUTED_HASHES[val as usize],
+                        costs,
+                    );
                 }
             }
         }
@@ -401,30 +384,43 @@ pub fn op_sha256(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Respo
     let mut hasher = Sha256::new();
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += SHA256_COST_PER_ARG;
+        cost = cost.saturating_add(costs.sha256_cost_per_arg);
         check_cost(
             a,
-            cost + byte_count as Cost * SHA256_COST_PER_BYTE,
+            cost.saturating_add(
+                costs
+                    .sha256_cost_per_byte
+                    .saturating_mul(byte_count as Cost),
+            ),
             max_cost,
         )?;
         let blob = atom(a, arg, "sha256")?;
         byte_count += blob.as_ref().len();
         hasher.update(blob);
     }
-    cost += byte_count as Cost * SHA256_COST_PER_BYTE;
-    new_atom_and_cost(a, cost, &hasher.finalize())
+    cost = cost.saturating_add(
+        costs
+            .sha256_cost_per_byte
+            .saturating_mul(byte_count as Cost),
+    );
+    new_atom_and_cost_with_costs(a, cost, &hasher.finalize(), costs)
 }
 
-pub fn op_add(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = ARITH_BASE_COST;
+pub fn op_add_with_costs(
+    a: &mut Allocator,
+    mut input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let mut cost = costs.arith_base_cost;
     let mut byte_count: usize = 0;
     let mut total: Number = 0.into();
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += ARITH_COST_PER_ARG;
+        cost = cost.saturating_add(costs.arith_cost_per_arg);
         check_cost(
             a,
-            cost + (byte_count as Cost * ARITH_COST_PER_BYTE),
+            cost.saturating_add(costs.arith_cost_per_byte.saturating_mul(byte_count as Cost)),
             max_cost,
         )?;
 
@@ -439,24 +435,33 @@ pub fn op_add(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response
                 byte_count += len_for_value(val);
             }
             NodeVisitor::Pair(_, _) => {
-                return err(arg, "+ requires int args");
+                return err(arg, EvalErrKind::RequiresIntArgs("+"));
             }
         }
     }
     let total = a.new_number(total)?;
-    cost += byte_count as Cost * ARITH_COST_PER_BYTE;
-    Ok(malloc_cost(a, cost, total))
+    cost = cost.saturating_add(costs.arith_cost_per_byte.saturating_mul(byte_count as Cost));
+    Ok(malloc_cost(a, cost, total, costs))
 }
 
-pub fn op_subtract(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = ARITH_BASE_COST;
+pub fn op_subtract_with_costs(
+    a: &mut Allocator,
+    mut input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let mut cost = costs.arith_base_cost;
     let mut byte_count: usize = 0;
     let mut total: Number = 0.into();
     let mut is_first = true;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += ARITH_COST_PER_ARG;
-        check_cost(a, cost + byte_count as Cost * ARITH_COST_PER_BYTE, max_cost)?;
+        cost = cost.saturating_add(costs.arith_cost_per_arg);
+        check_cost(
+            a,
+            cost.saturating_add(costs.arith_cost_per_byte.saturating_mul(byte_count as Cost)),
+            max_cost,
+        )?;
         if is_first {
             let (v, len) = int_atom(a, arg, "-")?;
             byte_count = len;
@@ -473,19 +478,24 @@ pub fn op_subtract(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Res
                     byte_count += len_for_value(val);
                 }
                 NodeVisitor::Pair(_, _) => {
-                    return err(arg, "- requires int args");
+                    return err(arg, EvalErrKind::RequiresIntArgs("-"));
                 }
             }
         };
         is_first = false;
     }
     let total = a.new_number(total)?;
-    cost += byte_count as Cost * ARITH_COST_PER_BYTE;
-    Ok(malloc_cost(a, cost, total))
+    cost = cost.saturating_add(costs.arith_cost_per_byte.saturating_mul(byte_count as Cost));
+    Ok(malloc_cost(a, cost, total, costs))
 }
 
-pub fn op_multiply(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost: Cost = MUL_BASE_COST;
+pub fn op_multiply_with_costs(
+    a: &mut Allocator,
+    mut input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let mut cost: Cost = costs.mul_base_cost;
     let mut first_iter: bool = true;
     let mut total: Number = 1.into();
     let mut l0: usize = 0;
@@ -509,122 +519,206 @@ pub fn op_multiply(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Res
                 len_for_value(val)
             }
             NodeVisitor::Pair(_, _) => {
-                return err(arg, "* requires int args");
+                return err(arg, EvalErrKind::RequiresIntArgs("*"));
             }
         };
 
-        cost += MUL_COST_PER_OP;
-        cost += (l0 + l1) as Cost * MUL_LINEAR_COST_PER_BYTE;
-        cost += (l0 * l1) as Cost / MUL_SQUARE_COST_PER_BYTE_DIVIDER;
+        cost = cost.saturating_add(costs.mul_cost_per_op);
+        cost = cost.saturating_add(
+            costs
+                .mul_linear_cost_per_byte
+                .saturating_mul((l0 + l1) as Cost),
+        );
+        cost = cost.saturating_add((l0 * l1) as Cost / costs.mul_square_cost_per_byte_divider);
         l0 = limbs_for_int(&total);
     }
     let total = a.new_number(total)?;
-    Ok(malloc_cost(a, cost, total))
+    Ok(malloc_cost(a, cost, total, costs))
 }
 
-pub fn op_div(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_div_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [v0, v1] = get_args::<2>(a, input, "/")?;
     let (a0, a0_len) = int_atom(a, v0, "/")?;
     let (a1, a1_len) = int_atom(a, v1, "/")?;
-    let cost = DIV_BASE_COST + ((a0_len + a1_len) as Cost) * DIV_COST_PER_BYTE;
+    let cost = costs.div_base_cost.saturating_add(
+        costs
+            .div_cost_per_byte
+            .saturating_mul((a0_len + a1_len) as Cost),
+    );
     if a1.sign() == Sign::NoSign {
-        err(input, "div with 0")
+        err(input, EvalErrKind::DivByZero("div"))
     } else {
         if a0.sign() == Sign::Minus || a1.sign() == Sign::Minus {
-            return err(input, "div operator with negative operands is deprecated");
+            return err(input, EvalErrKind::DeprecatedNegativeDiv);
         }
         let q = a0.div_floor(&a1);
         let q = a.new_number(q)?;
-        Ok(malloc_cost(a, cost, q))
+        Ok(malloc_cost(a, cost, q, costs))
     }
 }
 
-pub fn op_div_fixed(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_div_fixed_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [v0, v1] = get_args::<2>(a, input, "/")?;
     let (a0, a0_len) = int_atom(a, v0, "/")?;
     let (a1, a1_len) = int_atom(a, v1, "/")?;
-    let cost = DIV_BASE_COST + ((a0_len + a1_len) as Cost) * DIV_COST_PER_BYTE;
+    let cost = costs.div_base_cost.saturating_add(
+        costs
+            .div_cost_per_byte
+            .saturating_mul((a0_len + a1_len) as Cost),
+    );
     if a1.sign() == Sign::NoSign {
-        err(input, "div with 0")
+        err(input, EvalErrKind::DivByZero("div"))
     } else {
         let q = a0.div_floor(&a1);
         let q = a.new_number(q)?;
-        Ok(malloc_cost(a, cost, q))
+        Ok(malloc_cost(a, cost, q, costs))
     }
 }
 
-pub fn op_divmod(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_divmod_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [v0, v1] = get_args::<2>(a, input, "divmod")?;
     let (a0, a0_len) = int_atom(a, v0, "divmod")?;
     let (a1, a1_len) = int_atom(a, v1, "divmod")?;
-    let cost = DIVMOD_BASE_COST + ((a0_len + a1_len) as Cost) * DIVMOD_COST_PER_BYTE;
+    let cost = costs.divmod_base_cost.saturating_add(
+        costs
+            .divmod_cost_per_byte
+            .saturating_mul((a0_len + a1_len) as Cost),
+    );
     if a1.sign() == Sign::NoSign {
-        err(input, "divmod with 0")
+        err(input, EvalErrKind::DivByZero("divmod"))
     } else {
         let (q, r) = a0.div_mod_floor(&a1);
         let q1 = a.new_number(q)?;
         let r1 = a.new_number(r)?;
 
-        let c = (a.atom_len(q1) + a.atom_len(r1)) as Cost * MALLOC_COST_PER_BYTE;
+        let c = costs
+            .malloc_cost_per_byte
+            .saturating_mul((a.atom_len(q1) + a.atom_len(r1)) as Cost);
         let r: NodePtr = a.new_pair(q1, r1)?;
-        Ok(Reduction(cost + c, r))
+        Ok(Reduction(cost.saturating_add(c), r))
     }
 }
 
-pub fn op_mod(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_mod_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [v0, v1] = get_args::<2>(a, input, "mod")?;
     let (a0, a0_len) = int_atom(a, v0, "mod")?;
     let (a1, a1_len) = int_atom(a, v1, "mod")?;
-    let cost = DIV_BASE_COST + ((a0_len + a1_len) as Cost) * DIV_COST_PER_BYTE;
+    let cost = costs.div_base_cost.saturating_add(
+        costs
+            .div_cost_per_byte
+            .saturating_mul((a0_len + a1_len) as Cost),
+    );
     if a1.sign() == Sign::NoSign {
-        err(input, "mod with 0")
+        err(input, EvalErrKind::DivByZero("mod"))
     } else {
         let q = a.new_number(a0.mod_floor(&a1))?;
-        let c = a.atom_len(q) as Cost * MALLOC_COST_PER_BYTE;
-        Ok(Reduction(cost + c, q))
+        let c = costs
+            .malloc_cost_per_byte
+            .saturating_mul(a.atom_len(q) as Cost);
+        Ok(Reduction(cost.saturating_add(c), q))
     }
 }
 
-pub fn op_gr(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_gr_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [v0, v1] = get_args::<2>(a, input, ">")?;
 
     match (a.small_number(v0), a.small_number(v1)) {
         (Some(lhs), Some(rhs)) => {
-            let cost =
-                GR_BASE_COST + (len_for_value(lhs) + len_for_value(rhs)) as Cost * GR_COST_PER_BYTE;
+            let cost = costs.gr_base_cost.saturating_add(
+                costs
+                    .gr_cost_per_byte
+                    .saturating_mul((len_for_value(lhs) + len_for_value(rhs)) as Cost),
+            );
             Ok(Reduction(cost, if lhs > rhs { a.one() } else { a.nil() }))
         }
         _ => {
             let (v0, v0_len) = int_atom(a, v0, ">")?;
             let (v1, v1_len) = int_atom(a, v1, ">")?;
-            let cost = GR_BASE_COST + (v0_len + v1_len) as Cost * GR_COST_PER_BYTE;
+            let cost = costs.gr_base_cost.saturating_add(
+                costs
+                    .gr_cost_per_byte
+                    .saturating_mul((v0_len + v1_len) as Cost),
+            );
             Ok(Reduction(cost, if v0 > v1 { a.one() } else { a.nil() }))
         }
     }
 }
 
-pub fn op_gr_bytes(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_gr_bytes_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n0, n1] = get_args::<2>(a, input, ">s")?;
     let v0_atom = atom(a, n0, ">s")?;
     let v1_atom = atom(a, n1, ">s")?;
     let v0 = v0_atom.as_ref();
     let v1 = v1_atom.as_ref();
-    let cost = GRS_BASE_COST + (v0.len() + v1.len()) as Cost * GRS_COST_PER_BYTE;
+    let cost = costs.grs_base_cost.saturating_add(
+        costs
+            .grs_cost_per_byte
+            .saturating_mul((v0.len() + v1.len()) as Cost),
+    );
     Ok(Reduction(cost, if v0 > v1 { a.one() } else { a.nil() }))
 }
 
-pub fn op_strlen(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_strlen_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n] = get_args::<1>(a, input, "strlen")?;
     let size = atom_len(a, n, "strlen")?;
     let size_node = a.new_number(size.into())?;
-    let cost = STRLEN_BASE_COST + size as Cost * STRLEN_COST_PER_BYTE;
-    Ok(malloc_cost(a, cost, size_node))
+    let cost = costs
+        .strlen_base_cost
+        .saturating_add(costs.strlen_cost_per_byte.saturating_mul(size as Cost));
+    Ok(malloc_cost(a, cost, size_node, costs))
 }
 
-pub fn op_substr(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_substr_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    _costs: &CostSchedule,
+) -> Response {
     let ([a0, start, end], argc) = get_varargs::<3>(a, input, "substr")?;
     if !(2..=3).contains(&argc) {
-        return err(input, "substr takes exactly 2 or 3 arguments");
+        return err(
+            input,
+            EvalErrKind::ArgCountRange {
+                op: "substr",
+                min: 2,
+                max: 3,
+            },
+        );
     }
     let size = atom_len(a, a0, "substr")?;
     let start = i32_atom(a, start, "substr")?;
@@ -635,7 +729,7 @@ pub fn op_substr(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
         size as i32
     };
     if end < 0 || start < 0 || end as usize > size || end < start {
-        err(input, "invalid indices for substr")
+        err(input, EvalErrKind::InvalidSubstrIndices)
     } else {
         let r = a.new_substr(a0, start as u32, end as u32)?;
         let cost: Cost = 1;
@@ -643,45 +737,69 @@ pub fn op_substr(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
     }
 }
 
-pub fn op_concat(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = CONCAT_BASE_COST;
+pub fn op_concat_with_costs(
+    a: &mut Allocator,
+    mut input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let mut cost = costs.concat_base_cost;
     let mut total_size: usize = 0;
     let mut terms = Vec::<NodePtr>::new();
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += CONCAT_COST_PER_ARG;
+        cost = cost.saturating_add(costs.concat_cost_per_arg);
         check_cost(
             a,
-            cost + total_size as Cost * CONCAT_COST_PER_BYTE,
+            cost.saturating_add(
+                costs
+                    .concat_cost_per_byte
+                    .saturating_mul(total_size as Cost),
+            ),
             max_cost,
         )?;
         match a.sexp(arg) {
-            SExp::Pair(_, _) => return err(arg, "concat on list"),
+            SExp::Pair(_, _) => return err(arg, EvalErrKind::OnList("concat")),
             SExp::Atom => total_size += a.atom_len(arg),
         };
         terms.push(arg);
     }
 
-    cost += total_size as Cost * CONCAT_COST_PER_BYTE;
-    cost += total_size as Cost * MALLOC_COST_PER_BYTE;
+    cost = cost.saturating_add(
+        costs
+            .concat_cost_per_byte
+            .saturating_mul(total_size as Cost),
+    );
+    cost = cost.saturating_add(
+        costs
+            .malloc_cost_per_byte
+            .saturating_mul(total_size as Cost),
+    );
     check_cost(a, cost, max_cost)?;
     let new_atom = a.new_concat(total_size, &terms)?;
     Ok(Reduction(cost, new_atom))
 }
 
-pub fn op_ash(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_ash_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n0, n1] = get_args::<2>(a, input, "ash")?;
     let (i0, l0) = int_atom(a, n0, "ash")?;
     let a1 = i32_atom(a, n1, "ash")?;
     if !(-65535..=65535).contains(&a1) {
-        return err(n1, "shift too large");
+        return err(n1, EvalErrKind::ShiftTooLarge);
     }
 
     let v: Number = if a1 > 0 { i0 << a1 } else { i0 >> -a1 };
     let l1 = limbs_for_int(&v);
     let r = a.new_number(v)?;
-    let cost = ASHIFT_BASE_COST + ((l0 + l1) as Cost) * ASHIFT_COST_PER_BYTE;
-    Ok(malloc_cost(a, cost, r))
+    let cost = costs
+        .ashift_base_cost
+        .saturating_add(costs.ashift_cost_per_byte.saturating_mul((l0 + l1) as Cost));
+    Ok(malloc_cost(a, cost, r, costs))
 }
 
 #[cfg(test)]
@@ -707,14 +825,14 @@ fn test_op_ash() {
         test_shift(op_ash, &mut a, &[1], &[0x80, 0, 0, 0])
             .unwrap_err()
             .1,
-        "shift too large"
+        EvalErrKind::ShiftTooLarge
     );
 
     assert_eq!(
         test_shift(op_ash, &mut a, &[1], &[0x80, 0, 0])
             .unwrap_err()
             .1,
-        "shift too large"
+        EvalErrKind::ShiftTooLarge
     );
 
     let node = test_shift(op_ash, &mut a, &[1], &[0x80, 0]).unwrap().1;
@@ -724,14 +842,14 @@ fn test_op_ash() {
         test_shift(op_ash, &mut a, &[1], &[0x7f, 0, 0, 0])
             .unwrap_err()
             .1,
-        "shift too large"
+        EvalErrKind::ShiftTooLarge
     );
 
     assert_eq!(
         test_shift(op_ash, &mut a, &[1], &[0x7f, 0, 0])
             .unwrap_err()
             .1,
-        "shift too large"
+        EvalErrKind::ShiftTooLarge
     );
 
     let node = test_shift(op_ash, &mut a, &[1], &[0x7f, 0]).unwrap().1;
@@ -742,13 +860,18 @@ fn test_op_ash() {
     assert_eq!(node_bytes.len(), 4065);
 }
 
-pub fn op_lsh(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_lsh_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n0, n1] = get_args::<2>(a, input, "lsh")?;
     let b0_atom = atom(a, n0, "lsh")?;
     let b0 = b0_atom.as_ref();
     let a1 = i32_atom(a, n1, "lsh")?;
     if !(-65535..=65535).contains(&a1) {
-        return err(n1, "shift too large");
+        return err(n1, EvalErrKind::ShiftTooLarge);
     }
     let i0 = BigUint::from_bytes_be(b0);
     let l0 = b0.len();
@@ -758,8 +881,10 @@ pub fn op_lsh(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
 
     let l1 = limbs_for_int(&v);
     let r = a.new_number(v)?;
-    let cost = LSHIFT_BASE_COST + ((l0 + l1) as Cost) * LSHIFT_COST_PER_BYTE;
-    Ok(malloc_cost(a, cost, r))
+    let cost = costs
+        .lshift_base_cost
+        .saturating_add(costs.lshift_cost_per_byte.saturating_mul((l0 + l1) as Cost));
+    Ok(malloc_cost(a, cost, r, costs))
 }
 
 #[test]
@@ -770,14 +895,14 @@ fn test_op_lsh() {
         test_shift(op_lsh, &mut a, &[1], &[0x80, 0, 0, 0])
             .unwrap_err()
             .1,
-        "shift too large"
+        EvalErrKind::ShiftTooLarge
     );
 
     assert_eq!(
         test_shift(op_lsh, &mut a, &[1], &[0x80, 0, 0])
             .unwrap_err()
             .1,
-        "shift too large"
+        EvalErrKind::ShiftTooLarge
     );
 
     let node = test_shift(op_lsh, &mut a, &[1], &[0x80, 0]).unwrap().1;
@@ -787,14 +912,14 @@ fn test_op_lsh() {
         test_shift(op_lsh, &mut a, &[1], &[0x7f, 0, 0, 0])
             .unwrap_err()
             .1,
-        "shift too large"
+        EvalErrKind::ShiftTooLarge
     );
 
     assert_eq!(
         test_shift(op_lsh, &mut a, &[1], &[0x7f, 0, 0])
             .unwrap_err()
             .1,
-        "shift too large"
+        EvalErrKind::ShiftTooLarge
     );
 
     let node = test_shift(op_lsh, &mut a, &[1], &[0x7f, 0]).unwrap().1;
@@ -806,156 +931,209 @@ fn test_op_lsh() {
 }
 
 fn binop_reduction(
-    op_name: &str,
+    op_name: &'static str,
     a: &mut Allocator,
     initial_value: Number,
     mut input: NodePtr,
     max_cost: Cost,
+    costs: &CostSchedule,
     op_f: fn(&mut Number, &Number) -> (),
 ) -> Response {
     let mut total = initial_value;
     let mut arg_size: usize = 0;
-    let mut cost = LOG_BASE_COST;
+    let mut cost = costs.log_base_cost;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
         let (n0, len) = int_atom(a, arg, op_name)?;
         op_f(&mut total, &n0);
         arg_size += len;
-        cost += LOG_COST_PER_ARG;
-        check_cost(a, cost + (arg_size as Cost * LOG_COST_PER_BYTE), max_cost)?;
+        cost = cost.saturating_add(costs.log_cost_per_arg);
+        check_cost(
+            a,
+            cost.saturating_add(costs.log_cost_per_byte.saturating_mul(arg_size as Cost)),
+            max_cost,
+        )?;
     }
-    cost += arg_size as Cost * LOG_COST_PER_BYTE;
+    cost = cost.saturating_add(costs.log_cost_per_byte.saturating_mul(arg_size as Cost));
     let total = a.new_number(total)?;
-    Ok(malloc_cost(a, cost, total))
+    Ok(malloc_cost(a, cost, total, costs))
 }
 
 fn logand_op(a: &mut Number, b: &Number) {
     a.bitand_assign(b);
 }
 
-pub fn op_logand(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
+pub fn op_logand_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let v: Number = (-1).into();
-    binop_reduction("logand", a, v, input, max_cost, logand_op)
+    binop_reduction("logand", a, v, input, max_cost, costs, logand_op)
 }
 
 fn logior_op(a: &mut Number, b: &Number) {
     a.bitor_assign(b);
 }
 
-pub fn op_logior(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
+pub fn op_logior_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let v: Number = (0).into();
-    binop_reduction("logior", a, v, input, max_cost, logior_op)
+    binop_reduction("logior", a, v, input, max_cost, costs, logior_op)
 }
 
 fn logxor_op(a: &mut Number, b: &Number) {
     a.bitxor_assign(b);
 }
 
-pub fn op_logxor(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
+pub fn op_logxor_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let v: Number = (0).into();
-    binop_reduction("logxor", a, v, input, max_cost, logxor_op)
+    binop_reduction("logxor", a, v, input, max_cost, costs, logxor_op)
 }
 
-pub fn op_lognot(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_lognot_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n] = get_args::<1>(a, input, "lognot")?;
     let (mut n, len) = int_atom(a, n, "lognot")?;
     n = !n;
-    let cost = LOGNOT_BASE_COST + ((len as Cost) * LOGNOT_COST_PER_BYTE);
+    let cost = costs
+        .lognot_base_cost
+        .saturating_add(costs.lognot_cost_per_byte.saturating_mul(len as Cost));
     let r = a.new_number(n)?;
-    Ok(malloc_cost(a, cost, r))
+    Ok(malloc_cost(a, cost, r, costs))
 }
 
-pub fn op_not(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_not_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n] = get_args::<1>(a, input, "not")?;
     let r = if nilp(a, n) { a.one() } else { a.nil() };
-    let cost = BOOL_BASE_COST;
+    let cost = costs.bool_base_cost;
     Ok(Reduction(cost, r))
 }
 
-pub fn op_any(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = BOOL_BASE_COST;
+pub fn op_any_with_costs(
+    a: &mut Allocator,
+    mut input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let mut cost = costs.bool_base_cost;
     let mut is_any = false;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += BOOL_COST_PER_ARG;
+        cost = cost.saturating_add(costs.bool_cost_per_arg);
         check_cost(a, cost, max_cost)?;
         is_any = is_any || !nilp(a, arg);
     }
     Ok(Reduction(cost, if is_any { a.one() } else { a.nil() }))
 }
 
-pub fn op_all(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = BOOL_BASE_COST;
+pub fn op_all_with_costs(
+    a: &mut Allocator,
+    mut input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let mut cost = costs.bool_base_cost;
     let mut is_all = true;
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
-        cost += BOOL_COST_PER_ARG;
+        cost = cost.saturating_add(costs.bool_cost_per_arg);
         check_cost(a, cost, max_cost)?;
         is_all = is_all && !nilp(a, arg);
     }
     Ok(Reduction(cost, if is_all { a.one() } else { a.nil() }))
 }
 
-pub fn op_pubkey_for_exp(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_pubkey_for_exp_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [n] = get_args::<1>(a, input, "pubkey_for_exp")?;
     let (v0, v0_len) = int_atom(a, n, "pubkey_for_exp")?;
     let bytes = mod_group_order(v0).to_bytes_be().1;
 
     let point = G1Element::from_integer(&bytes);
 
-    let cost = PUBKEY_BASE_COST + (v0_len as Cost) * PUBKEY_COST_PER_BYTE;
+    let cost = costs
+        .pubkey_base_cost
+        .saturating_add(costs.pubkey_cost_per_byte.saturating_mul(v0_len as Cost));
     Ok(Reduction(
-        cost + 48 * MALLOC_COST_PER_BYTE,
+        cost.saturating_add(costs.malloc_cost_per_byte.saturating_mul(48)),
         a.new_g1(point)?,
     ))
 }
 
-pub fn op_point_add(a: &mut Allocator, mut input: NodePtr, max_cost: Cost) -> Response {
-    let mut cost = POINT_ADD_BASE_COST;
+pub fn op_point_add_with_costs(
+    a: &mut Allocator,
+    mut input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let mut cost = costs.point_add_base_cost;
     let mut total = G1Element::default();
     while let Some((arg, rest)) = a.next(input) {
         input = rest;
         let point = a.g1(arg)?;
-        cost += POINT_ADD_COST_PER_ARG;
+        cost = cost.saturating_add(costs.point_add_cost_per_arg);
         check_cost(a, cost, max_cost)?;
         total += &point;
     }
     Ok(Reduction(
-        cost + 48 * MALLOC_COST_PER_BYTE,
+        cost.saturating_add(costs.malloc_cost_per_byte.saturating_mul(48)),
         a.new_g1(total)?,
     ))
 }
 
-pub fn op_coinid(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response {
+pub fn op_coinid_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    _max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [parent_coin, puzzle_hash, amount] = get_args::<3>(a, input, "coinid")?;
 
     let parent_coin = atom(a, parent_coin, "coinid")?;
     if parent_coin.as_ref().len() != 32 {
-        return err(input, "coinid: invalid parent coin id (must be 32 bytes)");
+        return err(input, EvalErrKind::InvalidParentCoinId);
     }
     let puzzle_hash = atom(a, puzzle_hash, "coinid")?;
     if puzzle_hash.as_ref().len() != 32 {
-        return err(input, "coinid: invalid puzzle hash (must be 32 bytes)");
+        return err(input, EvalErrKind::InvalidPuzzleHash);
     }
     let amount_atom = atom(a, amount, "coinid")?;
     let amount = amount_atom.as_ref();
     if !amount.is_empty() {
         if (amount[0] & 0x80) != 0 {
-            return err(input, "coinid: invalid amount (may not be negative");
+            return err(input, EvalErrKind::NegativeAmount);
         }
         if amount == [0_u8] || (amount.len() > 1 && amount[0] == 0 && (amount[1] & 0x80) == 0) {
-            return err(
-                input,
-                "coinid: invalid amount (may not have redundant leading zero)",
-            );
+            return err(input, EvalErrKind::AmountRedundantLeadingZero);
         }
         // the only valid coin value that's 9 bytes is when a leading zero is
         // required to not have the value interpreted as negative
         if amount.len() > 9 || (amount.len() == 9 && amount[0] != 0) {
-            return err(
-                input,
-                "coinid: invalid amount (may not exceed max coin amount)",
-            );
+            return err(input, EvalErrKind::AmountTooLarge);
         }
     }
 
@@ -969,33 +1147,50 @@ pub fn op_coinid(a: &mut Allocator, input: NodePtr, _max_cost: Cost) -> Response
         .try_into()
         .expect("sha256 hash is not 32 bytes");
 
-    new_atom_and_cost(a, COINID_COST, &ret)
+    new_atom_and_cost_with_costs(a, costs.coinid_cost, &ret, costs)
 }
 
-pub fn op_modpow(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
+pub fn op_modpow_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
     let [base, exponent, modulus] = get_args::<3>(a, input, "modpow")?;
 
-    let mut cost = MODPOW_BASE_COST;
+    let mut cost = costs.modpow_base_cost;
     let (base, bsize) = int_atom(a, base, "modpow")?;
-    cost += bsize as Cost * MODPOW_COST_PER_BYTE_BASE_VALUE;
+    cost = cost.saturating_add(
+        costs
+            .modpow_cost_per_byte_base_value
+            .saturating_mul(bsize as Cost),
+    );
     let (exponent, esize) = int_atom(a, exponent, "modpow")?;
-    cost += (esize * esize) as Cost * MODPOW_COST_PER_BYTE_EXPONENT;
+    cost = cost.saturating_add(
+        costs
+            .modpow_cost_per_byte_exponent
+            .saturating_mul((esize * esize) as Cost),
+    );
     check_cost(a, cost, max_cost)?;
     let (modulus, msize) = int_atom(a, modulus, "modpow")?;
-    cost += (msize * msize) as Cost * MODPOW_COST_PER_BYTE_MOD;
+    cost = cost.saturating_add(
+        costs
+            .modpow_cost_per_byte_mod
+            .saturating_mul((msize * msize) as Cost),
+    );
     check_cost(a, cost, max_cost)?;
 
     if exponent.sign() == Sign::Minus {
-        return err(input, "modpow with negative exponent");
+        return err(input, EvalErrKind::ModpowNegativeExponent);
     }
 
     if modulus.sign() == Sign::NoSign {
-        return err(input, "modpow with 0 modulus");
+        return err(input, EvalErrKind::ModpowZeroModulus);
     }
 
     let ret = base.modpow(&exponent, &modulus);
     let ret = a.new_number(ret)?;
-    Ok(malloc_cost(a, cost, ret))
+    Ok(malloc_cost(a, cost, ret, costs))
 }
 
 #[cfg(test)]
@@ -1007,10 +1202,11 @@ fn test_sha256_atom(buf: &[u8]) {
     let v = a.new_small_number(1).unwrap();
     args = a.new_pair(v, args).unwrap();
 
-    let cost = SHA256_BASE_COST
-        + (2 * SHA256_COST_PER_ARG)
-        + ((1 + buf.len()) as Cost * SHA256_COST_PER_BYTE)
-        + 32 * MALLOC_COST_PER_BYTE;
+    let costs = CostSchedule::DEFAULT;
+    let cost = costs.sha256_base_cost
+        + (2 * costs.sha256_cost_per_arg)
+        + ((1 + buf.len()) as Cost * costs.sha256_cost_per_byte)
+        + 32 * costs.malloc_cost_per_byte;
     let Reduction(actual_cost, result) = op_sha256(&mut a, args, cost).unwrap();
 
     let mut hasher = Sha256::new();
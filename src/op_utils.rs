@@ -1,30 +1,29 @@
 use crate::allocator::{Allocator, Atom, NodePtr, NodeVisitor, SExp};
-use crate::cost::Cost;
+use crate::cost::{Cost, CostSchedule};
 use crate::err_utils::err;
 use crate::number::Number;
-use crate::reduction::EvalErr;
+use crate::reduction::{EvalErr, EvalErrKind};
 use crate::reduction::{Reduction, Response};
 use lazy_static::lazy_static;
 use num_bigint::{BigUint, Sign};
 use num_integer::Integer;
 
 // We ascribe some additional cost per byte for operations that allocate new atoms
-pub const MALLOC_COST_PER_BYTE: Cost = 10;
+#[deprecated(note = "use CostSchedule::malloc_cost_per_byte")]
+pub const MALLOC_COST_PER_BYTE: Cost = CostSchedule::DEFAULT.malloc_cost_per_byte;
 
 pub fn get_args<const N: usize>(
     a: &Allocator,
     args: NodePtr,
-    name: &str,
+    name: &'static str,
 ) -> Result<[NodePtr; N], EvalErr> {
-    match_args::<N>(a, args).ok_or_else(|| {
-        EvalErr(
-            args,
-            format!(
-                "{name} takes exactly {N} argument{}",
-                if N == 1 { "" } else { "s" }
-            ),
-        )
-    })
+    match_args::<N>(a, args).ok_or(EvalErr(
+        args,
+        EvalErrKind::ArgCount {
+            op: name,
+            expected: N,
+        },
+    ))
 }
 
 pub fn match_args<const N: usize>(a: &Allocator, args: NodePtr) -> Option<[NodePtr; N]> {
@@ -65,29 +64,29 @@ fn test_get_args() {
 
     let r = get_args::<3>(&a, args4, "test").unwrap_err();
     assert_eq!(r.0, args4);
-    assert_eq!(r.1, "test takes exactly 3 arguments");
+    assert_eq!(r.1.to_string(), "test takes exactly 3 arguments");
 
     let r = get_args::<5>(&a, args4, "test").unwrap_err();
     assert_eq!(r.0, args4);
-    assert_eq!(r.1, "test takes exactly 5 arguments");
+    assert_eq!(r.1.to_string(), "test takes exactly 5 arguments");
 
     let r = get_args::<4>(&a, args3, "test").unwrap_err();
     assert_eq!(r.0, args3);
-    assert_eq!(r.1, "test takes exactly 4 arguments");
+    assert_eq!(r.1.to_string(), "test takes exactly 4 arguments");
 
     let r = get_args::<4>(&a, args2, "test").unwrap_err();
     assert_eq!(r.0, args2);
-    assert_eq!(r.1, "test takes exactly 4 arguments");
+    assert_eq!(r.1.to_string(), "test takes exactly 4 arguments");
 
     let r = get_args::<1>(&a, args2, "test").unwrap_err();
     assert_eq!(r.0, args2);
-    assert_eq!(r.1, "test takes exactly 1 argument");
+    assert_eq!(r.1.to_string(), "test takes exactly 1 argument");
 }
 
 pub fn get_varargs<const N: usize>(
     a: &Allocator,
     args: NodePtr,
-    name: &str,
+    name: &'static str,
 ) -> Result<([NodePtr; N], usize), EvalErr> {
     let mut next = args;
     let mut counter = 0;
@@ -96,13 +95,7 @@ pub fn get_varargs<const N: usize>(
     while let Some((first, rest)) = a.next(next) {
         next = rest;
         if counter == N {
-            return err(
-                args,
-                &format!(
-                    "{name} takes no more than {N} argument{}",
-                    if N == 1 { "" } else { "s" }
-                ),
-            );
+            return err(args, EvalErrKind::TooManyArgs { op: name, max: N });
         }
         ret[counter] = first;
         counter += 1;
@@ -148,11 +141,11 @@ fn test_get_varargs() {
 
     let r = get_varargs::<3>(&a, args4, "test").unwrap_err();
     assert_eq!(r.0, args4);
-    assert_eq!(r.1, "test takes no more than 3 arguments");
+    assert_eq!(r.1.to_string(), "test takes no more than 3 arguments");
 
     let r = get_varargs::<1>(&a, args4, "test").unwrap_err();
     assert_eq!(r.0, args4);
-    assert_eq!(r.1, "test takes no more than 1 argument");
+    assert_eq!(r.1.to_string(), "test takes no more than 1 argument");
 }
 
 pub fn nilp(a: &Allocator, n: NodePtr) -> bool {
@@ -182,7 +175,7 @@ fn test_nilp() {
 pub fn first(a: &Allocator, n: NodePtr) -> Result<NodePtr, EvalErr> {
     match a.sexp(n) {
         SExp::Pair(first, _) => Ok(first),
-        _ => err(n, "first of non-cons"),
+        _ => err(n, EvalErrKind::FirstOfNonCons),
     }
 }
 
@@ -196,13 +189,13 @@ fn test_first() {
 
     let r = first(&a, a0).unwrap_err();
     assert_eq!(r.0, a0);
-    assert_eq!(r.1, "first of non-cons");
+    assert_eq!(r.1, EvalErrKind::FirstOfNonCons);
 }
 
 pub fn rest(a: &Allocator, n: NodePtr) -> Result<NodePtr, EvalErr> {
     match a.sexp(n) {
         SExp::Pair(_, rest) => Ok(rest),
-        _ => err(n, "rest of non-cons"),
+        _ => err(n, EvalErrKind::RestOfNonCons),
     }
 }
 
@@ -216,13 +209,17 @@ fn test_rest() {
 
     let r = rest(&a, a0).unwrap_err();
     assert_eq!(r.0, a0);
-    assert_eq!(r.1, "rest of non-cons");
+    assert_eq!(r.1, EvalErrKind::RestOfNonCons);
 }
 
-pub fn int_atom(a: &Allocator, args: NodePtr, op_name: &str) -> Result<(Number, usize), EvalErr> {
+pub fn int_atom(
+    a: &Allocator,
+    args: NodePtr,
+    op_name: &'static str,
+) -> Result<(Number, usize), EvalErr> {
     match a.sexp(args) {
         SExp::Atom => Ok((a.number(args), a.atom_len(args))),
-        _ => err(args, &format!("{op_name} requires int args")),
+        _ => err(args, EvalErrKind::RequiresIntArgs(op_name)),
     }
 }
 
@@ -248,13 +245,13 @@ fn test_int_atom_failure() {
     let pair = a.new_pair(a0, a1).unwrap();
     let r = int_atom(&a, pair, "test").unwrap_err();
     assert_eq!(r.0, pair);
-    assert_eq!(r.1, "test requires int args");
+    assert_eq!(r.1.to_string(), "test requires int args");
 }
 
-pub fn atom_len(a: &Allocator, args: NodePtr, op_name: &str) -> Result<usize, EvalErr> {
+pub fn atom_len(a: &Allocator, args: NodePtr, op_name: &'static str) -> Result<usize, EvalErr> {
     match a.sexp(args) {
         SExp::Atom => Ok(a.atom_len(args)),
-        _ => err(args, &format!("{op_name} requires an atom")),
+        _ => err(args, EvalErrKind::RequiresAtom(op_name)),
     }
 }
 
@@ -268,7 +265,7 @@ fn test_atom_len() {
 
     let r = atom_len(&a, pair, "test").unwrap_err();
     assert_eq!(r.0, pair);
-    assert_eq!(r.1, "test requires an atom");
+    assert_eq!(r.1.to_string(), "test requires an atom");
 
     assert_eq!(atom_len(&a, a0, "test").unwrap(), 1);
     assert_eq!(atom_len(&a, a1, "test").unwrap(), 2);
@@ -277,7 +274,7 @@ fn test_atom_len() {
 pub fn uint_atom<const SIZE: usize>(
     a: &Allocator,
     args: NodePtr,
-    op_name: &str,
+    op_name: &'static str,
 ) -> Result<u64, EvalErr> {
     match a.node(args) {
         NodeVisitor::Buffer(bytes) => {
@@ -286,7 +283,7 @@ pub fn uint_atom<const SIZE: usize>(
             }
 
             if (bytes[0] & 0x80) != 0 {
-                return err(args, &format!("{op_name} requires positive int arg"));
+                return err(args, EvalErrKind::RequiresPositiveIntArg(op_name));
             }
 
             // strip leading zeros
@@ -296,7 +293,13 @@ pub fn uint_atom<const SIZE: usize>(
             }
 
             if buf.len() > SIZE {
-                return err(args, &format!("{op_name} requires u{} arg", SIZE * 8));
+                return err(
+                    args,
+                    EvalErrKind::RequiresUintArg {
+                        op: op_name,
+                        bits: SIZE * 8,
+                    },
+                );
             }
 
             let mut ret = 0;
@@ -307,7 +310,7 @@ pub fn uint_atom<const SIZE: usize>(
             Ok(ret)
         }
         NodeVisitor::U32(val) => Ok(val as u64),
-        NodeVisitor::Pair(_, _) => err(args, &format!("{op_name} requires int arg")),
+        NodeVisitor::Pair(_, _) => err(args, EvalErrKind::RequiresIntArg(op_name)),
     }
 }
 
@@ -336,12 +339,12 @@ fn test_uint_atom_4_success(#[case] buf: &[u8], #[case] expected: u64) {
 // u32, 4 bytes
 #[cfg(test)]
 #[rstest]
-#[case(&[0xff,0xff,0xff,0xff], "test requires positive int arg")]
-#[case(&[0xff], "test requires positive int arg")]
-#[case(&[0x80], "test requires positive int arg")]
-#[case(&[0x80,0,0,0], "test requires positive int arg")]
-#[case(&[1, 0xff,0xff,0xff,0xff], "test requires u32 arg")]
-fn test_uint_atom_4_failure(#[case] buf: &[u8], #[case] expected: &str) {
+#[case(&[0xff,0xff,0xff,0xff], EvalErrKind::RequiresPositiveIntArg("test"))]
+#[case(&[0xff], EvalErrKind::RequiresPositiveIntArg("test"))]
+#[case(&[0x80], EvalErrKind::RequiresPositiveIntArg("test"))]
+#[case(&[0x80,0,0,0], EvalErrKind::RequiresPositiveIntArg("test"))]
+#[case(&[1, 0xff,0xff,0xff,0xff], EvalErrKind::RequiresUintArg { op: "test", bits: 32 })]
+fn test_uint_atom_4_failure(#[case] buf: &[u8], #[case] expected: EvalErrKind) {
     use crate::allocator::Allocator;
     let mut a = Allocator::new();
     let n = a.new_atom(buf).unwrap();
@@ -354,7 +357,7 @@ fn test_uint_atom_4_pair() {
     let mut a = Allocator::new();
     let n = a.new_atom(&[0, 0]).unwrap();
     let p = a.new_pair(n, n).unwrap();
-    assert!(uint_atom::<4>(&a, p, "test") == err(p, "test requires int arg"));
+    assert!(uint_atom::<4>(&a, p, "test") == err(p, EvalErrKind::RequiresIntArg("test")));
 }
 
 // u64, 8 bytes
@@ -383,13 +386,13 @@ fn test_uint_atom_8_success(#[case] buf: &[u8], #[case] expected: u64) {
 // u64, 8 bytes
 #[cfg(test)]
 #[rstest]
-#[case(&[0xff,0xff,0xff,0xff], "test requires positive int arg")]
-#[case(&[0xff,0xff,0xff,0xff,0xff,0xff,0xff,0xff], "test requires positive int arg")]
-#[case(&[0xff], "test requires positive int arg")]
-#[case(&[0x80], "test requires positive int arg")]
-#[case(&[0x80,0,0,0], "test requires positive int arg")]
-#[case(&[1,0xff,0xff,0xff,0xff,0xff,0xff,0xff,0xff], "test requires u64 arg")]
-fn test_uint_atom_8_failure(#[case] buf: &[u8], #[case] expected: &str) {
+#[case(&[0xff,0xff,0xff,0xff], EvalErrKind::RequiresPositiveIntArg("test"))]
+#[case(&[0xff,0xff,0xff,0xff,0xff,0xff,0xff,0xff], EvalErrKind::RequiresPositiveIntArg("test"))]
+#[case(&[0xff], EvalErrKind::RequiresPositiveIntArg("test"))]
+#[case(&[0x80], EvalErrKind::RequiresPositiveIntArg("test"))]
+#[case(&[0x80,0,0,0], EvalErrKind::RequiresPositiveIntArg("test"))]
+#[case(&[1,0xff,0xff,0xff,0xff,0xff,0xff,0xff,0xff], EvalErrKind::RequiresUintArg { op: "test", bits: 64 })]
+fn test_uint_atom_8_failure(#[case] buf: &[u8], #[case] expected: EvalErrKind) {
     use crate::allocator::Allocator;
     let mut a = Allocator::new();
     let n = a.new_atom(buf).unwrap();
@@ -402,12 +405,12 @@ fn test_uint_atom_8_pair() {
     let mut a = Allocator::new();
     let n = a.new_atom(&[0, 0]).unwrap();
     let p = a.new_pair(n, n).unwrap();
-    assert!(uint_atom::<8>(&a, p, "test") == err(p, "test requires int arg"));
+    assert!(uint_atom::<8>(&a, p, "test") == err(p, EvalErrKind::RequiresIntArg("test")));
 }
 
-pub fn atom<'a>(a: &'a Allocator, n: NodePtr, op_name: &str) -> Result<Atom<'a>, EvalErr> {
+pub fn atom<'a>(a: &'a Allocator, n: NodePtr, op_name: &'static str) -> Result<Atom<'a>, EvalErr> {
     if n.is_pair() {
-        return err(n, &format!("{op_name} on list"));
+        return err(n, EvalErrKind::OnList(op_name));
     }
     Ok(a.atom(n))
 }
@@ -530,17 +533,14 @@ fn test_u64_from_bytes() {
     );
 }
 
-pub fn i32_atom(a: &Allocator, args: NodePtr, op_name: &str) -> Result<i32, EvalErr> {
+pub fn i32_atom(a: &Allocator, args: NodePtr, op_name: &'static str) -> Result<i32, EvalErr> {
     match a.node(args) {
         NodeVisitor::Buffer(buf) => match i32_from_u8(buf) {
             Some(v) => Ok(v),
-            _ => err(
-                args,
-                &format!("{op_name} requires int32 args (with no leading zeros)"),
-            ),
+            _ => err(args, EvalErrKind::RequiresCanonicalInt32Args(op_name)),
         },
         NodeVisitor::U32(val) => Ok(val as i32),
-        NodeVisitor::Pair(_, _) => err(args, &format!("{op_name} requires int32 args")),
+        NodeVisitor::Pair(_, _) => err(args, EvalErrKind::RequiresInt32Args(op_name)),
     }
 }
 
@@ -555,7 +555,7 @@ fn test_i32_atom() {
 
     let r = i32_atom(&a, pair, "test").unwrap_err();
     assert_eq!(r.0, pair);
-    assert_eq!(r.1, "test requires int32 args");
+    assert_eq!(r.1.to_string(), "test requires int32 args");
 
     assert_eq!(i32_atom(&a, a0, "test").unwrap(), 42);
     assert_eq!(i32_atom(&a, a1, "test").unwrap(), 1337);
@@ -563,17 +563,32 @@ fn test_i32_atom() {
     let a2 = a.new_number(0x100000000_i64.into()).unwrap();
     let r = i32_atom(&a, a2, "test").unwrap_err();
     assert_eq!(r.0, a2);
-    assert_eq!(r.1, "test requires int32 args (with no leading zeros)");
+    assert_eq!(
+        r.1.to_string(),
+        "test requires int32 args (with no leading zeros)"
+    );
 
     let a3 = a.new_number((-0xffffffff_i64).into()).unwrap();
     let r = i32_atom(&a, a3, "test").unwrap_err();
     assert_eq!(r.0, a3);
-    assert_eq!(r.1, "test requires int32 args (with no leading zeros)");
+    assert_eq!(
+        r.1.to_string(),
+        "test requires int32 args (with no leading zeros)"
+    );
 }
 
 pub fn new_atom_and_cost(a: &mut Allocator, cost: Cost, buf: &[u8]) -> Response {
-    let c = buf.len() as Cost * MALLOC_COST_PER_BYTE;
-    Ok(Reduction(cost + c, a.new_atom(buf)?))
+    new_atom_and_cost_with_costs(a, cost, buf, &CostSchedule::DEFAULT)
+}
+
+pub fn new_atom_and_cost_with_costs(
+    a: &mut Allocator,
+    cost: Cost,
+    buf: &[u8],
+    costs: &CostSchedule,
+) -> Response {
+    let c = costs.malloc_cost_per_byte.saturating_mul(buf.len() as Cost);
+    Ok(Reduction(cost.saturating_add(c), a.new_atom(buf)?))
 }
 
 pub fn mod_group_order(n: Number) -> Number {
@@ -3,8 +3,215 @@ use std::{fmt, io};
 use crate::allocator::NodePtr;
 use crate::cost::Cost;
 
+/// The reason evaluating a program (or running an operator) failed. The
+/// `Display` implementation produces the error message.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct EvalErr(pub NodePtr, pub String);
+pub enum EvalErrKind {
+    // resource limits
+    CostExceeded,
+    OutOfMemory,
+    TooManyAtoms,
+    TooManyPairs,
+    ValueStackLimitReached,
+    EnvStackLimitReached,
+    Timeout,
+    Cancelled,
+
+    // the program called `raise`
+    Raise,
+
+    // interpreter
+    ValueStackEmpty,
+    EnvStackEmpty,
+    PathIntoAtom,
+    BadOperandList,
+    InvalidOperatorSyntax,
+    UnimplementedOperator,
+    ReservedOperator,
+    InvalidOperator,
+    UnknownSoftforkExtension,
+    InvalidSoftforkCost,
+    SoftforkCostMismatch,
+
+    // a list that's not terminated by nil
+    ImproperList,
+
+    // operator arguments
+    ArgCount {
+        op: &'static str,
+        expected: usize,
+    },
+    ArgCountRange {
+        op: &'static str,
+        min: usize,
+        max: usize,
+    },
+    TooManyArgs {
+        op: &'static str,
+        max: usize,
+    },
+    FirstOfNonCons,
+    RestOfNonCons,
+    OnList(&'static str),
+    RequiresAtom(&'static str),
+    RequiresIntArg(&'static str),
+    RequiresIntArgs(&'static str),
+    RequiresPositiveIntArg(&'static str),
+    RequiresUintArg {
+        op: &'static str,
+        bits: usize,
+    },
+    RequiresInt32Args(&'static str),
+    RequiresCanonicalInt32Args(&'static str),
+
+    // arithmetic and atom operators
+    DivByZero(&'static str),
+    DeprecatedNegativeDiv,
+    ModpowNegativeExponent,
+    ModpowZeroModulus,
+    InvalidSubstrIndices,
+    SubstrStartOutOfBounds,
+    SubstrEndOutOfBounds,
+    SubstrInvalidBounds,
+    ShiftTooLarge,
+
+    // coinid
+    InvalidParentCoinId,
+    InvalidPuzzleHash,
+    NegativeAmount,
+    AmountRedundantLeadingZero,
+    AmountTooLarge,
+
+    // BLS
+    G1WrongSize,
+    G2WrongSize,
+    G1ExpectedAtom,
+    G2ExpectedAtom,
+    NotG1Point,
+    NotG2Point,
+    InvalidG1Point,
+    InvalidG2Point,
+    BlsPairingIdentityFailed,
+    BlsVerifyFailed,
+
+    // secp256k1 and secp256r1
+    SecpInvalidPubkey(&'static str),
+    SecpInvalidDigest(&'static str),
+    SecpInvalidSignature(&'static str),
+    SecpVerifyFailed(&'static str),
+
+    // an operator defined outside of this crate (see `dialect::Operator`)
+    // rejected its arguments. The message is up to the operator, and may
+    // e.g. include the offending value
+    OperatorFailed(String),
+
+    // a bug in the allocator or in an operator
+    InternalError(&'static str),
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+impl fmt::Display for EvalErrKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use EvalErrKind::*;
+        match self {
+            CostExceeded => write!(f, "cost exceeded"),
+            OutOfMemory => write!(f, "out of memory"),
+            TooManyAtoms => write!(f, "too many atoms"),
+            TooManyPairs => write!(f, "too many pairs"),
+            ValueStackLimitReached => write!(f, "value stack limit reached"),
+            EnvStackLimitReached => write!(f, "environment stack limit reached"),
+            Timeout => write!(f, "timeout"),
+            Cancelled => write!(f, "cancelled"),
+            Raise => write!(f, "clvm raise"),
+            ValueStackEmpty => write!(f, "runtime error: value stack empty"),
+            EnvStackEmpty => write!(f, "runtime error: env stack empty"),
+            PathIntoAtom => write!(f, "path into atom"),
+            BadOperandList => write!(f, "bad operand list"),
+            InvalidOperatorSyntax => write!(f, "in ((X)...) syntax X must be lone atom"),
+            UnimplementedOperator => write!(f, "unimplemented operator"),
+            ReservedOperator => write!(f, "reserved operator"),
+            InvalidOperator => write!(f, "invalid operator"),
+            UnknownSoftforkExtension => write!(f, "unknown softfork extension"),
+            InvalidSoftforkCost => write!(f, "cost must be > 0"),
+            SoftforkCostMismatch => write!(f, "softfork specified cost mismatch"),
+            ImproperList => write!(f, "list is not nil terminated"),
+            ArgCount { op, expected } => {
+                write!(
+                    f,
+                    "{op} takes exactly {expected} argument{}",
+                    plural(*expected)
+                )
+            }
+            ArgCountRange { op, min, max } => {
+                write!(f, "{op} takes exactly {min} or {max} arguments")
+            }
+            TooManyArgs { op, max } => {
+                write!(f, "{op} takes no more than {max} argument{}", plural(*max))
+            }
+            FirstOfNonCons => write!(f, "first of non-cons"),
+            RestOfNonCons => write!(f, "rest of non-cons"),
+            OnList(op) => write!(f, "{op} on list"),
+            RequiresAtom(op) => write!(f, "{op} requires an atom"),
+            RequiresIntArg(op) => write!(f, "{op} requires int arg"),
+            RequiresIntArgs(op) => write!(f, "{op} requires int args"),
+            RequiresPositiveIntArg(op) => write!(f, "{op} requires positive int arg"),
+            RequiresUintArg { op, bits } => write!(f, "{op} requires u{bits} arg"),
+            RequiresInt32Args(op) => write!(f, "{op} requires int32 args"),
+            RequiresCanonicalInt32Args(op) => {
+                write!(f, "{op} requires int32 args (with no leading zeros)")
+            }
+            DivByZero(op) => write!(f, "{op} with 0"),
+            DeprecatedNegativeDiv => {
+                write!(f, "div operator with negative operands is deprecated")
+            }
+            ModpowNegativeExponent => write!(f, "modpow with negative exponent"),
+            ModpowZeroModulus => write!(f, "modpow with 0 modulus"),
+            InvalidSubstrIndices => write!(f, "invalid indices for substr"),
+            SubstrStartOutOfBounds => write!(f, "substr start out of bounds"),
+            SubstrEndOutOfBounds => write!(f, "substr end out of bounds"),
+            SubstrInvalidBounds => write!(f, "substr invalid bounds"),
+            ShiftTooLarge => write!(f, "shift too large"),
+            InvalidParentCoinId => {
+                write!(f, "coinid: invalid parent coin id (must be 32 bytes)")
+            }
+            InvalidPuzzleHash => write!(f, "coinid: invalid puzzle hash (must be 32 bytes)"),
+            NegativeAmount => write!(f, "coinid: invalid amount (may not be negative"),
+            AmountRedundantLeadingZero => {
+                write!(
+                    f,
+                    "coinid: invalid amount (may not have redundant leading zero)"
+                )
+            }
+            AmountTooLarge => write!(f, "coinid: invalid amount (may not exceed max coin amount)"),
+            G1WrongSize => write!(f, "atom is not G1 size, 48 bytes"),
+            G2WrongSize => write!(f, "atom is not G2 size, 96 bytes"),
+            G1ExpectedAtom => write!(f, "pair found, expected G1 point"),
+            G2ExpectedAtom => write!(f, "pair found, expected G2 point"),
+            NotG1Point => write!(f, "atom is not a G1 point"),
+            NotG2Point => write!(f, "atom is not a G2 point"),
+            InvalidG1Point => write!(f, "atom is not a valid G1 point"),
+            InvalidG2Point => write!(f, "atom is not a valid G2 point"),
+            BlsPairingIdentityFailed => write!(f, "bls_pairing_identity failed"),
+            BlsVerifyFailed => write!(f, "bls_verify failed"),
+            SecpInvalidPubkey(op) => write!(f, "{op} pubkey is not valid"),
+            SecpInvalidDigest(op) => write!(f, "{op} message digest is not 32 bytes"),
+            SecpInvalidSignature(op) => write!(f, "{op} sig is not valid"),
+            SecpVerifyFailed(op) => write!(f, "{op} failed"),
+            OperatorFailed(msg) => write!(f, "{msg}"),
+            InternalError(msg) => write!(f, "(internal error) {msg}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalErr(pub NodePtr, pub EvalErrKind);
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Reduction(pub Cost, pub NodePtr);
@@ -21,6 +228,6 @@ impl std::error::Error for EvalErr {}
 
 impl From<EvalErr> for io::Error {
     fn from(v: EvalErr) -> Self {
-        Self::new(io::ErrorKind::Other, v.1)
+        Self::new(io::ErrorKind::Other, v.1.to_string())
     }
 }
@@ -1,23 +1,27 @@
-use super::traverse_path::{traverse_path, traverse_path_fast};
-use crate::allocator::{Allocator, Checkpoint, NodePtr, NodeVisitor, SExp};
+use super::traverse_path::{traverse_path_fast_with_costs, traverse_path_with_costs};
+use crate::allocator::{
+    Allocator, Checkpoint, NodePtr, NodeVisitor, SExp, MAX_NUM_ATOMS, MAX_NUM_PAIRS,
+};
+use crate::chia_dialect::LIMIT_HEAP;
 use crate::cost::Cost;
 use crate::dialect::{Dialect, OperatorSet};
 use crate::err_utils::err;
 use crate::op_utils::{first, get_args, uint_atom};
-use crate::reduction::{EvalErr, Reduction, Response};
+use crate::reduction::{EvalErr, EvalErrKind, Reduction, Response};
 
-// lowered from 46
-const QUOTE_COST: Cost = 20;
-// lowered from 138
-const APPLY_COST: Cost = 90;
-// the cost of entering a softfork guard
-const GUARD_COST: Cost = 140;
-// mandatory base cost for every operator we execute
-const OP_COST: Cost = 1;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-// The max number of elements allowed on the stack. The program fails if this is
-// exceeded
-const STACK_SIZE_LIMIT: usize = 20000000;
+#[cfg(feature = "profiler")]
+use std::collections::BTreeMap;
+
+/// The default max number of elements allowed on the value and environment
+/// stacks. The program fails if this is exceeded
+pub const STACK_SIZE_LIMIT: usize = 20000000;
+
+/// The heap limit used in mempool mode (i.e. with the `LIMIT_HEAP` flag)
+pub const MEMPOOL_HEAP_LIMIT: usize = 500000000;
 
 #[cfg(feature = "pre-eval")]
 pub type PreEval =
@@ -26,8 +30,10 @@ pub type PreEval =
 #[cfg(feature = "pre-eval")]
 pub type PostEval = dyn Fn(Option<NodePtr>);
 
+/// The operations that make up the work queue (op_stack) of the interpreter.
 #[repr(u8)]
-enum Operation {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
     Apply,
     Cons,
     ExitGuard,
@@ -64,10 +70,222 @@ impl Counters {
     }
 }
 
+/// The cost and number of invocations of one operator (or one kind of
+/// interpreter operation) while running a program.
+#[cfg(feature = "profiler")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OpProfile {
+    pub calls: u64,
+    pub cost: Cost,
+    /// The sum of the sizes of all atom arguments passed to the operator. For
+    /// path lookups, this is the size of the path atoms.
+    pub arg_bytes: u64,
+}
+
+#[cfg(feature = "profiler")]
+impl OpProfile {
+    fn add(&mut self, cost: Cost, arg_bytes: u64) {
+        self.calls += 1;
+        self.cost = self.cost.saturating_add(cost);
+        self.arg_bytes += arg_bytes;
+    }
+}
+
+/// A breakdown of where the cost of running a program went. The sum of all
+/// buckets (see `total_cost()`) is the cost of the program.
+#[cfg(feature = "profiler")]
+#[derive(Debug, Default, Clone)]
+pub struct Profile {
+    /// operators executed by the dialect, keyed by the operator atom
+    pub ops: BTreeMap<Vec<u8>, OpProfile>,
+    /// `q` operator
+    pub quote: OpProfile,
+    /// the `a` operator and the `((X) ...)` syntax
+    pub apply: OpProfile,
+    /// environment lookups, i.e. `traverse_path` and `traverse_path_fast`
+    pub path: OpProfile,
+    /// entering softfork guards. The cost of the program inside the guard is
+    /// accounted for in the other buckets
+    pub softfork: OpProfile,
+    /// the base cost charged for every operator invocation (except quote)
+    pub dispatch: OpProfile,
+}
+
+#[cfg(feature = "profiler")]
+impl Profile {
+    pub fn total_cost(&self) -> Cost {
+        [
+            &self.quote,
+            &self.apply,
+            &self.path,
+            &self.softfork,
+            &self.dispatch,
+        ]
+        .into_iter()
+        .chain(self.ops.values())
+        .fold(0, |acc: Cost, p| acc.saturating_add(p.cost))
+    }
+}
+
+// The number of operations executed between checks of the Interrupt
+// conditions
+const INTERRUPT_CHECK_INTERVAL: u32 = 1024;
+
+/// Conditions, other than cost, under which `run_program_with_interrupt()`
+/// stops executing the program. These are checked periodically, so the
+/// program may run slightly past the deadline or past the cancel flag being
+/// set. They don't affect the cost of the program.
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt {
+    /// fail with `EvalErrKind::Timeout` once this point in time has passed
+    pub deadline: Option<Instant>,
+    /// fail with `EvalErrKind::Cancelled` once this flag is set
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Interrupt {
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    pub fn with_cancel_flag(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn check(&self, node: NodePtr) -> Result<(), EvalErr> {
+        if let Some(cancel) = &self.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                return err(node, EvalErrKind::Cancelled);
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return err(node, EvalErrKind::Timeout);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The limits to run a program under, passed to `run_program_with_options()`.
+/// The heap, atom and pair limits are enforced by the `Allocator`. During the
+/// run they're lowered to the ones in here, but the allocator's own limits
+/// still apply if they're lower.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    max_cost: Cost,
+    stack_limit: usize,
+    heap_limit: usize,
+    max_atoms: usize,
+    max_pairs: usize,
+    interrupt: Option<Interrupt>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self::consensus()
+    }
+}
+
+impl RunOptions {
+    /// The limits used when validating blocks. The heap limit is 4 GiB and the
+    /// atom and pair limits are `MAX_NUM_ATOMS` and `MAX_NUM_PAIRS`, also with
+    /// the "wide-heap" feature
+    pub fn consensus() -> Self {
+        Self {
+            max_cost: 0,
+            stack_limit: STACK_SIZE_LIMIT,
+            heap_limit: u32::MAX as usize,
+            max_atoms: MAX_NUM_ATOMS,
+            max_pairs: MAX_NUM_PAIRS,
+            interrupt: None,
+        }
+    }
+
+    /// The limits used in mempool mode (see `MEMPOOL_MODE`)
+    pub fn mempool() -> Self {
+        Self::consensus().with_heap_limit(MEMPOOL_HEAP_LIMIT)
+    }
+
+    /// The limits implied by the `ChiaDialect` flags. i.e. the heap limit
+    /// when `LIMIT_HEAP` is set
+    pub fn from_flags(flags: u32) -> Self {
+        if (flags & LIMIT_HEAP) != 0 {
+            Self::mempool()
+        } else {
+            Self::consensus()
+        }
+    }
+
+    /// 0 means no limit
+    pub fn with_max_cost(mut self, max_cost: Cost) -> Self {
+        self.max_cost = max_cost;
+        self
+    }
+
+    /// the max number of elements on the value and environment stacks
+    pub fn with_stack_limit(mut self, stack_limit: usize) -> Self {
+        self.stack_limit = stack_limit;
+        self
+    }
+
+    /// the max number of bytes of atoms, at most `MAX_HEAP_LIMIT`
+    pub fn with_heap_limit(mut self, heap_limit: usize) -> Self {
+        self.heap_limit = heap_limit;
+        self
+    }
+
+    pub fn with_max_atoms(mut self, max_atoms: usize) -> Self {
+        self.max_atoms = max_atoms;
+        self
+    }
+
+    pub fn with_max_pairs(mut self, max_pairs: usize) -> Self {
+        self.max_pairs = max_pairs;
+        self
+    }
+
+    pub fn with_interrupt(mut self, interrupt: Interrupt) -> Self {
+        self.interrupt = Some(interrupt);
+        self
+    }
+
+    pub fn max_cost(&self) -> Cost {
+        self.max_cost
+    }
+
+    pub fn stack_limit(&self) -> usize {
+        self.stack_limit
+    }
+
+    pub fn heap_limit(&self) -> usize {
+        self.heap_limit
+    }
+
+    pub fn max_atoms(&self) -> usize {
+        self.max_atoms
+    }
+
+    pub fn max_pairs(&self) -> usize {
+        self.max_pairs
+    }
+
+    /// create an allocator enforcing the heap, atom and pair limits
+    pub fn new_allocator(&self) -> Allocator {
+        Allocator::new_with_limits(self.heap_limit, self.max_atoms, self.max_pairs)
+    }
+}
+
 // this represents the state we were in before entering a soft-fork guard. We
 // may need this to long-jump out of the guard, and also to validate the cost
 // when exiting the guard
-struct SoftforkGuard {
+pub struct SoftforkGuard {
     // This is the expected cost of the program when exiting the guard. i.e. the
     // current_cost + the first argument to the operator
     expected_cost: Cost,
@@ -84,6 +302,29 @@ struct SoftforkGuard {
     start_cost: Cost,
 }
 
+impl SoftforkGuard {
+    /// the cost the program is expected to have reached when exiting the guard
+    pub fn expected_cost(&self) -> Cost {
+        self.expected_cost
+    }
+
+    /// the operators enabled inside the guard
+    pub fn operator_set(&self) -> OperatorSet {
+        self.operator_set
+    }
+}
+
+/// One pending operator invocation, part of a backtrace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub operator: NodePtr,
+    /// The evaluated operands. For frames whose operands are still being
+    /// evaluated, this is the list of operands evaluated so far. Operands are
+    /// evaluated from last to first, so this is a tail of the operand list.
+    pub operands: NodePtr,
+    pub env: NodePtr,
+}
+
 // `run_program` has three stacks:
 // 1. the operand stack of `NodePtr` objects. val_stack
 // 2. the operator stack of Operation. op_stack
@@ -97,8 +338,16 @@ struct RunProgramContext<'a, D> {
     env_stack: Vec<NodePtr>,
     op_stack: Vec<Operation>,
     softfork_stack: Vec<SoftforkGuard>,
+    stack_limit: usize,
+    // whether to remember the frame of the operator that failed. This is
+    // only needed for backtraces
+    record_failed_frame: bool,
+    // the frame of the operator that failed, if any
+    failed_frame: Option<Frame>,
     #[cfg(feature = "counters")]
     pub counters: Counters,
+    #[cfg(feature = "profiler")]
+    profile: Profile,
 
     #[cfg(feature = "pre-eval")]
     pre_eval: Option<PreEval>,
@@ -108,7 +357,7 @@ struct RunProgramContext<'a, D> {
 
 fn augment_cost_errors(r: Result<Cost, EvalErr>, max_cost: NodePtr) -> Result<Cost, EvalErr> {
     r.map_err(|e| {
-        if &e.1 != "cost exceeded" {
+        if e.1 != EvalErrKind::CostExceeded {
             e
         } else {
             EvalErr(max_cost, e.1)
@@ -150,19 +399,99 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
     #[inline(always)]
     fn account_op_push(&mut self) {}
 
+    #[cfg(feature = "profiler")]
+    fn atom_bytes(&self, node: NodePtr) -> u64 {
+        match self.allocator.sexp(node) {
+            SExp::Atom => self.allocator.atom_len(node) as u64,
+            SExp::Pair(_, _) => 0,
+        }
+    }
+
+    #[cfg(feature = "profiler")]
+    fn account_op(&mut self, operator: NodePtr, operand_list: NodePtr, cost: Cost) {
+        let mut arg_bytes = 0;
+        let mut args = operand_list;
+        while let SExp::Pair(first, rest) = self.allocator.sexp(args) {
+            arg_bytes += self.atom_bytes(first);
+            args = rest;
+        }
+        let key = self.allocator.atom(operator).as_ref().to_vec();
+        self.profile
+            .ops
+            .entry(key)
+            .or_default()
+            .add(cost, arg_bytes);
+    }
+
+    #[cfg(feature = "profiler")]
+    fn account_path(&mut self, path: NodePtr, cost: Cost) {
+        let arg_bytes = self.atom_bytes(path);
+        self.profile.path.add(cost, arg_bytes);
+    }
+
+    #[cfg(feature = "profiler")]
+    fn account_quote(&mut self) {
+        self.profile
+            .quote
+            .add(self.dialect.cost_schedule().quote_cost, 0);
+    }
+
+    #[cfg(feature = "profiler")]
+    fn account_apply(&mut self) {
+        self.profile
+            .apply
+            .add(self.dialect.cost_schedule().apply_cost, 0);
+    }
+
+    #[cfg(feature = "profiler")]
+    fn account_softfork(&mut self, cost: Cost) {
+        self.profile.softfork.add(cost, 0);
+    }
+
+    #[cfg(feature = "profiler")]
+    fn account_dispatch(&mut self) {
+        self.profile
+            .dispatch
+            .add(self.dialect.cost_schedule().op_cost, 0);
+    }
+
+    #[cfg(not(feature = "profiler"))]
+    #[inline(always)]
+    fn account_op(&mut self, _operator: NodePtr, _operand_list: NodePtr, _cost: Cost) {}
+
+    #[cfg(not(feature = "profiler"))]
+    #[inline(always)]
+    fn account_path(&mut self, _path: NodePtr, _cost: Cost) {}
+
+    #[cfg(not(feature = "profiler"))]
+    #[inline(always)]
+    fn account_quote(&mut self) {}
+
+    #[cfg(not(feature = "profiler"))]
+    #[inline(always)]
+    fn account_apply(&mut self) {}
+
+    #[cfg(not(feature = "profiler"))]
+    #[inline(always)]
+    fn account_softfork(&mut self, _cost: Cost) {}
+
+    #[cfg(not(feature = "profiler"))]
+    #[inline(always)]
+    fn account_dispatch(&mut self) {}
+
     pub fn pop(&mut self) -> Result<NodePtr, EvalErr> {
         let v: Option<NodePtr> = self.val_stack.pop();
         match v {
             None => {
                 let node: NodePtr = self.allocator.nil();
-                err(node, "runtime error: value stack empty")
+                err(node, EvalErrKind::ValueStackEmpty)
             }
             Some(k) => Ok(k),
         }
     }
     pub fn push(&mut self, node: NodePtr) -> Result<(), EvalErr> {
-        if self.val_stack.len() == STACK_SIZE_LIMIT {
-            return err(node, "value stack limit reached");
+        if self.val_stack.len() >= self.stack_limit {
+            return err(node, EvalErrKind::ValueStackLimitReached);
         }
         self.val_stack.push(node);
         self.account_val_push();
@@ -170,8 +499,8 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
     }
 
     pub fn push_env(&mut self, env: NodePtr) -> Result<(), EvalErr> {
-        if self.env_stack.len() == STACK_SIZE_LIMIT {
-            return err(env, "environment stack limit reached");
+        if self.env_stack.len() >= self.stack_limit {
+            return err(env, EvalErrKind::EnvStackLimitReached);
         }
         self.env_stack.push(env);
         self.account_env_push();
@@ -191,8 +520,13 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             env_stack: Vec::new(),
             op_stack: Vec::new(),
             softfork_stack: Vec::new(),
+            stack_limit: STACK_SIZE_LIMIT,
+            record_failed_frame: false,
+            failed_frame: None,
             #[cfg(feature = "counters")]
             counters: Counters::new(),
+            #[cfg(feature = "profiler")]
+            profile: Profile::default(),
             pre_eval,
             posteval_stack: Vec::new(),
         }
@@ -206,8 +540,13 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             env_stack: Vec::new(),
             op_stack: Vec::new(),
             softfork_stack: Vec::new(),
+            stack_limit: STACK_SIZE_LIMIT,
+            record_failed_frame: false,
+            failed_frame: None,
             #[cfg(feature = "counters")]
             counters: Counters::new(),
+            #[cfg(feature = "profiler")]
+            profile: Profile::default(),
             #[cfg(feature = "pre-eval")]
             pre_eval: None,
             #[cfg(feature = "pre-eval")]
@@ -233,7 +572,8 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         // special case check for quote
         if self.allocator.small_number(operator_node) == Some(self.dialect.quote_kw()) {
             self.push(operand_list)?;
-            Ok(QUOTE_COST)
+            self.account_quote();
+            Ok(self.dialect.cost_schedule().quote_cost)
         } else {
             self.push_env(env)?;
             self.op_stack.push(Operation::Apply);
@@ -259,10 +599,11 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             }
             // ensure a correct nil terminator
             if self.allocator.atom_len(operands) != 0 {
-                err(operand_list, "bad operand list")
+                err(operand_list, EvalErrKind::BadOperandList)
             } else {
                 self.push(self.allocator.nil())?;
-                Ok(OP_COST)
+                self.account_dispatch();
+                Ok(self.dialect.cost_schedule().op_cost)
             }
         }
     }
@@ -280,13 +621,24 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         let SExp::Pair(op_node, op_list) = self.allocator.sexp(program) else {
             // the program is just a bitfield path through the env tree
             let r = match self.allocator.node(program) {
-                NodeVisitor::Buffer(buf) => traverse_path(self.allocator, buf, env)?,
-                NodeVisitor::U32(val) => traverse_path_fast(self.allocator, val, env)?,
+                NodeVisitor::Buffer(buf) => traverse_path_with_costs(
+                    self.allocator,
+                    buf,
+                    env,
+                    self.dialect.cost_schedule(),
+                )?,
+                NodeVisitor::U32(val) => traverse_path_fast_with_costs(
+                    self.allocator,
+                    val,
+                    env,
+                    self.dialect.cost_schedule(),
+                )?,
                 NodeVisitor::Pair(_, _) => {
                     panic!("expected atom, got pair");
                 }
             };
             self.push(r.1)?;
+            self.account_path(program, r.0);
             return Ok(r.0);
         };
 
@@ -298,14 +650,15 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                     "in the ((X)...) syntax, the inner list",
                 )?;
                 if let SExp::Pair(_, _) = self.allocator.sexp(inner) {
-                    return err(program, "in ((X)...) syntax X must be lone atom");
+                    return err(program, EvalErrKind::InvalidOperatorSyntax);
                 }
                 self.push_env(env)?;
                 self.push(new_operator)?;
                 self.push(op_list)?;
                 self.op_stack.push(Operation::Apply);
                 self.account_op_push();
-                Ok(APPLY_COST)
+                self.account_apply();
+                Ok(self.dialect.cost_schedule().apply_cost)
             }
             SExp::Atom => self.eval_op_atom(op_node, op_list, env),
         }
@@ -317,7 +670,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         let env: NodePtr = *self
             .env_stack
             .last()
-            .ok_or_else(|| EvalErr(program, "runtime error: env stack empty".into()))?;
+            .ok_or(EvalErr(program, EvalErrKind::EnvStackEmpty))?;
         self.push(v2)?;
 
         // on the way back, build a list from the values
@@ -337,7 +690,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             self.dialect
                 .softfork_extension(uint_atom::<4>(self.allocator, extension, "softfork")? as u32);
         if extension == OperatorSet::Default {
-            err(args, "unknown softfork extension")
+            err(args, EvalErrKind::UnknownSoftforkExtension)
         } else {
             Ok((extension, program, env))
         }
@@ -346,14 +699,37 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
     fn apply_op(&mut self, current_cost: Cost, max_cost: Cost) -> Result<Cost, EvalErr> {
         let operand_list = self.pop()?;
         let operator = self.pop()?;
-        if self.env_stack.pop().is_none() {
-            return err(operator, "runtime error: env stack empty");
-        }
+        let Some(env) = self.env_stack.pop() else {
+            return err(operator, EvalErrKind::EnvStackEmpty);
+        };
+        // the frame has been popped off the stacks at this point. In case the
+        // operator fails, we remember it, to include it in backtraces
+        self.invoke_op(operator, operand_list, current_cost, max_cost)
+            .inspect_err(|_| {
+                if self.record_failed_frame {
+                    self.failed_frame = Some(Frame {
+                        operator,
+                        operands: operand_list,
+                        env,
+                    });
+                }
+            })
+    }
+
+    fn invoke_op(
+        &mut self,
+        operator: NodePtr,
+        operand_list: NodePtr,
+        current_cost: Cost,
+        max_cost: Cost,
+    ) -> Result<Cost, EvalErr> {
         let op_atom = self.allocator.small_number(operator);
 
         if op_atom == Some(self.dialect.apply_kw()) {
             let [new_operator, env] = get_args::<2>(self.allocator, operand_list, "apply")?;
-            self.eval_pair(new_operator, env).map(|c| c + APPLY_COST)
+            let cost = self.eval_pair(new_operator, env)?;
+            self.account_apply();
+            Ok(cost.saturating_add(self.dialect.cost_schedule().apply_cost))
         } else if op_atom == Some(self.dialect.softfork_kw()) {
             let expected_cost = uint_atom::<8>(
                 self.allocator,
@@ -361,10 +737,10 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                 "softfork",
             )?;
             if expected_cost > max_cost {
-                return err(operand_list, "cost exceeded");
+                return err(operand_list, EvalErrKind::CostExceeded);
             }
             if expected_cost == 0 {
-                return err(operand_list, "cost must be > 0");
+                return err(operand_list, EvalErrKind::InvalidSoftforkCost);
             }
 
             // we can't blindly propagate errors here, since we handle errors
@@ -378,6 +754,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                         // if we're in consensus mode, we have to accept this as
                         // something we don't understand
                         self.push(self.allocator.nil())?;
+                        self.account_softfork(expected_cost);
                         return Ok(expected_cost);
                     }
                     return Err(err);
@@ -385,7 +762,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             };
 
             self.softfork_stack.push(SoftforkGuard {
-                expected_cost: current_cost + expected_cost,
+                expected_cost: current_cost.saturating_add(expected_cost),
                 allocator_state: self.allocator.checkpoint(),
                 operator_set: ext,
                 #[cfg(test)]
@@ -396,7 +773,9 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
             // specified match the true cost. We also free heap allocations
             self.op_stack.push(Operation::ExitGuard);
 
-            self.eval_pair(prg, env).map(|c| c + GUARD_COST)
+            let cost = self.eval_pair(prg, env)?;
+            self.account_softfork(self.dialect.cost_schedule().guard_cost);
+            Ok(cost.saturating_add(self.dialect.cost_schedule().guard_cost))
         } else {
             let current_extensions = if let Some(sf) = self.softfork_stack.last() {
                 sf.operator_set
@@ -412,6 +791,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                 current_extensions,
             )?;
             self.push(r.1)?;
+            self.account_op(operator, operand_list, r.0);
             Ok(r.0)
         }
     }
@@ -431,7 +811,7 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
                 current_cost - guard.start_cost,
                 guard.expected_cost - guard.start_cost
             );
-            return err(self.allocator.nil(), "softfork specified cost mismatch");
+            return err(self.allocator.nil(), EvalErrKind::SoftforkCostMismatch);
         }
 
         // restore the allocator to the state when we entered the softfork guard
@@ -451,7 +831,98 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
         Ok(0)
     }
 
+    // executes the operation at the top of the op_stack. Returns the cost of
+    // the operation, or None if there are no more operations to execute
+    #[inline(always)]
+    fn step(
+        &mut self,
+        cost: Cost,
+        max_cost: Cost,
+        max_cost_ptr: NodePtr,
+    ) -> Result<Option<Cost>, EvalErr> {
+        // if we are in a softfork guard, temporarily use the guard's
+        // expected cost as the upper limit. This lets us fail early in case
+        // it's wrong. It's guaranteed to be <= max_cost, because we check
+        // that when entering the softfork guard
+        let effective_max_cost = if let Some(sf) = self.softfork_stack.last() {
+            sf.expected_cost
+        } else {
+            max_cost
+        };
+
+        if cost > effective_max_cost {
+            return err(max_cost_ptr, EvalErrKind::CostExceeded);
+        }
+        let Some(op) = self.op_stack.pop() else {
+            return Ok(None);
+        };
+        Ok(Some(match op {
+            Operation::Apply => {
+                augment_cost_errors(self.apply_op(cost, effective_max_cost - cost), max_cost_ptr)?
+            }
+            Operation::ExitGuard => self.exit_guard(cost)?,
+            Operation::Cons => self.cons_op()?,
+            Operation::SwapEval => augment_cost_errors(self.swap_eval_op(), max_cost_ptr)?,
+            #[cfg(feature = "pre-eval")]
+            Operation::PostEval => {
+                let f = self.posteval_stack.pop().unwrap();
+                let peek: Option<NodePtr> = self.val_stack.last().copied();
+                f(peek);
+                0
+            }
+        }))
+    }
+
+    // reconstruct the pending Apply frames from the stacks. The stacks are
+    // laid out by eval_op_atom() and eval_pair() as:
+    // op_stack: Apply, SwapEval * n (unevaluated operands), [Cons]
+    // val_stack: operator, operand * n, (partial) operand list
+    // env_stack: env
+    // If the program failed half-way through setting up a frame, that frame
+    // is incomplete and left out.
+    fn backtrace(&self) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        let mut val_idx = 0;
+        let mut env_idx = 0;
+        let mut ops = self.op_stack.iter().peekable();
+        while let Some(op) = ops.next() {
+            if *op != Operation::Apply {
+                continue;
+            }
+            let operator = self.val_stack.get(val_idx);
+            val_idx += 1;
+            while ops.next_if_eq(&&Operation::SwapEval).is_some() {
+                val_idx += 1;
+            }
+            let operands = self.val_stack.get(val_idx);
+            val_idx += 1;
+            let env = self.env_stack.get(env_idx);
+            env_idx += 1;
+            if let (Some(operator), Some(operands), Some(env)) = (operator, operands, env) {
+                frames.push(Frame {
+                    operator: *operator,
+                    operands: *operands,
+                    env: *env,
+                });
+            }
+        }
+        if let Some(frame) = &self.failed_frame {
+            frames.push(frame.clone());
+        }
+        frames
+    }
+
     pub fn run_program(&mut self, program: NodePtr, env: NodePtr, max_cost: Cost) -> Response {
+        self.run_program_interruptible(program, env, max_cost, None)
+    }
+
+    fn run_program_interruptible(
+        &mut self,
+        program: NodePtr,
+        env: NodePtr,
+        max_cost: Cost,
+        interrupt: Option<&Interrupt>,
+    ) -> Response {
         self.val_stack = vec![];
         self.op_stack = vec![];
 
@@ -462,45 +933,116 @@ impl<'a, D: Dialect> RunProgramContext<'a, D> {
 
         let mut cost: Cost = 0;
 
-        cost += self.eval_pair(program, env)?;
+        cost = cost.saturating_add(self.eval_pair(program, env)?);
+
+        if let Some(interrupt) = interrupt {
+            // the interrupt conditions are only checked every
+            // INTERRUPT_CHECK_INTERVAL operations, to keep the overhead low
+            let mut countdown = 0;
+            while let Some(step_cost) = self.step(cost, max_cost, max_cost_ptr)? {
+                cost = cost.saturating_add(step_cost);
+                if countdown == 0 {
+                    interrupt.check(self.allocator.nil())?;
+                    countdown = INTERRUPT_CHECK_INTERVAL;
+                }
+                countdown -= 1;
+            }
+        } else {
+            while let Some(step_cost) = self.step(cost, max_cost, max_cost_ptr)? {
+                cost = cost.saturating_add(step_cost);
+            }
+        }
+        Ok(Reduction(cost, self.pop()?))
+    }
+}
 
-        loop {
-            // if we are in a softfork guard, temporarily use the guard's
-            // expected cost as the upper limit. This lets us fail early in case
-            // it's wrong. It's guaranteed to be <= max_cost, because we check
-            // that when entering the softfork guard
-            let effective_max_cost = if let Some(sf) = self.softfork_stack.last() {
-                sf.expected_cost
-            } else {
-                max_cost
-            };
+/// Runs a program one `Operation` at a time, making it possible to inspect the
+/// state of the interpreter between steps. This is meant for debuggers and
+/// tracing tools, the cost and the result are identical to `run_program()`.
+pub struct RunProgramStepper<'a, D> {
+    rpc: RunProgramContext<'a, D>,
+    cost: Cost,
+    max_cost: Cost,
+    max_cost_ptr: NodePtr,
+    result: Option<NodePtr>,
+}
 
-            if cost > effective_max_cost {
-                return err(max_cost_ptr, "cost exceeded");
+impl<'a, D: Dialect> RunProgramStepper<'a, D> {
+    /// Prepares to run `program` with the environment `env`. This performs the
+    /// initial evaluation of `program`, which may fail.
+    pub fn new(
+        allocator: &'a mut Allocator,
+        dialect: &'a D,
+        program: NodePtr,
+        env: NodePtr,
+        max_cost: Cost,
+    ) -> Result<Self, EvalErr> {
+        let mut rpc = RunProgramContext::new(allocator, dialect);
+        let max_cost = if max_cost == 0 { Cost::MAX } else { max_cost };
+        let max_cost_ptr = rpc.allocator.new_number(max_cost.into())?;
+        let cost = rpc.eval_pair(program, env)?;
+        Ok(Self {
+            rpc,
+            cost,
+            max_cost,
+            max_cost_ptr,
+            result: None,
+        })
+    }
+
+    /// Executes the next operation. Once the program has completed, the final
+    /// `Reduction` is returned (and will keep being returned by subsequent
+    /// calls). If an error is returned, the program has failed and must not be
+    /// stepped any further.
+    pub fn step(&mut self) -> Result<Option<Reduction>, EvalErr> {
+        if let Some(result) = self.result {
+            return Ok(Some(Reduction(self.cost, result)));
+        }
+        match self.rpc.step(self.cost, self.max_cost, self.max_cost_ptr)? {
+            Some(step_cost) => {
+                self.cost = self.cost.saturating_add(step_cost);
+                Ok(None)
+            }
+            None => {
+                let result = self.rpc.pop()?;
+                self.result = Some(result);
+                Ok(Some(Reduction(self.cost, result)))
             }
-            let top = self.op_stack.pop();
-            let op = match top {
-                Some(f) => f,
-                None => break,
-            };
-            cost += match op {
-                Operation::Apply => augment_cost_errors(
-                    self.apply_op(cost, effective_max_cost - cost),
-                    max_cost_ptr,
-                )?,
-                Operation::ExitGuard => self.exit_guard(cost)?,
-                Operation::Cons => self.cons_op()?,
-                Operation::SwapEval => augment_cost_errors(self.swap_eval_op(), max_cost_ptr)?,
-                #[cfg(feature = "pre-eval")]
-                Operation::PostEval => {
-                    let f = self.posteval_stack.pop().unwrap();
-                    let peek: Option<NodePtr> = self.val_stack.last().copied();
-                    f(peek);
-                    0
-                }
-            };
         }
-        Ok(Reduction(cost, self.pop()?))
+    }
+
+    /// Returns true once the program has run to completion
+    pub fn is_done(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// The cost of the program, so far
+    pub fn cost(&self) -> Cost {
+        self.cost
+    }
+
+    pub fn allocator(&self) -> &Allocator {
+        self.rpc.allocator
+    }
+
+    /// The operand stack. The top of the stack is the last element
+    pub fn val_stack(&self) -> &[NodePtr] {
+        &self.rpc.val_stack
+    }
+
+    /// The environment stack. The top of the stack is the last element
+    pub fn env_stack(&self) -> &[NodePtr] {
+        &self.rpc.env_stack
+    }
+
+    /// The operations left to execute. The next operation is the last element
+    pub fn op_stack(&self) -> &[Operation] {
+        &self.rpc.op_stack
+    }
+
+    /// The innermost softfork guard we're currently executing in, if any
+    pub fn softfork_guard(&self) -> Option<&SoftforkGuard> {
+        self.rpc.softfork_stack.last()
     }
 }
 
@@ -515,6 +1057,63 @@ pub fn run_program<'a, D: Dialect>(
     rpc.run_program(program, env, max_cost)
 }
 
+/// Runs the program like `run_program()`. In case of failure, the error is
+/// returned along with a backtrace of the pending operator invocations, the
+/// outermost first and the operator that failed (if any) last.
+pub fn run_program_with_backtrace<'a, D: Dialect>(
+    allocator: &'a mut Allocator,
+    dialect: &'a D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+) -> Result<Reduction, (EvalErr, Vec<Frame>)> {
+    let mut rpc = RunProgramContext::new(allocator, dialect);
+    rpc.record_failed_frame = true;
+    rpc.run_program(program, env, max_cost)
+        .map_err(|e| (e, rpc.backtrace()))
+}
+
+/// Runs the program like `run_program()`, but also stops when the deadline of
+/// `interrupt` has passed or its cancel flag is set.
+pub fn run_program_with_interrupt<'a, D: Dialect>(
+    allocator: &'a mut Allocator,
+    dialect: &'a D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+    interrupt: &Interrupt,
+) -> Response {
+    let mut rpc = RunProgramContext::new(allocator, dialect);
+    rpc.run_program_interruptible(program, env, max_cost, Some(interrupt))
+}
+
+/// Runs the program with the limits and interrupt conditions from `options`.
+/// The options can only lower the allocator's limits, never raise them, and
+/// the allocator's own limits are restored once the program completes. Since
+/// `RunOptions::consensus()` limits the heap to 4 GiB, an allocator created
+/// with larger limits (see the "wide-heap" feature) needs options with
+/// correspondingly larger limits to make use of them.
+pub fn run_program_with_options<'a, D: Dialect>(
+    allocator: &'a mut Allocator,
+    dialect: &'a D,
+    program: NodePtr,
+    env: NodePtr,
+    options: &RunOptions,
+) -> Response {
+    let saved = allocator.limits();
+    allocator.set_limits(
+        saved.0.min(options.heap_limit),
+        saved.1.min(options.max_atoms),
+        saved.2.min(options.max_pairs),
+    );
+    let mut rpc = RunProgramContext::new(allocator, dialect);
+    rpc.stack_limit = options.stack_limit;
+    let ret =
+        rpc.run_program_interruptible(program, env, options.max_cost, options.interrupt.as_ref());
+    allocator.set_limits(saved.0, saved.1, saved.2);
+    ret
+}
+
 #[cfg(feature = "pre-eval")]
 pub fn run_program_with_pre_eval<'a, D: Dialect>(
     allocator: &'a mut Allocator,
@@ -545,6 +1144,21 @@ pub fn run_program_with_counters<'a, D: Dialect>(
     (rpc.counters, ret)
 }
 
+/// Runs the program and returns a `Profile` with a breakdown of its cost per
+/// operator, along with the result. The cost is identical to `run_program()`.
+#[cfg(feature = "profiler")]
+pub fn run_program_with_profile<'a, D: Dialect>(
+    allocator: &'a mut Allocator,
+    dialect: &'a D,
+    program: NodePtr,
+    env: NodePtr,
+    max_cost: Cost,
+) -> (Profile, Response) {
+    let mut rpc = RunProgramContext::new(allocator, dialect);
+    let ret = rpc.run_program(program, env, max_cost);
+    (rpc.profile, ret)
+}
+
 #[cfg(test)]
 struct RunProgramTest {
     prg: &'static str,
@@ -559,7 +1173,12 @@ struct RunProgramTest {
 use crate::test_ops::parse_exp;
 
 #[cfg(test)]
-use crate::chia_dialect::{ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_FIXED_DIV, NO_UNKNOWN_OPS};
+use rstest::rstest;
+
+#[cfg(test)]
+use crate::chia_dialect::{
+    ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_FIXED_DIV, MEMPOOL_MODE, NO_UNKNOWN_OPS,
+};
 
 #[cfg(test)]
 const TEST_CASES: &[RunProgramTest] = &[
@@ -1312,17 +1931,159 @@ fn test_run_program() {
                 // ensure it fails with the correct error
                 let expected_cost_exceeded =
                     run_program(&mut allocator, &dialect, program, args, t.cost - 1).unwrap_err();
-                assert_eq!(expected_cost_exceeded.1, "cost exceeded");
+                assert_eq!(expected_cost_exceeded.1, EvalErrKind::CostExceeded);
             }
             Err(err) => {
                 println!("FAILED: {}", err.1);
-                assert_eq!(err.1, t.err);
+                assert_eq!(err.1.to_string(), t.err);
                 assert!(expected_result.is_none());
             }
         }
     }
 }
 
+#[test]
+fn test_runtime_dialect() {
+    use crate::chia_dialect::ChiaDialect;
+    use crate::runtime_dialect::RuntimeDialect;
+    use crate::test_ops::node_eq;
+
+    // the RuntimeDialect must behave exactly like the ChiaDialect it's
+    // describing
+    for t in TEST_CASES {
+        let mut allocator = Allocator::new();
+
+        let program = check(parse_exp(&mut allocator, t.prg));
+        let args = check(parse_exp(&mut allocator, t.args));
+
+        println!("prg: {}", t.prg);
+        let max_cost = if t.cost == 0 { 11000000000 } else { t.cost };
+        let expected = run_program(
+            &mut allocator,
+            &ChiaDialect::new(t.flags),
+            program,
+            args,
+            max_cost,
+        );
+        let actual = run_program(
+            &mut allocator,
+            &RuntimeDialect::chia(t.flags),
+            program,
+            args,
+            max_cost,
+        );
+        match (expected, actual) {
+            (Ok(expected), Ok(actual)) => {
+                assert_eq!(expected.0, actual.0);
+                assert!(node_eq(&allocator, expected.1, actual.1));
+            }
+            (Err(expected), Err(actual)) => {
+                assert_eq!(expected.1, actual.1);
+            }
+            (expected, actual) => {
+                panic!("mismatch: {expected:?} {actual:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stepper() {
+    use crate::chia_dialect::ChiaDialect;
+
+    // stepping through a program must produce the same result, cost and error
+    // as running it in one go
+    for t in TEST_CASES {
+        let mut allocator = Allocator::new();
+
+        let program = check(parse_exp(&mut allocator, t.prg));
+        let args = check(parse_exp(&mut allocator, t.args));
+        let dialect = ChiaDialect::new(t.flags);
+
+        let expected = run_program(&mut allocator, &dialect, program, args, t.cost);
+
+        let result: Response = (|| {
+            let mut stepper =
+                RunProgramStepper::new(&mut allocator, &dialect, program, args, t.cost)?;
+            loop {
+                assert!(!stepper.is_done());
+                if let Some(r) = stepper.step()? {
+                    assert!(stepper.is_done());
+                    assert!(stepper.op_stack().is_empty());
+                    // once done, the stepper keeps returning the result
+                    assert_eq!(stepper.step().unwrap(), Some(Reduction(r.0, r.1)));
+                    return Ok(r);
+                }
+            }
+        })();
+
+        match (expected, result) {
+            (Ok(expected), Ok(result)) => {
+                assert_eq!(expected.0, result.0);
+                assert!(crate::test_ops::node_eq(&allocator, expected.1, result.1));
+            }
+            (Err(expected), Err(result)) => {
+                assert_eq!(expected.1, result.1);
+            }
+            (expected, result) => {
+                panic!("prg: {} expected: {expected:?} got: {result:?}", t.prg);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stepper_inspect_state() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut allocator = Allocator::new();
+
+    // the coinid operator is only available inside the softfork guard
+    let program = check(parse_exp(
+        &mut allocator,
+        "(softfork (q . 1432) (q . 0) (q a (i (= (coinid (q . 0x1234500000000000000000000000000000000000000000000000000000000000) (q . 0x6789abcdef000000000000000000000000000000000000000000000000000000) (q . 123456789)) (q . 0x69bfe81b052bfc6bd7f3fb9167fec61793175b897c16a35827f947d5cc98e4bc)) (q . 0) (q x)) (q . ())) (q . ()))",
+    ));
+    let args = allocator.nil();
+    let dialect = ChiaDialect::new(0);
+
+    let mut stepper = RunProgramStepper::new(&mut allocator, &dialect, program, args, 0).unwrap();
+
+    // the initial evaluation schedules the softfork operator and its four
+    // arguments
+    assert_eq!(
+        stepper.op_stack(),
+        &[
+            Operation::Apply,
+            Operation::SwapEval,
+            Operation::SwapEval,
+            Operation::SwapEval,
+            Operation::SwapEval
+        ]
+    );
+    assert_eq!(stepper.env_stack(), &[args]);
+    assert_eq!(stepper.val_stack().len(), 6);
+    assert!(stepper.softfork_guard().is_none());
+
+    let mut saw_guard = false;
+    let mut last_cost = stepper.cost();
+    let result = loop {
+        if let Some(r) = stepper.step().unwrap() {
+            break r;
+        }
+        assert!(stepper.cost() >= last_cost);
+        last_cost = stepper.cost();
+        if let Some(guard) = stepper.softfork_guard() {
+            saw_guard = true;
+            assert_eq!(guard.operator_set(), OperatorSet::BLS);
+            assert!(stepper.op_stack().contains(&Operation::ExitGuard));
+            assert!(stepper.cost() <= guard.expected_cost());
+        }
+    };
+    assert!(saw_guard);
+    assert!(stepper.softfork_guard().is_none());
+    assert_eq!(result.1, NodePtr::NIL);
+}
+
 #[cfg(feature = "counters")]
 #[test]
 fn test_counters() {
@@ -1347,3 +2108,400 @@ fn test_counters() {
 
     assert_eq!(result.unwrap().0, cost);
 }
+
+#[cfg(feature = "profiler")]
+#[test]
+fn test_profile_matches_cost() {
+    use crate::chia_dialect::ChiaDialect;
+
+    // the profile must account for every unit of cost, and profiling must not
+    // affect the result
+    for t in TEST_CASES {
+        let mut allocator = Allocator::new();
+
+        let program = check(parse_exp(&mut allocator, t.prg));
+        let args = check(parse_exp(&mut allocator, t.args));
+        let dialect = ChiaDialect::new(t.flags);
+
+        let expected = run_program(&mut allocator, &dialect, program, args, t.cost);
+        let (profile, result) =
+            run_program_with_profile(&mut allocator, &dialect, program, args, t.cost);
+
+        match (expected, result) {
+            (Ok(expected), Ok(result)) => {
+                assert_eq!(expected.0, result.0);
+                assert_eq!(profile.total_cost(), result.0);
+                assert!(crate::test_ops::node_eq(&allocator, expected.1, result.1));
+            }
+            (Err(expected), Err(result)) => {
+                assert_eq!(expected.1, result.1);
+            }
+            (expected, result) => {
+                panic!("prg: {} expected: {expected:?} got: {result:?}", t.prg);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "profiler")]
+#[test]
+fn test_profile() {
+    use crate::chia_dialect::ChiaDialect;
+    use crate::cost::CostSchedule;
+
+    let mut a = Allocator::new();
+
+    // (+ 2 (q . 0x1234)), a softfork guard calling coinid and an
+    // (a (q . 2) 1) apply
+    let program = check(parse_exp(
+        &mut a,
+        "(c (+ 2 (q . 0x1234)) (c (softfork (q . 1432) (q . 0) (q . (a (i (= (coinid (q . 0x1234500000000000000000000000000000000000000000000000000000000000) (q . 0x6789abcdef000000000000000000000000000000000000000000000000000000) (q . 123456789)) (q . 0x69bfe81b052bfc6bd7f3fb9167fec61793175b897c16a35827f947d5cc98e4bc)) (q . 0) (q x)) (q . ()))) (q . ())) (a (q . 2) 1)))",
+    ));
+    let args = check(parse_exp(&mut a, "(7 . 8)"));
+
+    let expected = run_program(&mut a, &ChiaDialect::new(0), program, args, 0)
+        .unwrap()
+        .0;
+    let (profile, result) =
+        run_program_with_profile(&mut a, &ChiaDialect::new(0), program, args, 0);
+    let result = result.unwrap();
+    assert_eq!(result.0, expected);
+    assert_eq!(profile.total_cost(), expected);
+
+    let add = &profile.ops[&vec![16_u8]];
+    assert_eq!(add.calls, 1);
+    // 0x07 and 0x1234
+    assert_eq!(add.arg_bytes, 3);
+
+    let coinid = &profile.ops[&vec![48_u8]];
+    assert_eq!(coinid.calls, 1);
+    assert_eq!(coinid.arg_bytes, 32 + 32 + 4);
+
+    assert_eq!(profile.ops[&vec![4_u8]].calls, 2);
+    assert_eq!(profile.softfork.calls, 1);
+    assert_eq!(profile.softfork.cost, CostSchedule::DEFAULT.guard_cost);
+    assert_eq!(profile.apply.calls, 2);
+    assert_eq!(profile.apply.cost, 2 * CostSchedule::DEFAULT.apply_cost);
+    // the paths 2 (in the main program), 1 and 2 (inside the apply) and the
+    // nil program applied inside the softfork guard
+    assert_eq!(profile.path.calls, 4);
+    assert_eq!(profile.path.arg_bytes, 3);
+    assert_eq!(
+        profile.dispatch.cost,
+        profile.dispatch.calls * CostSchedule::DEFAULT.op_cost
+    );
+}
+
+#[test]
+fn test_error_kinds() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let run = |a: &mut Allocator, prg: &str, max_cost: Cost| -> EvalErrKind {
+        let program = check(parse_exp(a, prg));
+        let args = a.nil();
+        run_program(a, &ChiaDialect::new(0), program, args, max_cost)
+            .unwrap_err()
+            .1
+    };
+
+    let mut a = Allocator::new();
+    assert_eq!(run(&mut a, "(x (q . 1))", 0), EvalErrKind::Raise);
+    assert_eq!(
+        run(&mut a, "(+ (q . 1) (q . 2))", 10),
+        EvalErrKind::CostExceeded
+    );
+    assert_eq!(run(&mut a, "(f (q . 1))", 0), EvalErrKind::FirstOfNonCons);
+    assert_eq!(run(&mut a, "(f 2)", 0), EvalErrKind::PathIntoAtom);
+
+    // concatenating the 100 byte argument 12 times exceeds the heap limit
+    let mut a = Allocator::new_limited(1000);
+    let program = check(parse_exp(&mut a, "(concat 2 2 2 2 2 2 2 2 2 2 2 2)"));
+    let atom = a.new_atom(&[0x55; 100]).unwrap();
+    let args = a.new_pair(atom, a.nil()).unwrap();
+    assert_eq!(
+        run_program(&mut a, &ChiaDialect::new(0), program, args, 0)
+            .unwrap_err()
+            .1,
+        EvalErrKind::OutOfMemory
+    );
+}
+
+#[cfg(test)]
+#[rstest]
+// the failing operator is the innermost frame. The operands of the pending
+// frames are the ones evaluated so far
+#[case(
+    "(+ (q . 1) (* (q . 2) (f (q . 3))))",
+    "()",
+    &[("+", "()"), ("*", "()"), ("f", "(3)")]
+)]
+#[case("(+ (f (q . 3)) (q . 5))", "()", &[("+", "(5)"), ("f", "(3)")])]
+// the failure isn't in an operator, but in the path lookup of the operand
+#[case("(+ 2 (q . 1))", "1", &[("+", "(1)")])]
+// the apply operator
+#[case("(a (q . (x (q . 7))) (q . 5))", "()", &[("x", "(7)")])]
+#[case("(a (q . 5) (q . 1))", "()", &[("a", "(5 1)")])]
+fn test_backtrace(#[case] prg: &str, #[case] args: &str, #[case] expected: &[(&str, &str)]) {
+    use crate::chia_dialect::ChiaDialect;
+    use crate::test_ops::node_eq;
+
+    let mut a = Allocator::new();
+    let program = check(parse_exp(&mut a, prg));
+    let args = check(parse_exp(&mut a, args));
+
+    let expected_err = run_program(&mut a, &ChiaDialect::new(0), program, args, 0).unwrap_err();
+    let (err, frames) =
+        run_program_with_backtrace(&mut a, &ChiaDialect::new(0), program, args, 0).unwrap_err();
+    assert_eq!(err, expected_err);
+
+    assert_eq!(frames.len(), expected.len());
+    for (frame, (operator, operands)) in frames.iter().zip(expected) {
+        let operator = check(parse_exp(&mut a, operator));
+        let operands = check(parse_exp(&mut a, operands));
+        assert!(node_eq(&a, frame.operator, operator));
+        assert!(node_eq(&a, frame.operands, operands));
+    }
+}
+
+#[test]
+fn test_backtrace_env() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut a = Allocator::new();
+    let program = check(parse_exp(&mut a, "(a (q . (+ (q . 1) (f 2))) (q . (5 6)))"));
+    let args = a.nil();
+    let (err, frames) =
+        run_program_with_backtrace(&mut a, &ChiaDialect::new(0), program, args, 0).unwrap_err();
+    assert_eq!(err.1, EvalErrKind::FirstOfNonCons);
+    assert_eq!(frames.len(), 2);
+
+    // the frames inside the applied program have the new environment
+    let env = check(parse_exp(&mut a, "(5 6)"));
+    assert!(crate::test_ops::node_eq(&a, frames[0].env, env));
+    assert!(crate::test_ops::node_eq(&a, frames[1].env, env));
+}
+
+#[test]
+fn test_backtrace_success() {
+    use crate::chia_dialect::ChiaDialect;
+
+    // when there's no error, the result is the same as run_program()
+    for t in TEST_CASES {
+        let mut a = Allocator::new();
+        let program = check(parse_exp(&mut a, t.prg));
+        let args = check(parse_exp(&mut a, t.args));
+        let dialect = ChiaDialect::new(t.flags);
+
+        let expected = run_program(&mut a, &dialect, program, args, t.cost);
+        let result = run_program_with_backtrace(&mut a, &dialect, program, args, t.cost);
+        match (expected, result) {
+            (Ok(expected), Ok(result)) => {
+                assert_eq!(expected.0, result.0);
+                assert!(crate::test_ops::node_eq(&a, expected.1, result.1));
+            }
+            (Err(expected), Err((result, _))) => {
+                assert_eq!(expected.1, result.1);
+            }
+            (expected, result) => {
+                panic!("prg: {} expected: {expected:?} got: {result:?}", t.prg);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_interrupt() {
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut a = Allocator::new();
+    let program = check(parse_exp(&mut a, "(+ (q . 1) (q . 2))"));
+    let args = a.nil();
+    let dialect = ChiaDialect::new(0);
+
+    // no interrupt conditions are met, the result and cost are unaffected
+    let expected = run_program(&mut a, &dialect, program, args, 0).unwrap();
+    let interrupt = Interrupt::default()
+        .with_timeout(Duration::from_secs(3600))
+        .with_cancel_flag(Arc::new(AtomicBool::new(false)));
+    let result = run_program_with_interrupt(&mut a, &dialect, program, args, 0, &interrupt);
+    assert_eq!(result.unwrap().0, expected.0);
+
+    let interrupt = Interrupt::default().with_deadline(Instant::now());
+    let result = run_program_with_interrupt(&mut a, &dialect, program, args, 0, &interrupt);
+    assert_eq!(result.unwrap_err().1, EvalErrKind::Timeout);
+
+    let interrupt = Interrupt::default().with_cancel_flag(Arc::new(AtomicBool::new(true)));
+    let result = run_program_with_interrupt(&mut a, &dialect, program, args, 0, &interrupt);
+    assert_eq!(result.unwrap_err().1, EvalErrKind::Cancelled);
+}
+
+#[test]
+fn test_interrupt_cancel_from_other_thread() {
+    use crate::runtime_dialect::RuntimeDialect;
+
+    let mut a = Allocator::new();
+    // this program loops forever, calling operator 64 in every iteration
+    let program = check(parse_exp(&mut a, "(a 2 (c 2 ()))"));
+    let args = check(parse_exp(&mut a, "((a 2 (c 2 (64))))"));
+
+    // operator 64 tells the other thread the program is running, and then
+    // blocks until it has set the cancel flag. This way, the program is
+    // guaranteed to be running when it's cancelled, regardless of timing
+    let started = Arc::new(AtomicBool::new(false));
+    let cancel = Arc::new(AtomicBool::new(false));
+    let dialect = RuntimeDialect::chia(0).with_operator(&[64], {
+        let started = started.clone();
+        let cancel = cancel.clone();
+        move |a: &mut Allocator, _args: NodePtr, _max_cost: Cost| -> Response {
+            started.store(true, Ordering::Relaxed);
+            while !cancel.load(Ordering::Relaxed) {
+                std::thread::yield_now();
+            }
+            Ok(Reduction(1, a.nil()))
+        }
+    });
+
+    let interrupt = Interrupt::default().with_cancel_flag(cancel.clone());
+    let thread = std::thread::spawn(move || {
+        while !started.load(Ordering::Relaxed) {
+            std::thread::yield_now();
+        }
+        cancel.store(true, Ordering::Relaxed);
+    });
+    let result = run_program_with_interrupt(&mut a, &dialect, program, args, 0, &interrupt);
+    thread.join().unwrap();
+    assert_eq!(result.unwrap_err().1, EvalErrKind::Cancelled);
+}
+
+#[test]
+fn test_cost_schedule() {
+    use crate::chia_dialect::ChiaDialect;
+    use crate::cost::CostSchedule;
+
+    let mut a = Allocator::new();
+    let program = check(parse_exp(&mut a, "(c (q . 1) (f 1))"));
+    let args = check(parse_exp(&mut a, "(2)"));
+
+    let dialect = ChiaDialect::new(0);
+    let Reduction(cost, _) = run_program(&mut a, &dialect, program, args, 1000000).unwrap();
+    // two operators, quote, path lookup, first and cons
+    assert_eq!(cost, 2 + 20 + 44 + 30 + 50);
+
+    let costs = CostSchedule {
+        version: 2,
+        op_cost: 5,
+        quote_cost: 1,
+        traverse_base_cost: 10,
+        first_cost: 2,
+        cons_cost: 3,
+        ..CostSchedule::DEFAULT
+    };
+    let dialect = ChiaDialect::new(0).with_cost_schedule(costs).unwrap();
+    let Reduction(cost, _) = run_program(&mut a, &dialect, program, args, 1000000).unwrap();
+    assert_eq!(cost, 2 * 5 + 1 + 14 + 2 + 3);
+}
+
+#[test]
+fn test_extreme_cost_schedule() {
+    use crate::chia_dialect::ChiaDialect;
+    use crate::cost::CostSchedule;
+
+    // every cost is as large as it can be. The cost computations must
+    // saturate rather than overflow
+    let mut costs = CostSchedule::DEFAULT;
+    for (name, _) in CostSchedule::DEFAULT.costs() {
+        *costs.cost_mut(name).unwrap() = Cost::MAX;
+    }
+    let dialect = ChiaDialect::new(0).with_cost_schedule(costs).unwrap();
+
+    let mut a = Allocator::new();
+    let program = check(parse_exp(
+        &mut a,
+        "(c (sha256 (q . 1) 2) (c (* 2 2 2) (c (concat 2 2) (c (modpow 2 2 2) (c (strlen 2) (c (= 2 2) (c (> 2 2) (c (ash 2 (q . 3)) (c (logand 2 2) (c (divmod 2 (q . 3)) (c (a (q . 2) 1) (q))))))))))))",
+    ));
+    let args = check(parse_exp(
+        &mut a,
+        "(0x0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20)",
+    ));
+
+    // a saturated cost exhausts any budget, including the unlimited one
+    for max_cost in [11_000_000_000, 0] {
+        let result = run_program(&mut a, &dialect, program, args, max_cost);
+        assert_eq!(result.unwrap_err().1, EvalErrKind::CostExceeded);
+    }
+}
+
+#[test]
+fn test_run_options_keep_allocator_limits() {
+    use crate::allocator::{MAX_HEAP_LIMIT, MAX_NODE_COUNT};
+    use crate::chia_dialect::ChiaDialect;
+
+    let mut a = Allocator::new_with_limits(MAX_HEAP_LIMIT, MAX_NODE_COUNT, MAX_NODE_COUNT);
+    let limits = a.limits();
+    let program = check(parse_exp(&mut a, "(c (q . 1) (q . 2))"));
+    let env = a.nil();
+    let dialect = ChiaDialect::new(0);
+
+    // the options clamp the limits while the program runs, but the allocator
+    // keeps its own (larger) limits afterwards
+    let options = RunOptions::consensus().with_max_pairs(1);
+    let result = run_program_with_options(&mut a, &dialect, program, env, &options);
+    assert_eq!(result.unwrap_err().1, EvalErrKind::TooManyPairs);
+    assert_eq!(a.limits(), limits);
+
+    run_program_with_options(&mut a, &dialect, program, env, &RunOptions::consensus()).unwrap();
+    assert_eq!(a.limits(), limits);
+}
+
+#[test]
+fn test_run_options_presets() {
+    let options = RunOptions::from_flags(MEMPOOL_MODE);
+    assert_eq!(options.heap_limit(), MEMPOOL_HEAP_LIMIT);
+    assert_eq!(options.stack_limit(), STACK_SIZE_LIMIT);
+    assert_eq!(options.max_atoms(), MAX_NUM_ATOMS);
+    assert_eq!(options.max_pairs(), MAX_NUM_PAIRS);
+
+    let options = RunOptions::from_flags(NO_UNKNOWN_OPS);
+    assert_eq!(options.heap_limit(), u32::MAX as usize);
+    assert_eq!(options.max_cost(), 0);
+}
+
+#[cfg(test)]
+#[rstest]
+#[case(RunOptions::consensus(), None)]
+#[case(RunOptions::consensus().with_max_cost(1000), Some(EvalErrKind::CostExceeded))]
+#[case(
+    RunOptions::consensus().with_stack_limit(10),
+    Some(EvalErrKind::ValueStackLimitReached)
+)]
+#[case(RunOptions::mempool().with_max_pairs(60), Some(EvalErrKind::TooManyPairs))]
+#[case(RunOptions::mempool().with_max_atoms(34), Some(EvalErrKind::TooManyAtoms))]
+#[case(RunOptions::mempool().with_heap_limit(100), Some(EvalErrKind::OutOfMemory))]
+fn test_run_options(#[case] options: RunOptions, #[case] expected: Option<EvalErrKind>) {
+    use crate::chia_dialect::ChiaDialect;
+
+    // the limits are enforced even though the allocator wasn't created by
+    // options.new_allocator()
+    let mut a = Allocator::new();
+    // this builds a list of 12 atoms, one concatenated and one computed. The
+    // program itself uses 44 pairs and 32 atoms
+    let program = check(parse_exp(
+        &mut a,
+        "(c (concat 2 2) (c 2 (c 2 (c 2 (c 2 (c 2 (c 2 (c 2 (c 2 (c 2 (c 2 (c (+ 2 (q . 1)) ()))))))))))))",
+    ));
+    let atom = a.new_atom(&[0x7f; 40]).unwrap();
+    let args = a.new_pair(atom, a.nil()).unwrap();
+
+    let result = run_program_with_options(&mut a, &ChiaDialect::new(0), program, args, &options);
+    match expected {
+        None => {
+            assert_eq!(result.unwrap().0, 4016);
+        }
+        Some(kind) => {
+            assert_eq!(result.unwrap_err().1, kind);
+        }
+    }
+
+    // once the program completes, the allocator's own limits apply again
+    assert!(a.new_atom(&[0x7f; 1000]).is_ok());
+}
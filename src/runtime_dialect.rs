@@ -1,19 +1,83 @@
 use crate::allocator::{Allocator, NodePtr};
-use crate::chia_dialect::NO_UNKNOWN_OPS;
-use crate::cost::Cost;
-use crate::dialect::{Dialect, OperatorSet};
+use crate::chia_dialect::{ENABLE_BLS_OPS_OUTSIDE_GUARD, ENABLE_FIXED_DIV, NO_UNKNOWN_OPS};
+use crate::cost::{Cost, CostSchedule, CostScheduleError};
+use crate::dialect::{Dialect, Operator, OperatorSet};
 use crate::err_utils::err;
-use crate::f_table::{f_lookup_for_hashmap, FLookup};
-use crate::more_ops::op_unknown;
-use crate::reduction::Response;
+use crate::f_table::{OpTable, UnknownOperator};
+use crate::more_ops::op_unknown_with_costs;
+use crate::reduction::{EvalErrKind, Response};
 use std::collections::HashMap;
 
+/// The operators of the chia dialect (outside of softfork guards), by name
+/// and opcode. Division is left out, since it depends on `ENABLE_FIXED_DIV`
+pub const CHIA_OPS: &[(&str, &[u8])] = &[
+    ("op_if", &[3]),
+    ("op_cons", &[4]),
+    ("op_first", &[5]),
+    ("op_rest", &[6]),
+    ("op_listp", &[7]),
+    ("op_raise", &[8]),
+    ("op_eq", &[9]),
+    ("op_gr_bytes", &[10]),
+    ("op_sha256", &[11]),
+    ("op_substr", &[12]),
+    ("op_strlen", &[13]),
+    ("op_concat", &[14]),
+    ("op_add", &[16]),
+    ("op_subtract", &[17]),
+    ("op_multiply", &[18]),
+    ("op_divmod", &[20]),
+    ("op_gr", &[21]),
+    ("op_ash", &[22]),
+    ("op_lsh", &[23]),
+    ("op_logand", &[24]),
+    ("op_logior", &[25]),
+    ("op_logxor", &[26]),
+    ("op_lognot", &[27]),
+    ("op_point_add", &[29]),
+    ("op_pubkey_for_exp", &[30]),
+    ("op_not", &[32]),
+    ("op_any", &[33]),
+    ("op_all", &[34]),
+    ("op_secp256k1_verify", &[0x13, 0xd6, 0x1f, 0x00]),
+    ("op_secp256r1_verify", &[0x1c, 0x3a, 0x8f, 0x00]),
+];
+
+/// The operators the chia dialect enables with softfork extension 0
+pub const BLS_OPS: &[(&str, &[u8])] = &[
+    ("op_coinid", &[48]),
+    ("op_g1_subtract", &[49]),
+    ("op_g1_multiply", &[50]),
+    ("op_g1_negate", &[51]),
+    ("op_g2_add", &[52]),
+    ("op_g2_subtract", &[53]),
+    ("op_g2_multiply", &[54]),
+    ("op_g2_negate", &[55]),
+    ("op_g1_map", &[56]),
+    ("op_g2_map", &[57]),
+    ("op_bls_pairing_identity", &[58]),
+    ("op_bls_verify", &[59]),
+    ("op_modpow", &[60]),
+    ("op_mod", &[61]),
+];
+
+fn op_map(ops: &[(&str, &[u8])]) -> HashMap<String, Vec<u8>> {
+    ops.iter()
+        .map(|(name, opcode)| (name.to_string(), opcode.to_vec()))
+        .collect()
+}
+
 pub struct RuntimeDialect {
-    f_lookup: FLookup,
+    ops: OpTable,
+    // operators only available inside a softfork guard, by extension
+    extensions: HashMap<u32, OpTable>,
+    // custom operators, by opcode. These take precedence over the native ones
+    operators: HashMap<Vec<u8>, Box<dyn Operator>>,
     quote_kw: Vec<u8>,
     apply_kw: Vec<u8>,
     softfork_kw: Vec<u8>,
     flags: u32,
+    costs: CostSchedule,
 }
 
 impl RuntimeDialect {
@@ -22,14 +86,81 @@ impl RuntimeDialect {
         quote_kw: Vec<u8>,
         apply_kw: Vec<u8>,
         flags: u32,
-    ) -> RuntimeDialect {
-        RuntimeDialect {
-            f_lookup: f_lookup_for_hashmap(op_map),
+    ) -> Result<RuntimeDialect, UnknownOperator> {
+        Ok(RuntimeDialect {
+            ops: OpTable::from_hashmap(op_map)?,
+            extensions: HashMap::new(),
+            operators: HashMap::new(),
             quote_kw,
             apply_kw,
             softfork_kw: vec![36], // softfork opcode
             flags,
+            costs: CostSchedule::DEFAULT,
+        })
+    }
+
+    /// builds a `RuntimeDialect` equivalent to `ChiaDialect::new(flags)`.
+    /// Operators can then be added or replaced, e.g. to experiment with
+    /// changes to the chia dialect
+    pub fn chia(flags: u32) -> RuntimeDialect {
+        let mut ops = op_map(CHIA_OPS);
+        let div = if (flags & ENABLE_FIXED_DIV) != 0 {
+            "op_div_fixed"
+        } else {
+            "op_div"
+        };
+        ops.insert(div.to_string(), vec![19]);
+        if (flags & ENABLE_BLS_OPS_OUTSIDE_GUARD) != 0 {
+            ops.extend(op_map(BLS_OPS));
         }
+        RuntimeDialect::new(ops, vec![1], vec![2], flags)
+            .expect("CHIA_OPS are native operators")
+            .with_extension(0, op_map(BLS_OPS))
+            .expect("BLS_OPS are native operators")
+    }
+
+    /// Fails if the schedule fails `CostSchedule::validate()`
+    pub fn with_cost_schedule(mut self, costs: CostSchedule) -> Result<Self, CostScheduleError> {
+        costs.validate()?;
+        self.costs = costs;
+        Ok(self)
+    }
+
+    pub fn with_softfork_kw(mut self, softfork_kw: Vec<u8>) -> Self {
+        self.softfork_kw = softfork_kw;
+        self
+    }
+
+    /// Adds the operators in `op_map` to the softfork extension `ext`. They
+    /// are only available inside a softfork guard enabling that extension,
+    /// in addition to the operators available outside of it.
+    pub fn add_extension(
+        &mut self,
+        ext: u32,
+        op_map: HashMap<String, Vec<u8>>,
+    ) -> Result<(), UnknownOperator> {
+        self.extensions.insert(ext, OpTable::from_hashmap(op_map)?);
+        Ok(())
+    }
+
+    pub fn with_extension(
+        mut self,
+        ext: u32,
+        op_map: HashMap<String, Vec<u8>>,
+    ) -> Result<Self, UnknownOperator> {
+        self.add_extension(ext, op_map)?;
+        Ok(self)
+    }
+
+    /// Adds (or replaces) the operator for the (possibly multi-byte) `opcode`.
+    /// Custom operators take precedence over the native ones.
+    pub fn add_operator(&mut self, opcode: &[u8], op: impl Operator + 'static) {
+        self.operators.insert(opcode.to_vec(), Box::new(op));
+    }
+
+    pub fn with_operator(mut self, opcode: &[u8], op: impl Operator + 'static) -> Self {
+        self.add_operator(opcode, op);
+        self
     }
 }
 
@@ -40,20 +171,25 @@ impl Dialect for RuntimeDialect {
         o: NodePtr,
         argument_list: NodePtr,
         max_cost: Cost,
-        _extensions: OperatorSet,
+        extension: OperatorSet,
     ) -> Response {
         let atom = allocator.atom(o);
         let b = atom.as_ref();
 
-        if b.len() == 1 {
-            if let Some(f) = self.f_lookup[b[0] as usize] {
-                return f(allocator, argument_list, max_cost);
-            }
+        if let Some(op) = self.operators.get(b) {
+            return op.invoke(allocator, argument_list, max_cost);
+        }
+        let ext_op = match extension {
+            OperatorSet::Extension(ext) => self.extensions.get(&ext).and_then(|t| t.get(b)),
+            _ => None,
+        };
+        if let Some(f) = ext_op.or_else(|| self.ops.get(b)) {
+            return f(allocator, argument_list, max_cost, &self.costs);
         }
         if (self.flags & NO_UNKNOWN_OPS) != 0 {
-            err(o, "unimplemented operator")
+            err(o, EvalErrKind::UnimplementedOperator)
         } else {
-            op_unknown(allocator, o, argument_list, max_cost)
+            op_unknown_with_costs(allocator, o, argument_list, max_cost, &self.costs)
         }
     }
 
@@ -67,11 +203,173 @@ impl Dialect for RuntimeDialect {
         self.softfork_kw[0] as u32
     }
 
-    fn softfork_extension(&self, _ext: u32) -> OperatorSet {
-        OperatorSet::Default
+    fn softfork_extension(&self, ext: u32) -> OperatorSet {
+        if self.extensions.contains_key(&ext) {
+            OperatorSet::Extension(ext)
+        } else {
+            OperatorSet::Default
+        }
     }
 
     fn allow_unknown_ops(&self) -> bool {
         (self.flags & NO_UNKNOWN_OPS) == 0
     }
+
+    fn cost_schedule(&self) -> &CostSchedule {
+        &self.costs
+    }
+}
+
+#[cfg(test)]
+use crate::reduction::Reduction;
+#[cfg(test)]
+use crate::run_program::run_program;
+#[cfg(test)]
+use crate::test_ops::parse_exp;
+
+#[cfg(test)]
+fn dialect() -> RuntimeDialect {
+    let op_map = HashMap::from([
+        ("op_if".to_string(), vec![3]),
+        ("op_eq".to_string(), vec![9]),
+        ("op_add".to_string(), vec![16]),
+    ]);
+    RuntimeDialect::new(op_map, vec![1], vec![2], NO_UNKNOWN_OPS).unwrap()
+}
+
+#[cfg(test)]
+fn run(d: &RuntimeDialect, prg: &str) -> Response {
+    let mut a = Allocator::new();
+    let program = parse_exp(&mut a, prg).0;
+    let args = a.nil();
+    run_program(&mut a, d, program, args, 100000)
+}
+
+#[test]
+fn test_unknown_operator_name() {
+    let op_map = HashMap::from([("op_foobar".to_string(), vec![3])]);
+    let Err(e) = RuntimeDialect::new(op_map, vec![1], vec![2], 0) else {
+        panic!("expected failure");
+    };
+    assert_eq!(e, UnknownOperator("op_foobar".to_string()));
+    assert_eq!(e.to_string(), "can't find native operator op_foobar");
+}
+
+#[test]
+fn test_closure_operator() {
+    // a mock signature check, accepting a single known signature. It uses the
+    // 4 byte opcode of secp256k1_verify
+    let good_sig = b"good".to_vec();
+    let d = dialect().with_operator(
+        &[0x13, 0xd6, 0x1f, 0x00],
+        move |a: &mut Allocator, args: NodePtr, _max_cost: Cost| -> Response {
+            let sig = crate::op_utils::atom(a, crate::op_utils::first(a, args)?, "verify")?;
+            if sig.as_ref() != good_sig.as_slice() {
+                let msg = format!("bad signature: {}", hex::encode(sig.as_ref()));
+                return err(args, EvalErrKind::OperatorFailed(msg));
+            }
+            Ok(Reduction(1234, a.nil()))
+        },
+    );
+
+    let r = run(&d, "(0x13d61f00 (q . \"good\"))").unwrap();
+    assert_eq!(r.0, 1255);
+    let r = run(&d, "(0x13d61f00 (q . \"bad\"))").unwrap_err();
+    assert_eq!(
+        r.1,
+        EvalErrKind::OperatorFailed("bad signature: 626164".to_string())
+    );
+    assert_eq!(r.1.to_string(), "bad signature: 626164");
+
+    // other multi-byte opcodes remain unknown
+    let r = run(&d, "(0x13d61f01 (q . \"good\"))").unwrap_err();
+    assert_eq!(r.1, EvalErrKind::UnimplementedOperator);
+}
+
+#[test]
+fn test_override_native_operator() {
+    let mut d = dialect();
+    assert_eq!(run(&d, "(+ (q . 1) (q . 2))").unwrap().0, 796);
+
+    // custom operators take precedence over native ones
+    d.add_operator(
+        &[16],
+        |a: &mut Allocator, _args: NodePtr, _max_cost: Cost| {
+            Ok(Reduction(1, a.new_small_number(1337)?))
+        },
+    );
+    let r = run(&d, "(i (= (+ (q . 1) (q . 2)) (q . 1337)) (q . 1) (q x))").unwrap();
+    assert_eq!(r.0, 258);
+}
+
+#[cfg(test)]
+use crate::chia_dialect::ChiaDialect;
+#[cfg(test)]
+use crate::serde::node_to_bytes;
+#[cfg(test)]
+use rstest::rstest;
+
+// calls the operator directly, returning the cost and serialized result, or
+// the error
+#[cfg(test)]
+fn call_op<D: Dialect>(
+    d: &D,
+    opcode: &[u8],
+    args: &str,
+    ext: Option<u32>,
+) -> Result<(Cost, Vec<u8>), EvalErrKind> {
+    let mut a = Allocator::new();
+    let o = a.new_atom(opcode).unwrap();
+    let args = parse_exp(&mut a, args).0;
+    let extension = ext.map_or(OperatorSet::Default, |e| d.softfork_extension(e));
+    match d.op(&mut a, o, args, 11000000000, extension) {
+        Ok(Reduction(cost, node)) => Ok((cost, node_to_bytes(&a, node).unwrap())),
+        Err(e) => Err(e.1),
+    }
+}
+
+#[cfg(test)]
+#[rstest]
+#[case(0)]
+#[case(NO_UNKNOWN_OPS)]
+#[case(ENABLE_FIXED_DIV)]
+#[case(ENABLE_BLS_OPS_OUTSIDE_GUARD)]
+#[case(NO_UNKNOWN_OPS | ENABLE_FIXED_DIV | ENABLE_BLS_OPS_OUTSIDE_GUARD)]
+fn test_matches_chia_dialect(#[case] flags: u32) {
+    let chia = ChiaDialect::new(flags);
+    let runtime = RuntimeDialect::chia(flags);
+
+    assert_eq!(chia.quote_kw(), runtime.quote_kw());
+    assert_eq!(chia.apply_kw(), runtime.apply_kw());
+    assert_eq!(chia.softfork_kw(), runtime.softfork_kw());
+    assert_eq!(chia.allow_unknown_ops(), runtime.allow_unknown_ops());
+    for ext in 0..3 {
+        assert_eq!(
+            chia.softfork_extension(ext) == OperatorSet::Default,
+            runtime.softfork_extension(ext) == OperatorSet::Default
+        );
+    }
+
+    let mut opcodes: Vec<Vec<u8>> = (0..=255).map(|op| vec![op]).collect();
+    opcodes.extend([
+        vec![],
+        vec![0, 3],
+        vec![0x13, 0xd6, 0x1f, 0x00],
+        vec![0x1c, 0x3a, 0x8f, 0x00],
+        vec![0x13, 0xd6, 0x1f, 0x01],
+        vec![0x00, 0x00, 0x00, 0x10],
+        vec![0xff, 0xff, 0xff, 0xff],
+        vec![0x13, 0xd6, 0x1f, 0x00, 0x00],
+    ]);
+    for opcode in &opcodes {
+        for args in ["()", "(5 3)", "(-7 2)", "(0x0a 0x0b 0x0c)", "((1 . 2) 3)"] {
+            for ext in [None, Some(0), Some(1)] {
+                assert_eq!(
+                    call_op(&chia, opcode, args, ext),
+                    call_op(&runtime, opcode, args, ext),
+                    "opcode: {opcode:?} args: {args} extension: {ext:?}"
+                );
+            }
+        }
+    }
 }
@@ -1,18 +1,25 @@
 use crate::allocator::{Allocator, NodePtr};
-use crate::cost::{check_cost, Cost};
+use crate::cost::{check_cost, default_cost_ops, Cost, CostSchedule};
 use crate::err_utils::err;
 use crate::op_utils::{atom, get_args};
-use crate::reduction::{Reduction, Response};
+use crate::reduction::{EvalErrKind, Reduction, Response};
 use k256::ecdsa::{Signature as K1Signature, VerifyingKey as K1VerifyingKey};
 use p256::ecdsa::signature::hazmat::PrehashVerifier;
 use p256::ecdsa::{Signature as P1Signature, VerifyingKey as P1VerifyingKey};
 
-const SECP256R1_VERIFY_COST: Cost = 1850000;
-const SECP256K1_VERIFY_COST: Cost = 1300000;
+default_cost_ops! {
+    op_secp256r1_verify => op_secp256r1_verify_with_costs,
+    op_secp256k1_verify => op_secp256k1_verify_with_costs,
+}
 
 // expects: pubkey msg sig
-pub fn op_secp256r1_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
-    let cost = SECP256R1_VERIFY_COST;
+pub fn op_secp256r1_verify_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let cost = costs.secp256r1_verify_cost;
     check_cost(a, cost, max_cost)?;
 
     let [pubkey, msg, sig] = get_args::<3>(a, input, "secp256r1_verify")?;
@@ -20,32 +27,37 @@ pub fn op_secp256r1_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) ->
     // first argument is sec1 encoded pubkey
     let pubkey = atom(a, pubkey, "secp256r1_verify pubkey")?;
     let verifier = P1VerifyingKey::from_sec1_bytes(pubkey.as_ref())
-        .or_else(|_| err(input, "secp256r1_verify pubkey is not valid"))?;
+        .or_else(|_| err(input, EvalErrKind::SecpInvalidPubkey("secp256r1_verify")))?;
 
     // second arg is sha256 hash of message
     let msg = atom(a, msg, "secp256r1_verify msg")?;
     if msg.as_ref().len() != 32 {
-        return err(input, "secp256r1_verify message digest is not 32 bytes");
+        return err(input, EvalErrKind::SecpInvalidDigest("secp256r1_verify"));
     }
 
     // third arg is a fixed-size signature
     let sig = atom(a, sig, "secp256r1_verify sig")?;
     let sig = P1Signature::from_slice(sig.as_ref())
-        .or_else(|_| err(input, "secp256r1_verify sig is not valid"))?;
+        .or_else(|_| err(input, EvalErrKind::SecpInvalidSignature("secp256r1_verify")))?;
 
     // verify signature
     let result = verifier.verify_prehash(msg.as_ref(), &sig);
 
     if result.is_err() {
-        err(input, "secp256r1_verify failed")
+        err(input, EvalErrKind::SecpVerifyFailed("secp256r1_verify"))
     } else {
         Ok(Reduction(cost, a.nil()))
     }
 }
 
 // expects: pubkey msg sig
-pub fn op_secp256k1_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) -> Response {
-    let cost = SECP256K1_VERIFY_COST;
+pub fn op_secp256k1_verify_with_costs(
+    a: &mut Allocator,
+    input: NodePtr,
+    max_cost: Cost,
+    costs: &CostSchedule,
+) -> Response {
+    let cost = costs.secp256k1_verify_cost;
     check_cost(a, cost, max_cost)?;
 
     let [pubkey, msg, sig] = get_args::<3>(a, input, "secp256k1_verify")?;
@@ -53,24 +65,24 @@ pub fn op_secp256k1_verify(a: &mut Allocator, input: NodePtr, max_cost: Cost) ->
     // first argument is sec1 encoded pubkey
     let pubkey = atom(a, pubkey, "secp256k1_verify pubkey")?;
     let verifier = K1VerifyingKey::from_sec1_bytes(pubkey.as_ref())
-        .or_else(|_| err(input, "secp256k1_verify pubkey is not valid"))?;
+        .or_else(|_| err(input, EvalErrKind::SecpInvalidPubkey("secp256k1_verify")))?;
 
     // second arg is message
     let msg = atom(a, msg, "secp256k1_verify msg")?;
     if msg.as_ref().len() != 32 {
-        return err(input, "secp256k1_verify message digest is not 32 bytes");
+        return err(input, EvalErrKind::SecpInvalidDigest("secp256k1_verify"));
     }
 
     // third arg is a fixed-size signature
     let sig = atom(a, sig, "secp256k1_verify sig")?;
     let sig = K1Signature::from_slice(sig.as_ref())
-        .or_else(|_| err(input, "secp256k1_verify sig is not valid"))?;
+        .or_else(|_| err(input, EvalErrKind::SecpInvalidSignature("secp256k1_verify")))?;
 
     // verify signature
     let result = verifier.verify_prehash(msg.as_ref(), &sig);
 
     if result.is_err() {
-        err(input, "secp256k1_verify failed")
+        err(input, EvalErrKind::SecpVerifyFailed("secp256k1_verify"))
     } else {
         Ok(Reduction(cost, a.nil()))
     }
@@ -1,9 +1,10 @@
 use std::io;
-use std::io::{Cursor, Read};
+use std::io::Cursor;
 
 use crate::allocator::{Allocator, NodePtr};
 
-use super::parse_atom::parse_atom;
+use super::limits::{DeserializeLimits, LimitTracker};
+use super::parse_atom::{atom_size, parse_atom, parse_canonical_atom, AtomSource};
 
 const CONS_BOX_MARKER: u8 = 0xff;
 
@@ -13,26 +14,56 @@ enum ParseOp {
     Cons,
 }
 
-/// deserialize a clvm node from a `std::io::Cursor`
-pub fn node_from_stream(allocator: &mut Allocator, f: &mut Cursor<&[u8]>) -> io::Result<NodePtr> {
+/// deserialize a clvm node from a stream. Byte slices (as `Cursor<&[u8]>`)
+/// are parsed without copying atoms. Any other `Read`, e.g. a file or a
+/// socket, can be wrapped in a `ReadSource`. Only the bytes of the
+/// serialization are read, anything following it is left in the stream
+pub fn node_from_stream<S: AtomSource>(
+    allocator: &mut Allocator,
+    f: &mut S,
+) -> io::Result<NodePtr> {
+    node_from_source(allocator, f, &DeserializeLimits::default(), false)
+}
+
+fn node_from_source<S: AtomSource>(
+    allocator: &mut Allocator,
+    f: &mut S,
+    limits: &DeserializeLimits,
+    strict: bool,
+) -> io::Result<NodePtr> {
     let mut values: Vec<NodePtr> = Vec::new();
     let mut ops = vec![ParseOp::SExp];
+    let mut tracker = LimitTracker::new(limits);
+    // the number of pairs above the node being parsed
+    let mut depth = 0;
 
     let mut b = [0; 1];
     while let Some(op) = ops.pop() {
         match op {
             ParseOp::SExp => {
                 f.read_exact(&mut b)?;
+                tracker.check_depth(depth)?;
                 if b[0] == CONS_BOX_MARKER {
+                    tracker.pair()?;
+                    depth += 1;
                     ops.push(ParseOp::Cons);
                     ops.push(ParseOp::SExp);
                     ops.push(ParseOp::SExp);
                 } else {
-                    values.push(parse_atom(allocator, b[0], f)?);
+                    // check the size before reading (and allocating) the atom
+                    let size = atom_size(f, b[0])?;
+                    tracker.atom(usize::try_from(size).unwrap_or(usize::MAX))?;
+                    let atom = if strict {
+                        parse_canonical_atom(allocator, b[0], size, f)?
+                    } else {
+                        parse_atom(allocator, b[0], size, f)?
+                    };
+                    values.push(atom);
                 }
             }
             ParseOp::Cons => {
                 // cons
+                depth -= 1;
                 let v2 = values.pop();
                 let v1 = values.pop();
                 values.push(allocator.new_pair(v1.unwrap(), v2.unwrap())?);
@@ -43,6 +74,24 @@ pub fn node_from_stream(allocator: &mut Allocator, f: &mut Cursor<&[u8]>) -> io:
 }
 
 pub fn node_from_bytes(allocator: &mut Allocator, b: &[u8]) -> io::Result<NodePtr> {
+    node_from_bytes_with_limits(allocator, b, &DeserializeLimits::default())
+}
+
+/// like `node_from_bytes()` but fails if the resulting tree exceeds any of
+/// the `limits`
+pub fn node_from_bytes_with_limits(
+    allocator: &mut Allocator,
+    b: &[u8],
+    limits: &DeserializeLimits,
+) -> io::Result<NodePtr> {
+    let mut buffer = Cursor::new(b);
+    node_from_source(allocator, &mut buffer, limits, false)
+}
+
+/// like `node_from_bytes()` but fails with a "non-canonical encoding" error
+/// unless every atom is serialized in its shortest form. This guarantees that
+/// there's only one serialization of any given tree
+pub fn node_from_bytes_strict(allocator: &mut Allocator, b: &[u8]) -> io::Result<NodePtr> {
     let mut buffer = Cursor::new(b);
-    node_from_stream(allocator, &mut buffer)
+    node_from_source(allocator, &mut buffer, &DeserializeLimits::default(), true)
 }
@@ -1,11 +1,12 @@
 use std::collections::HashSet;
 use std::io;
-use std::io::{Cursor, Read};
+use std::io::Cursor;
 
 use crate::allocator::{Allocator, NodePtr, SExp};
 use crate::traverse_path::traverse_path;
 
-use super::parse_atom::{parse_atom, parse_path};
+use super::limits::{DeserializeLimits, LimitTracker};
+use super::parse_atom::{atom_size, parse_atom, parse_path, AtomSource};
 
 const BACK_REFERENCE: u8 = 0xfe;
 const CONS_BOX_MARKER: u8 = 0xff;
@@ -16,37 +17,65 @@ enum ParseOp {
     Cons,
 }
 
-/// deserialize a clvm node from a `std::io::Cursor`
-pub fn node_from_stream_backrefs(
+/// deserialize a clvm node, that may contain back references, from a stream.
+/// See `node_from_stream()`. Only the bytes of the serialization are read,
+/// anything following it is left in the stream
+pub fn node_from_stream_backrefs<S: AtomSource>(
     allocator: &mut Allocator,
-    f: &mut Cursor<&[u8]>,
+    f: &mut S,
+    backref_callback: impl FnMut(NodePtr),
+) -> io::Result<NodePtr> {
+    node_from_source_backrefs(
+        allocator,
+        f,
+        &DeserializeLimits::default(),
+        backref_callback,
+    )
+}
+
+fn node_from_source_backrefs<S: AtomSource>(
+    allocator: &mut Allocator,
+    f: &mut S,
+    limits: &DeserializeLimits,
     mut backref_callback: impl FnMut(NodePtr),
 ) -> io::Result<NodePtr> {
     let mut values = allocator.nil();
     let mut ops = vec![ParseOp::SExp];
+    let mut tracker = LimitTracker::new(limits);
+    // the number of pairs above the node being parsed
+    let mut depth = 0;
 
     let mut b = [0; 1];
     while let Some(op) = ops.pop() {
         match op {
             ParseOp::SExp => {
                 f.read_exact(&mut b)?;
+                tracker.check_depth(depth)?;
                 if b[0] == CONS_BOX_MARKER {
+                    tracker.pair()?;
+                    depth += 1;
                     ops.push(ParseOp::Cons);
                     ops.push(ParseOp::SExp);
                     ops.push(ParseOp::SExp);
                 } else if b[0] == BACK_REFERENCE {
                     let path = parse_path(f)?;
+                    tracker.path(path.len())?;
                     let reduction = traverse_path(allocator, path, values)?;
                     let back_reference = reduction.1;
+                    tracker.backref(allocator, back_reference, depth)?;
                     backref_callback(back_reference);
                     values = allocator.new_pair(back_reference, values)?;
                 } else {
-                    let new_atom = parse_atom(allocator, b[0], f)?;
+                    // check the size before reading (and allocating) the atom
+                    let size = atom_size(f, b[0])?;
+                    tracker.atom(usize::try_from(size).unwrap_or(usize::MAX))?;
+                    let new_atom = parse_atom(allocator, b[0], size, f)?;
                     values = allocator.new_pair(new_atom, values)?;
                 }
             }
             ParseOp::Cons => {
                 // cons
+                depth -= 1;
                 if let SExp::Pair(v1, v2) = allocator.sexp(values) {
                     if let SExp::Pair(v3, v4) = allocator.sexp(v2) {
                         let new_root = allocator.new_pair(v3, v1)?;
@@ -63,8 +92,18 @@ pub fn node_from_stream_backrefs(
 }
 
 pub fn node_from_bytes_backrefs(allocator: &mut Allocator, b: &[u8]) -> io::Result<NodePtr> {
+    node_from_bytes_backrefs_with_limits(allocator, b, &DeserializeLimits::default())
+}
+
+/// like `node_from_bytes_backrefs()` but fails if the resulting tree, with
+/// all back references expanded, exceeds any of the `limits`
+pub fn node_from_bytes_backrefs_with_limits(
+    allocator: &mut Allocator,
+    b: &[u8],
+    limits: &DeserializeLimits,
+) -> io::Result<NodePtr> {
     let mut buffer = Cursor::new(b);
-    node_from_stream_backrefs(allocator, &mut buffer, |_node| {})
+    node_from_source_backrefs(allocator, &mut buffer, limits, |_node| {})
 }
 
 pub fn node_from_bytes_backrefs_record(
@@ -73,9 +112,14 @@ pub fn node_from_bytes_backrefs_record(
 ) -> io::Result<(NodePtr, HashSet<NodePtr>)> {
     let mut buffer = Cursor::new(b);
     let mut backrefs = HashSet::<NodePtr>::new();
-    let ret = node_from_stream_backrefs(allocator, &mut buffer, |node| {
-        backrefs.insert(node);
-    })?;
+    let ret = node_from_source_backrefs(
+        allocator,
+        &mut buffer,
+        &DeserializeLimits::default(),
+        |node| {
+            backrefs.insert(node);
+        },
+    )?;
     Ok((ret, backrefs))
 }
 
@@ -85,16 +129,13 @@ use hex::FromHex;
 #[test]
 fn test_deserialize_with_backrefs() {
     fn deserialize_check(serialization_as_hex: &str, expected_hash_as_hex: &str) {
-        use crate::serde::object_cache::{treehash, ObjectCache};
         let buf = Vec::from_hex(serialization_as_hex).unwrap();
         let mut allocator = Allocator::new();
         let node = node_from_bytes_backrefs(&mut allocator, &buf).unwrap();
 
-        let mut oc = ObjectCache::new(&allocator, treehash);
-        let calculated_hash = oc.get_or_calculate(&node).unwrap();
-        let ch: &[u8] = calculated_hash;
+        let calculated_hash = allocator.tree_hash(node);
         let expected_hash: Vec<u8> = Vec::from_hex(expected_hash_as_hex).unwrap();
-        assert_eq!(expected_hash, ch);
+        assert_eq!(expected_hash, calculated_hash);
     }
 
     // ("foobar" "foobar")
@@ -4,6 +4,7 @@ use sha2::Digest;
 
 use crate::sha2::Sha256;
 
+use super::limits::{DeserializeLimits, LimitTracker};
 use super::parse_atom::decode_size_with_offset;
 use super::utils::{copy_exactly, skip_bytes};
 
@@ -120,6 +121,19 @@ pub fn parse_triples<R: Read>(
     f: &mut R,
     calculate_tree_hashes: bool,
 ) -> Result<ParsedTriplesOutput> {
+    parse_triples_with_limits(f, calculate_tree_hashes, &DeserializeLimits::default())
+}
+
+/// like `parse_triples()` but fails if the tree exceeds any of the `limits`.
+/// Atoms that are too large are detected before they are read
+pub fn parse_triples_with_limits<R: Read>(
+    f: &mut R,
+    calculate_tree_hashes: bool,
+    limits: &DeserializeLimits,
+) -> Result<ParsedTriplesOutput> {
+    let mut tracker = LimitTracker::new(limits);
+    // the number of pairs above the object being parsed
+    let mut depth = 0;
     let mut r = Vec::new();
     let mut tree_hashes = Vec::new();
     let mut op_stack = vec![ParseOpRef::ParseObj];
@@ -132,7 +146,10 @@ pub fn parse_triples<R: Read>(
                 let start = cursor;
                 cursor += 1;
                 let b = b[0];
+                tracker.check_depth(depth)?;
                 if b == CONS_BOX_MARKER {
+                    tracker.pair()?;
+                    depth += 1;
                     let index = r.len();
                     let new_obj = ParsedTriple::Pair {
                         start,
@@ -150,6 +167,7 @@ pub fn parse_triples<R: Read>(
                 } else {
                     let (start, end, atom_offset, tree_hash) = {
                         if b <= MAX_SINGLE_BYTE {
+                            tracker.atom(1)?;
                             (
                                 start,
                                 start + 1,
@@ -158,6 +176,7 @@ pub fn parse_triples<R: Read>(
                             )
                         } else {
                             let (atom_offset, atom_size) = decode_size_with_offset(f, b)?;
+                            tracker.atom(usize::try_from(atom_size).unwrap_or(usize::MAX))?;
                             let end = start + (atom_offset as u64) + atom_size;
                             let h = skip_or_sha_bytes(f, atom_size, calculate_tree_hashes)?;
                             (start, end, atom_offset as u32, h)
@@ -181,6 +200,7 @@ pub fn parse_triples<R: Read>(
                     end,
                     right_index,
                 } => {
+                    depth -= 1;
                     if calculate_tree_hashes {
                         let h = sha_blobs(&[
                             &[2],
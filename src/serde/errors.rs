@@ -4,6 +4,10 @@ pub fn bad_encoding() -> Error {
     Error::new(ErrorKind::InvalidInput, "bad encoding")
 }
 
+pub fn non_canonical_encoding() -> Error {
+    Error::new(ErrorKind::InvalidInput, "non-canonical encoding")
+}
+
 pub fn internal_error() -> Error {
     Error::new(ErrorKind::InvalidInput, "internal error")
 }
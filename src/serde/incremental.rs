@@ -0,0 +1,365 @@
+use std::io;
+use std::io::{Cursor, Read};
+
+use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::traverse_path::traverse_path;
+
+use super::errors::internal_error;
+use super::parse_atom::{atom_size, decode_size_with_offset, parse_atom, parse_path};
+
+const MAX_SINGLE_BYTE: u8 = 0x7f;
+const BACK_REFERENCE: u8 = 0xfe;
+const CONS_BOX_MARKER: u8 = 0xff;
+
+#[repr(u8)]
+enum ParseOp {
+    SExp,
+    Cons,
+}
+
+// the length of the atom at the start of `buf`, including its length prefix.
+// Returns None if `buf` is too short to tell
+fn atom_len(buf: &[u8]) -> io::Result<Option<usize>> {
+    let Some(&first) = buf.first() else {
+        return Ok(None);
+    };
+    if first <= MAX_SINGLE_BYTE || first == 0x80 {
+        return Ok(Some(1));
+    }
+    let prefix_len = first.leading_ones() as usize;
+    if buf.len() < prefix_len {
+        return Ok(None);
+    }
+    let (_, size) = decode_size_with_offset(&mut &buf[1..prefix_len], first)?;
+    Ok(Some(prefix_len + size as usize))
+}
+
+// the length of the token (a cons box marker, an atom or a back reference) at
+// the start of `buf`. Returns None if `buf` is too short to tell
+fn token_len(buf: &[u8], backrefs: bool) -> io::Result<Option<usize>> {
+    match buf.first() {
+        None => Ok(None),
+        Some(&CONS_BOX_MARKER) => Ok(Some(1)),
+        Some(&BACK_REFERENCE) if backrefs => Ok(atom_len(&buf[1..])?.map(|len| len + 1)),
+        Some(_) => atom_len(buf),
+    }
+}
+
+/// A push-style deserializer, for when the serialization arrives in chunks,
+/// e.g. from a socket. Each chunk is parsed as it's fed to the parser, so the
+/// whole serialization never needs to be held in memory, only the token
+/// (atom or back reference) that straddles two chunks.
+///
+/// ```
+/// use clvmr::serde::IncrementalParser;
+/// use clvmr::Allocator;
+///
+/// let mut a = Allocator::new();
+/// let mut parser = IncrementalParser::new();
+/// // (1 2 3)
+/// parser.feed(&mut a, &[0xff, 0x01, 0xff]).unwrap();
+/// assert!(!parser.is_done());
+/// parser.feed(&mut a, &[0x02, 0xff, 0x03, 0x80]).unwrap();
+/// assert!(parser.is_done());
+/// let list = parser.finish().unwrap();
+/// ```
+///
+/// Once `feed()` has failed, the parser must not be used anymore.
+pub struct IncrementalParser {
+    backrefs: bool,
+    ops: Vec<ParseOp>,
+    // the values parsed so far. This is only used without back references
+    values: Vec<NodePtr>,
+    // with back references, the values parsed so far are kept as a clvm
+    // list, for back references to point into
+    values_list: Option<NodePtr>,
+    // the start of a token that straddles chunks
+    pending: Vec<u8>,
+    result: Option<NodePtr>,
+    bytes_read: u64,
+}
+
+impl Default for IncrementalParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IncrementalParser {
+    /// a parser of the plain serialization, as `node_from_bytes()` parses
+    pub fn new() -> Self {
+        Self {
+            backrefs: false,
+            ops: vec![ParseOp::SExp],
+            values: Vec::new(),
+            values_list: None,
+            pending: Vec::new(),
+            result: None,
+            bytes_read: 0,
+        }
+    }
+
+    /// a parser of serializations with back references, as
+    /// `node_from_bytes_backrefs()` parses
+    pub fn new_backrefs() -> Self {
+        Self {
+            backrefs: true,
+            ..Self::new()
+        }
+    }
+
+    /// whether the whole serialization has been parsed
+    pub fn is_done(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// the number of bytes of the serialization consumed so far
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// return the parsed node, or an `UnexpectedEof` error if the
+    /// serialization is incomplete
+    pub fn finish(self) -> io::Result<NodePtr> {
+        self.result
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))
+    }
+
+    /// parse the next chunk of the serialization. Returns the number of bytes
+    /// of `chunk` that were consumed. That's the whole chunk, unless the
+    /// serialization ends in it. Any bytes following the serialization are
+    /// left alone.
+    pub fn feed(&mut self, a: &mut Allocator, mut chunk: &[u8]) -> io::Result<usize> {
+        let chunk_len = chunk.len();
+        while let Some(op) = self.ops.last() {
+            if let ParseOp::Cons = op {
+                self.ops.pop();
+                self.cons(a)?;
+                continue;
+            }
+
+            // fast path, the whole token is in this chunk
+            if self.pending.is_empty() {
+                if let Some(len) = token_len(chunk, self.backrefs)? {
+                    if len <= chunk.len() {
+                        let (token, rest) = chunk.split_at(len);
+                        chunk = rest;
+                        self.ops.pop();
+                        self.parse_token(a, token)?;
+                        continue;
+                    }
+                }
+            }
+
+            // the token straddles chunks. Take as many bytes as we know the
+            // token needs
+            if chunk.is_empty() {
+                break;
+            }
+            let needed = match token_len(&self.pending, self.backrefs)? {
+                Some(len) => len - self.pending.len(),
+                None => 1,
+            };
+            let (head, rest) = chunk.split_at(needed.min(chunk.len()));
+            self.pending.extend_from_slice(head);
+            chunk = rest;
+            if token_len(&self.pending, self.backrefs)? == Some(self.pending.len()) {
+                let token = std::mem::take(&mut self.pending);
+                self.ops.pop();
+                self.parse_token(a, &token)?;
+            }
+        }
+
+        if self.ops.is_empty() && self.result.is_none() {
+            self.result = if self.backrefs {
+                let values = self.values_list.ok_or_else(internal_error)?;
+                a.next(values).map(|(first, _)| first)
+            } else {
+                self.values.pop()
+            };
+            if self.result.is_none() {
+                return Err(internal_error());
+            }
+        }
+        let consumed = chunk_len - chunk.len();
+        self.bytes_read += consumed as u64;
+        Ok(consumed)
+    }
+
+    fn push_value(&mut self, a: &mut Allocator, node: NodePtr) -> io::Result<()> {
+        if self.backrefs {
+            let values = self.values_list.unwrap_or_else(|| a.nil());
+            self.values_list = Some(a.new_pair(node, values)?);
+        } else {
+            self.values.push(node);
+        }
+        Ok(())
+    }
+
+    fn cons(&mut self, a: &mut Allocator) -> io::Result<()> {
+        if self.backrefs {
+            let values = self.values_list.ok_or_else(internal_error)?;
+            let SExp::Pair(v1, v2) = a.sexp(values) else {
+                return Err(internal_error());
+            };
+            let SExp::Pair(v3, v4) = a.sexp(v2) else {
+                return Err(internal_error());
+            };
+            let new_root = a.new_pair(v3, v1)?;
+            self.values_list = Some(a.new_pair(new_root, v4)?);
+        } else {
+            let (Some(v2), Some(v1)) = (self.values.pop(), self.values.pop()) else {
+                return Err(internal_error());
+            };
+            self.values.push(a.new_pair(v1, v2)?);
+        }
+        Ok(())
+    }
+
+    // `token` is exactly one complete token
+    fn parse_token(&mut self, a: &mut Allocator, token: &[u8]) -> io::Result<()> {
+        let mut f = Cursor::new(token);
+        let mut b = [0; 1];
+        f.read_exact(&mut b)?;
+        if b[0] == CONS_BOX_MARKER {
+            self.ops.push(ParseOp::Cons);
+            self.ops.push(ParseOp::SExp);
+            self.ops.push(ParseOp::SExp);
+            Ok(())
+        } else if b[0] == BACK_REFERENCE && self.backrefs {
+            let path = parse_path(&mut f)?;
+            let values = self.values_list.unwrap_or_else(|| a.nil());
+            let back_reference = traverse_path(a, path, values)?.1;
+            self.push_value(a, back_reference)
+        } else {
+            let size = atom_size(&mut f, b[0])?;
+            let atom = parse_atom(a, b[0], size, &mut f)?;
+            self.push_value(a, atom)
+        }
+    }
+}
+
+#[cfg(test)]
+use super::errors::bad_encoding;
+
+#[cfg(test)]
+use crate::serde::{node_from_bytes, node_from_bytes_backrefs, node_to_bytes_backrefs};
+
+#[cfg(test)]
+use crate::serde::{node_to_bytes, serialized_length_from_bytes};
+
+#[cfg(test)]
+use rstest::rstest;
+
+#[cfg(test)]
+fn parse_in_chunks(
+    a: &mut Allocator,
+    mut parser: IncrementalParser,
+    buf: &[u8],
+    chunk_size: usize,
+) -> io::Result<NodePtr> {
+    for chunk in buf.chunks(chunk_size) {
+        assert!(!parser.is_done());
+        assert_eq!(parser.feed(a, chunk)?, chunk.len());
+    }
+    assert_eq!(parser.bytes_read(), buf.len() as u64);
+    parser.finish()
+}
+
+#[test]
+fn test_incremental_block() {
+    let block = include_bytes!("../../benches/block_af9c3d98.bin");
+    // the file has a trailing byte
+    let block = &block[..serialized_length_from_bytes(block).unwrap() as usize];
+    let mut a = Allocator::new();
+    let expected = node_from_bytes(&mut a, block).unwrap();
+    let compressed = node_to_bytes_backrefs(&a, expected).unwrap();
+
+    for chunk_size in [1, 2, 3, 7, 1000, 1000000] {
+        let cp = a.checkpoint();
+        let node = parse_in_chunks(&mut a, IncrementalParser::new(), block, chunk_size).unwrap();
+        assert_eq!(node_to_bytes(&a, node).unwrap(), block);
+
+        let node = parse_in_chunks(
+            &mut a,
+            IncrementalParser::new_backrefs(),
+            &compressed,
+            chunk_size,
+        )
+        .unwrap();
+        assert_eq!(node_to_bytes(&a, node).unwrap(), block);
+        a.restore_checkpoint(&cp);
+    }
+}
+
+#[cfg(test)]
+#[rstest]
+#[case("80")]
+#[case("01")]
+#[case("ff83666f6f83626172")]
+#[case("ffff0102ff0304")]
+#[case("ff83666f6ffe02")]
+#[case("ff846c6f6e67ff86737472696e67ff826f66fffe0bff8474657874fffe1780")]
+#[case("ff83666f6ffffe01fffe01fffe01fffe01fffe01fffe0180")]
+fn test_incremental_backrefs(#[case] serialized: &str) {
+    let buf = hex::decode(serialized).unwrap();
+    let mut a = Allocator::new();
+    let expected = node_from_bytes_backrefs(&mut a, &buf).unwrap();
+    for chunk_size in 1..=buf.len() {
+        let node =
+            parse_in_chunks(&mut a, IncrementalParser::new_backrefs(), &buf, chunk_size).unwrap();
+        assert_eq!(
+            node_to_bytes(&a, node).unwrap(),
+            node_to_bytes(&a, expected).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_incremental_trailing_bytes() {
+    let mut a = Allocator::new();
+    let mut parser = IncrementalParser::new();
+    // (1 . 2), followed by garbage
+    assert_eq!(parser.feed(&mut a, &[0xff, 0x01]).unwrap(), 2);
+    assert_eq!(parser.feed(&mut a, &[0x02, 0xff, 0xff]).unwrap(), 1);
+    assert!(parser.is_done());
+    assert_eq!(parser.feed(&mut a, &[0xff]).unwrap(), 0);
+    assert_eq!(parser.bytes_read(), 3);
+    let node = parser.finish().unwrap();
+    let (first, rest) = a.next(node).unwrap();
+    assert_eq!(a.small_number(first), Some(1));
+    assert_eq!(a.small_number(rest), Some(2));
+}
+
+#[test]
+fn test_incremental_errors() {
+    let mut a = Allocator::new();
+
+    // truncated
+    let mut parser = IncrementalParser::new();
+    parser.feed(&mut a, &[0xff, 0x83, 0x66]).unwrap();
+    assert!(!parser.is_done());
+    assert_eq!(
+        parser.finish().unwrap_err().kind(),
+        io::ErrorKind::UnexpectedEof
+    );
+
+    // back references aren't allowed. 0xfe is the start of an atom length
+    // prefix that's too long
+    let mut parser = IncrementalParser::new();
+    parser.feed(&mut a, &[0xff, 0x01, 0xfe, 0x02]).unwrap();
+    let e = parser.feed(&mut a, &[0, 0, 0, 0, 0]).unwrap_err();
+    assert_eq!(e.kind(), bad_encoding().kind());
+
+    // invalid back reference
+    let mut parser = IncrementalParser::new_backrefs();
+    assert!(parser.feed(&mut a, &[0xff, 0x01, 0xfe, 0x07]).is_err());
+
+    // the atom is too large
+    let mut parser = IncrementalParser::new();
+    for b in [0xff, 0xfc, 0x04, 0, 0] {
+        parser.feed(&mut a, &[b]).unwrap();
+    }
+    let e = parser.feed(&mut a, &[0, 0]).unwrap_err();
+    assert_eq!(e.kind(), bad_encoding().kind());
+}
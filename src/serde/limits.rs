@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::io::ErrorKind;
+
+use crate::allocator::{Allocator, NodePtr, SExp};
+
+/// Bounds on the tree a deserializer is allowed to produce. These are meant to
+/// be used when accepting serialized programs from untrusted sources.
+///
+/// All limits apply to the logical tree, i.e. after back references have been
+/// expanded. A small blob using back references can describe a tree with
+/// billions of nodes, and each time a sub tree is referenced it counts again.
+///
+/// The default is to not limit anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeserializeLimits {
+    /// the maximum number of pairs between the root and any node. The root
+    /// itself is at depth 0
+    pub max_depth: usize,
+    /// the maximum number of atoms in the tree
+    pub max_atoms: usize,
+    /// the maximum number of pairs in the tree
+    pub max_pairs: usize,
+    /// the maximum sum of the lengths of all atoms in the tree
+    pub max_atom_bytes: usize,
+    /// the maximum number of back references in the serialization
+    pub max_backrefs: usize,
+    /// the maximum length, in bytes, of the path of a back reference
+    pub max_path_len: usize,
+}
+
+impl Default for DeserializeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: usize::MAX,
+            max_atoms: usize::MAX,
+            max_pairs: usize::MAX,
+            max_atom_bytes: usize::MAX,
+            max_backrefs: usize::MAX,
+            max_path_len: usize::MAX,
+        }
+    }
+}
+
+/// The error a deserializer fails with when one of the `DeserializeLimits` is
+/// exceeded. It's returned wrapped in an `io::Error`, use `from_io_error()` to
+/// get it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Depth,
+    Atoms,
+    Pairs,
+    AtomBytes,
+    Backrefs,
+    PathLength,
+}
+
+impl LimitExceeded {
+    pub fn from_io_error(e: &io::Error) -> Option<Self> {
+        e.get_ref()?.downcast_ref::<Self>().copied()
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let what = match self {
+            Self::Depth => "depth",
+            Self::Atoms => "atom count",
+            Self::Pairs => "pair count",
+            Self::AtomBytes => "atom bytes",
+            Self::Backrefs => "back reference count",
+            Self::PathLength => "back reference path length",
+        };
+        write!(f, "deserialization {what} limit exceeded")
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl From<LimitExceeded> for io::Error {
+    fn from(e: LimitExceeded) -> Self {
+        io::Error::new(ErrorKind::InvalidInput, e)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Subtree {
+    atoms: usize,
+    pairs: usize,
+    atom_bytes: usize,
+    height: usize,
+}
+
+/// Keeps count of what a deserializer has produced so far, and fails once a
+/// limit is exceeded.
+pub(crate) struct LimitTracker<'a> {
+    limits: &'a DeserializeLimits,
+    atoms: usize,
+    pairs: usize,
+    atom_bytes: usize,
+    backrefs: usize,
+    // the sizes of the pairs that have been back referenced (directly or
+    // as part of a larger sub tree), so each one is only counted once
+    subtrees: HashMap<NodePtr, Subtree>,
+}
+
+impl<'a> LimitTracker<'a> {
+    pub fn new(limits: &'a DeserializeLimits) -> Self {
+        Self {
+            limits,
+            atoms: 0,
+            pairs: 0,
+            atom_bytes: 0,
+            backrefs: 0,
+            subtrees: HashMap::new(),
+        }
+    }
+
+    pub fn check_depth(&self, depth: usize) -> Result<(), LimitExceeded> {
+        if depth > self.limits.max_depth {
+            Err(LimitExceeded::Depth)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn atom(&mut self, len: usize) -> Result<(), LimitExceeded> {
+        self.add_atoms(1, len)
+    }
+
+    pub fn pair(&mut self) -> Result<(), LimitExceeded> {
+        self.add_pairs(1)
+    }
+
+    pub fn path(&self, len: usize) -> Result<(), LimitExceeded> {
+        if len > self.limits.max_path_len {
+            Err(LimitExceeded::PathLength)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// a back reference to `node`, found at `depth`
+    pub fn backref(
+        &mut self,
+        a: &Allocator,
+        node: NodePtr,
+        depth: usize,
+    ) -> Result<(), LimitExceeded> {
+        self.backrefs += 1;
+        if self.backrefs > self.limits.max_backrefs {
+            return Err(LimitExceeded::Backrefs);
+        }
+        let l = self.limits;
+        if l.max_depth == usize::MAX
+            && l.max_atoms == usize::MAX
+            && l.max_pairs == usize::MAX
+            && l.max_atom_bytes == usize::MAX
+        {
+            // nothing depends on the size of the sub tree, so don't bother
+            // computing it
+            return Ok(());
+        }
+        let subtree = self.subtree(a, node);
+        self.check_depth(depth.saturating_add(subtree.height))?;
+        self.add_atoms(subtree.atoms, subtree.atom_bytes)?;
+        self.add_pairs(subtree.pairs)
+    }
+
+    fn add_atoms(&mut self, count: usize, len: usize) -> Result<(), LimitExceeded> {
+        self.atoms = self.atoms.saturating_add(count);
+        if self.atoms > self.limits.max_atoms {
+            return Err(LimitExceeded::Atoms);
+        }
+        self.atom_bytes = self.atom_bytes.saturating_add(len);
+        if self.atom_bytes > self.limits.max_atom_bytes {
+            return Err(LimitExceeded::AtomBytes);
+        }
+        Ok(())
+    }
+
+    fn add_pairs(&mut self, count: usize) -> Result<(), LimitExceeded> {
+        self.pairs = self.pairs.saturating_add(count);
+        if self.pairs > self.limits.max_pairs {
+            Err(LimitExceeded::Pairs)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn lookup(&self, a: &Allocator, node: NodePtr) -> Option<Subtree> {
+        match a.sexp(node) {
+            SExp::Atom => Some(Subtree {
+                atoms: 1,
+                pairs: 0,
+                atom_bytes: a.atom_len(node),
+                height: 0,
+            }),
+            SExp::Pair(..) => self.subtrees.get(&node).copied(),
+        }
+    }
+
+    fn subtree(&mut self, a: &Allocator, root: NodePtr) -> Subtree {
+        let mut stack = vec![root];
+        while let Some(node) = stack.last().copied() {
+            if self.lookup(a, node).is_some() {
+                stack.pop();
+                continue;
+            }
+            let SExp::Pair(first, rest) = a.sexp(node) else {
+                unreachable!();
+            };
+            match (self.lookup(a, first), self.lookup(a, rest)) {
+                (Some(f), Some(r)) => {
+                    stack.pop();
+                    self.subtrees.insert(
+                        node,
+                        Subtree {
+                            atoms: f.atoms.saturating_add(r.atoms),
+                            pairs: f.pairs.saturating_add(r.pairs).saturating_add(1),
+                            atom_bytes: f.atom_bytes.saturating_add(r.atom_bytes),
+                            height: f.height.max(r.height) + 1,
+                        },
+                    );
+                }
+                (f, r) => {
+                    if r.is_none() {
+                        stack.push(rest);
+                    }
+                    if f.is_none() {
+                        stack.push(first);
+                    }
+                }
+            }
+        }
+        self.lookup(a, root).unwrap()
+    }
+}
+
+#[cfg(test)]
+use crate::serde::{
+    node_from_bytes_backrefs_with_limits, node_from_bytes_with_limits, node_to_bytes_backrefs,
+    parse_triples_with_limits,
+};
+
+#[cfg(test)]
+use rstest::rstest;
+
+#[cfg(test)]
+fn limit(which: LimitExceeded, value: usize) -> DeserializeLimits {
+    let mut ret = DeserializeLimits::default();
+    match which {
+        LimitExceeded::Depth => ret.max_depth = value,
+        LimitExceeded::Atoms => ret.max_atoms = value,
+        LimitExceeded::Pairs => ret.max_pairs = value,
+        LimitExceeded::AtomBytes => ret.max_atom_bytes = value,
+        LimitExceeded::Backrefs => ret.max_backrefs = value,
+        LimitExceeded::PathLength => ret.max_path_len = value,
+    }
+    ret
+}
+
+// (1 2 300)
+#[cfg(test)]
+#[rstest]
+#[case(LimitExceeded::Depth, 3)]
+#[case(LimitExceeded::Atoms, 4)]
+#[case(LimitExceeded::Pairs, 3)]
+#[case(LimitExceeded::AtomBytes, 4)]
+fn test_limits(#[case] which: LimitExceeded, #[case] value: usize) {
+    let buf = hex::decode("ff01ff02ff82012c80").unwrap();
+    let mut a = Allocator::new();
+
+    let ok = limit(which, value);
+    assert!(node_from_bytes_with_limits(&mut a, &buf, &ok).is_ok());
+    assert!(node_from_bytes_backrefs_with_limits(&mut a, &buf, &ok).is_ok());
+    assert!(parse_triples_with_limits(&mut &buf[..], false, &ok).is_ok());
+
+    let exceeded = limit(which, value - 1);
+    let e = node_from_bytes_with_limits(&mut a, &buf, &exceeded).unwrap_err();
+    assert_eq!(LimitExceeded::from_io_error(&e), Some(which));
+    let e = node_from_bytes_backrefs_with_limits(&mut a, &buf, &exceeded).unwrap_err();
+    assert_eq!(LimitExceeded::from_io_error(&e), Some(which));
+    let e = parse_triples_with_limits(&mut &buf[..], false, &exceeded).unwrap_err();
+    assert_eq!(LimitExceeded::from_io_error(&e), Some(which));
+}
+
+// ((1 2 3 4) 1 2 3 4), where the second list is a back reference to the first
+#[cfg(test)]
+#[rstest]
+#[case(LimitExceeded::Depth, 5)]
+#[case(LimitExceeded::Atoms, 10)]
+#[case(LimitExceeded::Pairs, 9)]
+#[case(LimitExceeded::AtomBytes, 8)]
+#[case(LimitExceeded::Backrefs, 1)]
+#[case(LimitExceeded::PathLength, 1)]
+fn test_limits_backrefs(#[case] which: LimitExceeded, #[case] value: usize) {
+    let buf = hex::decode("ffff01ff02ff03ff0480fe02").unwrap();
+    let mut a = Allocator::new();
+
+    let ok = limit(which, value);
+    assert!(node_from_bytes_backrefs_with_limits(&mut a, &buf, &ok).is_ok());
+
+    let exceeded = limit(which, value - 1);
+    let e = node_from_bytes_backrefs_with_limits(&mut a, &buf, &exceeded).unwrap_err();
+    assert_eq!(LimitExceeded::from_io_error(&e), Some(which));
+}
+
+#[test]
+fn test_backref_expansion() {
+    // a tree where every pair refers to the same sub tree twice. Expanded, it
+    // has 2^64 atoms, but it takes very little space to serialize
+    let mut a = Allocator::new();
+    let mut node = a.one();
+    for _ in 0..64 {
+        node = a.new_pair(node, node).unwrap();
+    }
+    let buf = node_to_bytes_backrefs(&a, node).unwrap();
+    assert!(buf.len() < 200);
+
+    let mut a = Allocator::new();
+    assert!(
+        node_from_bytes_backrefs_with_limits(&mut a, &buf, &DeserializeLimits::default()).is_ok()
+    );
+
+    let limits = DeserializeLimits {
+        max_atoms: 1_000_000,
+        ..Default::default()
+    };
+    let e = node_from_bytes_backrefs_with_limits(&mut a, &buf, &limits).unwrap_err();
+    assert_eq!(LimitExceeded::from_io_error(&e), Some(LimitExceeded::Atoms));
+}
+
+#[test]
+fn test_large_atom() {
+    // the atom claims to be 0x3ffffffff bytes, but the buffer ends. The limit is
+    // hit before trying to read it
+    let buf = hex::decode("fbffffffff00").unwrap();
+    let limits = limit(LimitExceeded::AtomBytes, 1000);
+    let e = parse_triples_with_limits(&mut &buf[..], false, &limits).unwrap_err();
+    assert_eq!(
+        LimitExceeded::from_io_error(&e),
+        Some(LimitExceeded::AtomBytes)
+    );
+    let mut a = Allocator::new();
+    let e = node_from_bytes_with_limits(&mut a, &buf, &limits).unwrap_err();
+    assert_eq!(
+        LimitExceeded::from_io_error(&e),
+        Some(LimitExceeded::AtomBytes)
+    );
+    let e = node_from_bytes_backrefs_with_limits(&mut a, &buf, &limits).unwrap_err();
+    assert_eq!(
+        LimitExceeded::from_io_error(&e),
+        Some(LimitExceeded::AtomBytes)
+    );
+
+    // errors that aren't about limits
+    let e =
+        parse_triples_with_limits(&mut &buf[..], false, &DeserializeLimits::default()).unwrap_err();
+    assert_eq!(LimitExceeded::from_io_error(&e), None);
+    let e = node_from_bytes_with_limits(&mut a, &buf, &DeserializeLimits::default()).unwrap_err();
+    assert_eq!(LimitExceeded::from_io_error(&e), None);
+}
@@ -1,25 +1,38 @@
-mod bytes32;
 mod de;
 mod de_br;
 mod de_tree;
 mod errors;
-mod object_cache;
+mod incremental;
+mod limits;
 mod parse_atom;
-mod read_cache_lookup;
 mod ser;
 mod ser_br;
 mod tools;
+mod tree_cache;
 mod utils;
 pub mod write_atom;
 
 #[cfg(test)]
 mod test;
 
-pub use de::node_from_bytes;
-pub use de_br::{node_from_bytes_backrefs, node_from_bytes_backrefs_record};
-pub use de_tree::{parse_triples, ParsedTriple};
+pub use de::{
+    node_from_bytes, node_from_bytes_strict, node_from_bytes_with_limits, node_from_stream,
+};
+pub use de_br::{
+    node_from_bytes_backrefs, node_from_bytes_backrefs_record,
+    node_from_bytes_backrefs_with_limits, node_from_stream_backrefs,
+};
+pub use de_tree::{parse_triples, parse_triples_with_limits, ParsedTriple};
+pub use incremental::IncrementalParser;
+pub use limits::{DeserializeLimits, LimitExceeded};
+pub use parse_atom::{AtomSource, ReadSource};
 pub use ser::node_to_bytes;
-pub use ser_br::node_to_bytes_backrefs;
+pub use ser_br::{
+    compressed_serialized_length, node_to_bytes_backrefs, node_to_bytes_backrefs_limit,
+    node_to_bytes_backrefs_max, node_to_stream_backrefs, node_to_stream_backrefs_max,
+};
 pub use tools::{
-    serialized_length_from_bytes, serialized_length_from_bytes_trusted, tree_hash_from_stream,
+    is_canonical, serialized_length_from_bytes, serialized_length_from_bytes_strict,
+    serialized_length_from_bytes_trusted, serialized_length_from_stream,
+    serialized_length_from_stream_trusted, tree_hash_from_stream,
 };
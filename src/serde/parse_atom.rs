@@ -1,16 +1,30 @@
-use std::io::{Cursor, Read, Result, Seek, SeekFrom};
+use std::cmp::min;
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom};
 
 use crate::allocator::{Allocator, NodePtr};
 
-use super::errors::{bad_encoding, internal_error};
+use super::errors::{bad_encoding, internal_error, non_canonical_encoding};
 
 const MAX_SINGLE_BYTE: u8 = 0x7f;
 
+// atoms up to this size are read straight into a buffer of the size their
+// length prefix specifies
+const MAX_PREALLOCATED_ATOM: u64 = 0x100000;
+
+// a stream that ends in the middle of an atom is a bad encoding
+fn truncated(e: Error) -> Error {
+    if e.kind() == ErrorKind::UnexpectedEof {
+        bad_encoding()
+    } else {
+        e
+    }
+}
+
 /// decode the length prefix for an atom, returning both the offset to the start
 /// of the atom and the full length of the atom.
 /// Atoms whose value fit in 7 bits don't have a length prefix, so those should
 /// be handled specially and never passed to this function.
-pub fn decode_size_with_offset<R: Read>(f: &mut R, initial_b: u8) -> Result<(u8, u64)> {
+pub fn decode_size_with_offset<R: Read + ?Sized>(f: &mut R, initial_b: u8) -> Result<(u8, u64)> {
     debug_assert!((initial_b & 0x80) != 0);
     if (initial_b & 0x80) == 0 {
         return Err(internal_error());
@@ -46,60 +60,210 @@ pub fn decode_size_with_offset<R: Read>(f: &mut R, initial_b: u8) -> Result<(u8,
     Ok((atom_start_offset as u8, atom_size))
 }
 
-pub fn decode_size<R: Read>(f: &mut R, initial_b: u8) -> Result<u64> {
+pub fn decode_size<R: Read + ?Sized>(f: &mut R, initial_b: u8) -> Result<u64> {
     decode_size_with_offset(f, initial_b).map(|v| v.1)
 }
 
-/// parse an atom from the stream and return a pointer to it
-/// the first byte has already been read
+// the number of bytes in the shortest length prefix for an atom of `size`
+// bytes
+fn canonical_prefix_len(size: usize) -> u32 {
+    if size < 0x40 {
+        1
+    } else if size < 0x2000 {
+        2
+    } else if size < 0x10_0000 {
+        3
+    } else if size < 0x800_0000 {
+        4
+    } else {
+        5
+    }
+}
+
+/// check that `atom`, whose serialization starts with `first_byte`, was
+/// serialized in the shortest possible form. That is, with the shortest
+/// length prefix, and without any prefix if the atom is a single byte in the
+/// range 0x00-0x7f
+pub fn check_canonical_atom(first_byte: u8, atom: &[u8]) -> Result<()> {
+    if first_byte <= MAX_SINGLE_BYTE {
+        return Ok(());
+    }
+    if (atom.len() == 1 && atom[0] <= MAX_SINGLE_BYTE)
+        || first_byte.leading_ones() != canonical_prefix_len(atom.len())
+    {
+        return Err(non_canonical_encoding());
+    }
+    Ok(())
+}
 
-fn parse_atom_ptr<'a>(f: &'a mut Cursor<&[u8]>, first_byte: u8) -> Result<&'a [u8]> {
-    let blob = if first_byte <= MAX_SINGLE_BYTE {
-        let pos = f.position() as usize;
-        &f.get_ref()[pos - 1..pos]
+/// The input to the deserializers. Byte slices (as `Cursor<&[u8]>`) are
+/// parsed without copying atoms, while any other `Read` is wrapped in a
+/// `ReadSource`, which reads atoms into a buffer
+pub trait AtomSource: Read {
+    /// read the body of an atom of `size` bytes, as returned by `atom_size()`,
+    /// and return a reference to it. The first byte and the length prefix have
+    /// already been read
+    fn read_atom_body(&mut self, first_byte: u8, size: u64) -> Result<&[u8]>;
+
+    /// skip over the body of an atom, whose length prefix has already been
+    /// read
+    fn skip_atom(&mut self, size: u64) -> Result<()>;
+
+    /// the number of bytes read from the stream so far
+    fn bytes_read(&self) -> u64;
+
+    /// read an atom from the stream and return a reference to it. The first
+    /// byte has already been read
+    fn read_atom(&mut self, first_byte: u8) -> Result<&[u8]> {
+        let size = atom_size(self, first_byte)?;
+        self.read_atom_body(first_byte, size)
+    }
+}
+
+/// the size of the atom whose serialization starts with `first_byte`. Any
+/// length prefix is read from `f`, leaving it at the start of the atom's body
+pub fn atom_size<R: Read + ?Sized>(f: &mut R, first_byte: u8) -> Result<u64> {
+    if first_byte <= MAX_SINGLE_BYTE {
+        Ok(1)
     } else {
-        let blob_size = decode_size(f, first_byte)?;
-        let pos = f.position() as usize;
-        if f.get_ref().len() < pos + blob_size as usize {
+        decode_size(f, first_byte)
+    }
+}
+
+impl AtomSource for Cursor<&[u8]> {
+    fn read_atom_body(&mut self, first_byte: u8, size: u64) -> Result<&[u8]> {
+        let blob = if first_byte <= MAX_SINGLE_BYTE {
+            let pos = self.position() as usize;
+            &self.get_ref()[pos - 1..pos]
+        } else {
+            let pos = self.position() as usize;
+            if self.get_ref().len() < pos + size as usize {
+                return Err(bad_encoding());
+            }
+            self.seek(SeekFrom::Current(size as i64))?;
+            &self.get_ref()[pos..(pos + size as usize)]
+        };
+        Ok(blob)
+    }
+
+    fn skip_atom(&mut self, size: u64) -> Result<()> {
+        self.seek(SeekFrom::Current(size as i64))?;
+        if (self.get_ref().len() as u64) < self.position() {
             return Err(bad_encoding());
         }
-        f.seek(SeekFrom::Current(blob_size as i64))?;
-        &f.get_ref()[pos..(pos + blob_size as usize)]
-    };
-    Ok(blob)
+        Ok(())
+    }
+
+    fn bytes_read(&self) -> u64 {
+        self.position()
+    }
+}
+
+/// Adapts any `Read`, e.g. a file or a socket, to be used as an `AtomSource`
+pub struct ReadSource<'a, R> {
+    inner: &'a mut R,
+    // the most recently read atom
+    buf: Vec<u8>,
+    bytes_read: u64,
+}
+
+impl<'a, R: Read> ReadSource<'a, R> {
+    pub fn new(inner: &'a mut R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            bytes_read: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for ReadSource<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes_read += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> AtomSource for ReadSource<'_, R> {
+    fn read_atom_body(&mut self, first_byte: u8, blob_size: u64) -> Result<&[u8]> {
+        let mut buf = std::mem::take(&mut self.buf);
+        buf.clear();
+        if first_byte <= MAX_SINGLE_BYTE {
+            buf.push(first_byte);
+        } else if blob_size <= MAX_PREALLOCATED_ATOM {
+            buf.resize(blob_size as usize, 0);
+            self.read_exact(&mut buf).map_err(truncated)?;
+        } else {
+            // we don't trust the size prefix enough to allocate a large
+            // buffer up-front, the stream may be truncated. read_to_end()
+            // grows the buffer as it goes
+            self.take(blob_size).read_to_end(&mut buf)?;
+            if (buf.len() as u64) < blob_size {
+                return Err(bad_encoding());
+            }
+        }
+        self.buf = buf;
+        Ok(&self.buf)
+    }
+
+    fn skip_atom(&mut self, mut size: u64) -> Result<()> {
+        let mut buf = [0_u8; 1024];
+        while size > 0 {
+            let len = min(size, buf.len() as u64) as usize;
+            self.read_exact(&mut buf[..len]).map_err(truncated)?;
+            size -= len as u64;
+        }
+        Ok(())
+    }
+
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
 }
 
 /// parse an atom from the stream into the allocator
 /// At this point, the first byte has already been read to ensure it's
 /// not a special code like `CONS_BOX_MARKER` = 0xff, so it must be
-/// passed in too
+/// passed in too, along with the atom's `size`, as returned by `atom_size()`
 
-pub fn parse_atom(
+pub fn parse_atom<S: AtomSource>(
     allocator: &mut Allocator,
     first_byte: u8,
-    f: &mut Cursor<&[u8]>,
+    size: u64,
+    f: &mut S,
 ) -> Result<NodePtr> {
     if first_byte == 0x01 {
         Ok(allocator.one())
     } else if first_byte == 0x80 {
         Ok(allocator.nil())
     } else {
-        let blob = parse_atom_ptr(f, first_byte)?;
+        let blob = f.read_atom_body(first_byte, size)?;
         Ok(allocator.new_atom(blob)?)
     }
 }
 
+/// like `parse_atom()`, but fails unless the atom is serialized in its
+/// canonical form
+pub fn parse_canonical_atom<S: AtomSource>(
+    allocator: &mut Allocator,
+    first_byte: u8,
+    size: u64,
+    f: &mut S,
+) -> Result<NodePtr> {
+    let blob = f.read_atom_body(first_byte, size)?;
+    check_canonical_atom(first_byte, blob)?;
+    Ok(allocator.new_atom(blob)?)
+}
+
 /// parse an atom from the stream and return a pointer to it
 
-pub fn parse_path<'a>(f: &'a mut Cursor<&[u8]>) -> Result<&'a [u8]> {
+pub fn parse_path<S: AtomSource>(f: &mut S) -> Result<&[u8]> {
     let mut buf1: [u8; 1] = [0];
     f.read_exact(&mut buf1)?;
-    parse_atom_ptr(f, buf1[0])
+    f.read_atom(buf1[0])
 }
 
-#[cfg(test)]
-use std::io::ErrorKind;
-
 #[cfg(test)]
 use hex;
 
@@ -179,7 +343,8 @@ fn check_parse_atom(blob: &[u8], expected_atom: &[u8]) {
     let first = first[0];
 
     let mut allocator = Allocator::new();
-    let atom_node = parse_atom(&mut allocator, first, &mut cursor).unwrap();
+    let size = atom_size(&mut cursor, first).unwrap();
+    let atom_node = parse_atom(&mut allocator, first, size, &mut cursor).unwrap();
 
     let atom = allocator.atom(atom_node);
 
@@ -225,7 +390,8 @@ fn test_truncated_parse_atom() {
     let first = 0b11111100;
     let mut cursor = Cursor::<&[u8]>::new(&[0x4, 0, 0, 0]);
     let mut allocator = Allocator::new();
-    let ret = parse_atom(&mut allocator, first, &mut cursor);
+    let ret = atom_size(&mut cursor, first)
+        .and_then(|size| parse_atom(&mut allocator, first, size, &mut cursor));
     let err = ret.unwrap_err();
     assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
 }
@@ -1,82 +1,372 @@
 // Serialization with "back-references"
 
+use std::collections::HashSet;
 use std::io;
 use std::io::Cursor;
 
-use super::object_cache::{serialized_length, treehash, ObjectCache};
-use super::read_cache_lookup::ReadCacheLookup;
+use super::ser::LimitedWriter;
+use super::tree_cache::{Entry, TreeCache};
 use super::write_atom::write_atom;
-use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::allocator::{Allocator, NodePtr};
 
 const BACK_REFERENCE: u8 = 0xfe;
 const CONS_BOX_MARKER: u8 = 0xff;
 
-#[derive(PartialEq, Eq)]
-enum ReadOp {
-    Parse,
-    Cons,
+// in the fast mode, this is the number of previous occurrences of a sub tree
+// we consider back-referencing, starting with the most recent one
+const FAST_CANDIDATES: usize = 4;
+
+// the same, for the max mode. This is only reached by degenerate trees, e.g.
+// deeply nested ones where the same atom appears thousands of times
+const MAX_CANDIDATES: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    // only consider the most recent occurrences of a sub tree
+    Fast,
+    // also consider ways of reaching a sub tree through larger sub trees that
+    // contain it, e.g. ones that were written as back-references
+    Max,
+}
+
+// a node in the tree, as it's written. Nodes written as back-references are
+// leaves, just like atoms.
+struct Written {
+    id: u32,
+    parent: u32,
+    depth: u32,
+    is_rest: bool,
 }
 
-// these test cases were produced by:
+// an item on the stack the deserializer will build
+struct StackItem {
+    // the id of the list from this item to the bottom of the stack, which is
+    // what a path consisting of only rests refers to. It's `None` if that
+    // list isn't part of the tree
+    list: Option<u32>,
+    // the first written node that's part of this item
+    start: u32,
+}
+
+enum Op {
+    Write { id: u32, parent: u32, is_rest: bool },
+    Cons(u32),
+}
 
-// from chia.types.blockchain_format.program import Program
-// a = Program.to(...)
-// print(bytes(a).hex())
-// print(a.get_tree_hash().hex())
+const NO_PARENT: u32 = u32::MAX;
 
-pub fn node_to_stream_backrefs<W: io::Write>(
+// the largest number of steps a back-reference path can have and still be
+// shorter than a sub tree serializing to `serialized_length` bytes
+fn max_path_steps(serialized_length: u64) -> Option<u32> {
+    match serialized_length {
+        0..=2 => None,
+        // 0xfe followed by a single byte atom, with up to 6 steps and the
+        // terminating bit
+        3 => Some(6),
+        _ => Some(
+            serialized_length
+                .saturating_sub(3)
+                .saturating_mul(8)
+                .saturating_sub(1)
+                .clamp(6, u32::MAX as u64) as u32,
+        ),
+    }
+}
+
+// turns a path, where `steps[i]` is true for going to the rest of a pair, into
+// the atom understood by `traverse_path()`
+fn path_atom(steps: &[bool]) -> Vec<u8> {
+    let len = steps.len() / 8 + 1;
+    let mut ret = vec![0; len];
+    for (bit, rest) in steps.iter().chain(std::iter::once(&true)).enumerate() {
+        if *rest {
+            ret[len - 1 - bit / 8] |= 1 << (bit % 8);
+        }
+    }
+    ret
+}
+
+struct Compressor<'a, 'b> {
+    tc: &'b TreeCache<'a>,
+    written: Vec<Written>,
+    // for every (repeated) sub tree, the written nodes it's been written as
+    occurrences: Vec<Vec<u32>>,
+    stack: Vec<StackItem>,
+    // for every sub tree, the positions in the stack where it's one of the
+    // lists from the stack item to the bottom
+    stack_lists: Vec<Vec<u32>>,
+    // for every sub tree, the pairs it's a direct child of that have been
+    // written, and whether it's the rest (true) or first (false) of that
+    // pair. Only used in `Compression::Max`
+    parents: Vec<Vec<(u32, bool)>>,
+    mode: Compression,
+}
+
+impl<'a, 'b> Compressor<'a, 'b> {
+    fn new(tc: &'b TreeCache<'a>, mode: Compression) -> Self {
+        Self {
+            tc,
+            written: Vec::new(),
+            occurrences: vec![Vec::new(); tc.len()],
+            stack: Vec::new(),
+            stack_lists: vec![Vec::new(); tc.len()],
+            parents: vec![Vec::new(); tc.len()],
+            mode,
+        }
+    }
+
+    // pushes the sub tree `id`, which starts at the written node `start`
+    fn push_stack(&mut self, id: u32, start: u32) {
+        let below = match self.stack.last() {
+            Some(item) => item.list,
+            None => self.tc.find_nil(),
+        };
+        let list = below.and_then(|below| self.tc.find_pair(id, below));
+        if let Some(list) = list {
+            self.stack_lists[list as usize].push(self.stack.len() as u32);
+        }
+        self.stack.push(StackItem { list, start });
+    }
+
+    fn pop_stack(&mut self) {
+        if let Some(StackItem {
+            list: Some(list), ..
+        }) = self.stack.pop()
+        {
+            self.stack_lists[list as usize].pop();
+        }
+    }
+
+    fn add_written(&mut self, id: u32, parent: u32, is_rest: bool, open: bool) -> u32 {
+        let depth = if parent == NO_PARENT {
+            0
+        } else {
+            self.written[parent as usize].depth + 1
+        };
+        let idx = self.written.len() as u32;
+        self.written.push(Written {
+            id,
+            parent,
+            depth,
+            is_rest,
+        });
+        if !open {
+            self.close(idx);
+        }
+        idx
+    }
+
+    // called once the node, and all of its children, have been written
+    fn close(&mut self, idx: u32) {
+        let id = self.written[idx as usize].id;
+        if !self.tc.is_repeated(id) || self.tc.serialized_length(id) <= 2 {
+            return;
+        }
+        if self.occurrences[id as usize].is_empty() && self.mode == Compression::Max {
+            if let Entry::Pair(first, rest) = self.tc.entry(id) {
+                self.parents[first as usize].push((id, false));
+                self.parents[rest as usize].push((id, true));
+            }
+        }
+        self.occurrences[id as usize].push(idx);
+    }
+
+    // the path to the written node `idx`, which is part of the stack item at
+    // position `pos`, with `down` appended
+    fn path_to_written(&self, idx: u32, pos: u32, down: &[bool]) -> Vec<bool> {
+        let stack_items = self.stack.len() as u32 - 1 - pos;
+        let mut ret = vec![true; stack_items as usize];
+        ret.push(false);
+        let mut below = Vec::new();
+        let mut node = idx;
+        while node != self.stack[pos as usize].start {
+            below.push(self.written[node as usize].is_rest);
+            node = self.written[node as usize].parent;
+        }
+        ret.extend(below.iter().rev());
+        ret.extend_from_slice(down);
+        ret
+    }
+
+    // the path to the stack item, or list of stack items, at stack position
+    // `pos`
+    fn path_to_stack(&self, pos: u32, down: &[bool], max_steps: u32) -> Option<Vec<bool>> {
+        let stack_items = self.stack.len() as u32 - 1 - pos;
+        if stack_items + down.len() as u32 > max_steps {
+            return None;
+        }
+        let mut ret = vec![true; stack_items as usize];
+        ret.extend_from_slice(down);
+        Some(ret)
+    }
+
+    // collects the paths to `id`, with `down` appended, that are no longer
+    // than `max_steps`. Once a path is found, `max_steps` is lowered to its
+    // length, as there's no point in looking for longer ones
+    fn candidates(&self, id: u32, down: &[bool], max_steps: &mut u32, found: &mut Vec<Vec<bool>>) {
+        let limit = match self.mode {
+            Compression::Fast => FAST_CANDIDATES,
+            Compression::Max => MAX_CANDIDATES,
+        };
+        for idx in self.occurrences[id as usize].iter().rev().take(limit) {
+            // the stack item the written node is part of. The path goes to
+            // it, and then down. Older nodes are never in a higher stack item,
+            // so once it's too far away, so are all the remaining ones
+            let pos = self.stack.partition_point(|item| item.start <= *idx) as u32 - 1;
+            let stack_items = self.stack.len() as u32 - 1 - pos;
+            if stack_items + 1 + down.len() as u32 > *max_steps {
+                break;
+            }
+            let start = &self.written[self.stack[pos as usize].start as usize];
+            let steps = stack_items
+                + 1
+                + (self.written[*idx as usize].depth - start.depth)
+                + down.len() as u32;
+            if steps <= *max_steps {
+                *max_steps = steps;
+                found.push(self.path_to_written(*idx, pos, down));
+            }
+        }
+        if let Some(pos) = self.stack_lists[id as usize].last() {
+            if let Some(path) = self.path_to_stack(*pos, down, *max_steps) {
+                *max_steps = path.len() as u32;
+                found.push(path);
+            }
+        }
+    }
+
+    // the shortest back-reference to `id`, if it's shorter than serializing
+    // it
+    fn find_path(&self, id: u32) -> Option<Vec<u8>> {
+        // a sub tree that only appears once may still be the same as one of
+        // the lists on the stack
+        let repeated = self.tc.is_repeated(id);
+        if !repeated && self.stack_lists[id as usize].is_empty() {
+            return None;
+        }
+        let mut max_steps = max_path_steps(self.tc.serialized_length(id))?;
+        let mut found = Vec::new();
+        self.candidates(id, &[], &mut max_steps, &mut found);
+
+        if repeated && self.mode == Compression::Max {
+            // the sub tree may also be part of a larger sub tree that's been
+            // back-referenced (in which case its own occurrence was never
+            // written). Look for occurrences of the pairs it's part of, one
+            // level at a time
+            let mut seen = HashSet::from([id]);
+            let mut level = vec![(id, Vec::<bool>::new())];
+            while !level.is_empty() && (level[0].1.len() as u32) < max_steps {
+                let mut next = Vec::new();
+                for (child, down) in &level {
+                    for (parent, is_rest) in &self.parents[*child as usize] {
+                        if !seen.insert(*parent) {
+                            continue;
+                        }
+                        let mut down = down.clone();
+                        down.insert(0, *is_rest);
+                        self.candidates(*parent, &down, &mut max_steps, &mut found);
+                        next.push((*parent, down));
+                    }
+                }
+                level = next;
+            }
+        }
+
+        // prefer the shortest path, and the lowest one among paths of the
+        // same length
+        found
+            .into_iter()
+            .map(|steps| (steps.len(), path_atom(&steps)))
+            .min()
+            .map(|(_, path)| path)
+    }
+}
+
+fn write_backrefs<W: io::Write>(
     allocator: &Allocator,
     node: NodePtr,
     f: &mut W,
+    mode: Compression,
 ) -> io::Result<()> {
-    let mut read_op_stack: Vec<ReadOp> = vec![ReadOp::Parse];
-    let mut write_stack: Vec<NodePtr> = vec![node];
-
-    let mut read_cache_lookup = ReadCacheLookup::new();
-
-    let mut thc = ObjectCache::new(allocator, treehash);
-    let mut slc = ObjectCache::new(allocator, serialized_length);
-
-    while let Some(node_to_write) = write_stack.pop() {
-        let op = read_op_stack.pop();
-        assert!(op == Some(ReadOp::Parse));
-
-        let node_serialized_length = *slc
-            .get_or_calculate(&node_to_write)
-            .expect("couldn't calculate serialized length");
-        let node_tree_hash = thc
-            .get_or_calculate(&node_to_write)
-            .expect("can't get treehash");
-        match read_cache_lookup.find_path(node_tree_hash, node_serialized_length) {
-            Some(path) => {
-                f.write_all(&[BACK_REFERENCE])?;
-                write_atom(f, &path)?;
-                read_cache_lookup.push(*node_tree_hash);
-            }
-            None => match allocator.sexp(node_to_write) {
-                SExp::Pair(left, right) => {
-                    f.write_all(&[CONS_BOX_MARKER])?;
-                    write_stack.push(right);
-                    write_stack.push(left);
-                    read_op_stack.push(ReadOp::Cons);
-                    read_op_stack.push(ReadOp::Parse);
-                    read_op_stack.push(ReadOp::Parse);
+    let tc = TreeCache::new(allocator, node);
+    let mut c = Compressor::new(&tc, mode);
+
+    let mut ops = vec![Op::Write {
+        id: tc.root(),
+        parent: NO_PARENT,
+        is_rest: false,
+    }];
+    while let Some(op) = ops.pop() {
+        match op {
+            Op::Write {
+                id,
+                parent,
+                is_rest,
+            } => {
+                if let Some(path) = c.find_path(id) {
+                    f.write_all(&[BACK_REFERENCE])?;
+                    write_atom(f, &path)?;
+                    let idx = c.add_written(id, parent, is_rest, false);
+                    c.push_stack(id, idx);
+                    continue;
                 }
-                SExp::Atom => {
-                    let atom = allocator.atom(node_to_write);
-                    write_atom(f, atom.as_ref())?;
-                    read_cache_lookup.push(*node_tree_hash);
+                match tc.entry(id) {
+                    Entry::Pair(first, rest) => {
+                        f.write_all(&[CONS_BOX_MARKER])?;
+                        let idx = c.add_written(id, parent, is_rest, true);
+                        ops.push(Op::Cons(idx));
+                        ops.push(Op::Write {
+                            id: rest,
+                            parent: idx,
+                            is_rest: true,
+                        });
+                        ops.push(Op::Write {
+                            id: first,
+                            parent: idx,
+                            is_rest: false,
+                        });
+                    }
+                    Entry::Atom(_) => {
+                        write_atom(f, tc.atom(id).as_ref())?;
+                        let idx = c.add_written(id, parent, is_rest, false);
+                        c.push_stack(id, idx);
+                    }
                 }
-            },
-        }
-        while !read_op_stack.is_empty() && read_op_stack[read_op_stack.len() - 1] == ReadOp::Cons {
-            read_op_stack.pop();
-            read_cache_lookup.pop2_and_cons();
+            }
+            Op::Cons(idx) => {
+                c.close(idx);
+                c.pop_stack();
+                c.pop_stack();
+                c.push_stack(c.written[idx as usize].id, idx);
+            }
         }
     }
     Ok(())
 }
 
+/// serialize a node, using back-references to sub trees that have already
+/// been serialized wherever that's shorter than serializing them again. Only
+/// the most recent occurrences of each sub tree are considered, which makes
+/// this fast, even on large trees with lots of repetition
+pub fn node_to_stream_backrefs<W: io::Write>(
+    allocator: &Allocator,
+    node: NodePtr,
+    f: &mut W,
+) -> io::Result<()> {
+    write_backrefs(allocator, node, f, Compression::Fast)
+}
+
+/// like `node_to_stream_backrefs()`, but considers more ways of referring to
+/// a sub tree that's already been serialized, including through sub trees of
+/// earlier back-references. This produces smaller output, but is slower
+pub fn node_to_stream_backrefs_max<W: io::Write>(
+    allocator: &Allocator,
+    node: NodePtr,
+    f: &mut W,
+) -> io::Result<()> {
+    write_backrefs(allocator, node, f, Compression::Max)
+}
+
 pub fn node_to_bytes_backrefs(a: &Allocator, node: NodePtr) -> io::Result<Vec<u8>> {
     let mut buffer = Cursor::new(Vec::new());
 
@@ -84,3 +374,182 @@ pub fn node_to_bytes_backrefs(a: &Allocator, node: NodePtr) -> io::Result<Vec<u8
     let vec = buffer.into_inner();
     Ok(vec)
 }
+
+pub fn node_to_bytes_backrefs_max(a: &Allocator, node: NodePtr) -> io::Result<Vec<u8>> {
+    let mut buffer = Cursor::new(Vec::new());
+
+    node_to_stream_backrefs_max(a, node, &mut buffer)?;
+    let vec = buffer.into_inner();
+    Ok(vec)
+}
+
+/// like `node_to_bytes_backrefs()`, but fails with `ErrorKind::OutOfMemory`
+/// as soon as the output would exceed `limit` bytes
+pub fn node_to_bytes_backrefs_limit(
+    a: &Allocator,
+    node: NodePtr,
+    limit: usize,
+) -> io::Result<Vec<u8>> {
+    let buffer = Cursor::new(Vec::new());
+    let mut writer = LimitedWriter::new(buffer, limit);
+    node_to_stream_backrefs(a, node, &mut writer)?;
+    let vec = writer.into_inner().into_inner();
+    Ok(vec)
+}
+
+// counts the bytes written to it, and discards them
+struct CountingWriter {
+    count: u64,
+}
+
+impl io::Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.count += buf.len() as u64;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// the length of the output of `node_to_bytes_backrefs()`, without
+/// allocating a buffer for it
+pub fn compressed_serialized_length(a: &Allocator, node: NodePtr) -> io::Result<u64> {
+    let mut counter = CountingWriter { count: 0 };
+    node_to_stream_backrefs(a, node, &mut counter)?;
+    Ok(counter.count)
+}
+
+#[cfg(test)]
+use crate::serde::{node_from_bytes, node_from_bytes_backrefs};
+
+#[cfg(test)]
+use rstest::rstest;
+
+#[cfg(test)]
+#[rstest]
+#[case(0, None)]
+#[case(2, None)]
+#[case(3, Some(6))]
+#[case(4, Some(7))]
+#[case(5, Some(15))]
+#[case(u64::MAX, Some(u32::MAX))]
+fn test_max_path_steps(#[case] len: u64, #[case] expected: Option<u32>) {
+    assert_eq!(max_path_steps(len), expected);
+}
+
+#[cfg(test)]
+#[rstest]
+#[case(&[], "01")]
+#[case(&[false], "02")]
+#[case(&[true], "03")]
+#[case(&[true, false, true], "0d")]
+#[case(&[false; 7], "80")]
+#[case(&[true; 8], "01ff")]
+fn test_path_atom(#[case] steps: &[bool], #[case] expected: &str) {
+    assert_eq!(hex::encode(path_atom(steps)), expected);
+}
+
+// compresses the tree in both modes and checks that the results decompress to
+// the same tree. The tree may be too large to serialize without
+// back-references, so it's compared by tree hash
+#[cfg(test)]
+fn check_compress(a: &Allocator, node: NodePtr) -> (Vec<u8>, Vec<u8>) {
+    let tc = TreeCache::new(a, node);
+    let plain_len = tc.serialized_length(tc.root());
+    let hash = a.tree_hash(node);
+    let fast = node_to_bytes_backrefs(a, node).unwrap();
+    let max = node_to_bytes_backrefs_max(a, node).unwrap();
+    assert_eq!(
+        compressed_serialized_length(a, node).unwrap(),
+        fast.len() as u64
+    );
+    assert!(fast.len() as u64 <= plain_len);
+    assert!(max.len() as u64 <= plain_len);
+
+    for buf in [&fast, &max] {
+        let mut a2 = Allocator::new();
+        let node2 = node_from_bytes_backrefs(&mut a2, buf).unwrap();
+        assert_eq!(a2.tree_hash(node2), hash);
+    }
+
+    // compressing the decompressed tree again gives the same result
+    let mut a2 = Allocator::new();
+    let node2 = node_from_bytes_backrefs(&mut a2, &fast).unwrap();
+    assert_eq!(node_to_bytes_backrefs(&a2, node2).unwrap(), fast);
+    let mut a2 = Allocator::new();
+    let node2 = node_from_bytes_backrefs(&mut a2, &max).unwrap();
+    assert_eq!(node_to_bytes_backrefs_max(&a2, node2).unwrap(), max);
+
+    (fast, max)
+}
+
+#[cfg(test)]
+#[rstest]
+#[case("80")]
+#[case("01")]
+#[case("ff0101")]
+#[case("ff01ff0101")]
+#[case("ffff0102ffff0102ff0380")]
+#[case("ffff01ff0280ffff01ff0280ff0380")]
+#[case("ff8300aaaaff8300aaaaff8300aaaa80")]
+#[case("ffff83666f6fff83626172ff8362617a80ff83666f6fff83626172ff8362617a80")]
+fn test_compress(#[case] input: &str) {
+    let mut a = Allocator::new();
+    let node = node_from_bytes(&mut a, &hex::decode(input).unwrap()).unwrap();
+    check_compress(&a, node);
+}
+
+#[test]
+fn test_compress_dag() {
+    // every pair refers to the same sub tree twice. Expanded, the tree has 2^64
+    // atoms, but each sub tree is only visited once
+    let mut a = Allocator::new();
+    let mut node = a.new_atom(b"foobar").unwrap();
+    for _ in 0..64 {
+        node = a.new_pair(node, node).unwrap();
+    }
+    let (fast, max) = check_compress(&a, node);
+    assert!(fast.len() < 200);
+    assert!(max.len() < 200);
+}
+
+#[test]
+fn test_compress_deep() {
+    // deep enough to overflow the stack, if we were recursing
+    let mut a = Allocator::new();
+    let atom = a.new_atom(b"foobar").unwrap();
+    let mut list = NodePtr::NIL;
+    let mut first = NodePtr::NIL;
+    for _ in 0..50_000 {
+        list = a.new_pair(atom, list).unwrap();
+        first = a.new_pair(first, atom).unwrap();
+    }
+    let node = a.new_pair(list, first).unwrap();
+    let (fast, max) = check_compress(&a, node);
+    assert!(fast.len() < 250_000);
+    assert!(max.len() < 250_000);
+}
+
+#[test]
+fn test_compressed_length() {
+    // ((1 2 3 4) 1 2 3 4)
+    let mut a = Allocator::new();
+    let buf = hex::decode("ffff01ff02ff03ff0480ff01ff02ff03ff0480").unwrap();
+    let node = node_from_bytes(&mut a, &buf).unwrap();
+
+    let compressed = node_to_bytes_backrefs(&a, node).unwrap();
+    assert_eq!(hex::encode(&compressed), "ffff01ff02ff03ff0480fe02");
+    assert_eq!(compressed_serialized_length(&a, node).unwrap(), 12);
+
+    assert_eq!(
+        node_to_bytes_backrefs_limit(&a, node, 12).unwrap(),
+        compressed
+    );
+    assert_eq!(
+        node_to_bytes_backrefs_limit(&a, node, 11)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::OutOfMemory
+    );
+}
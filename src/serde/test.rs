@@ -5,7 +5,7 @@ use crate::allocator::Allocator;
 use crate::serde::de::node_from_bytes;
 use crate::serde::de_br::node_from_bytes_backrefs;
 use crate::serde::ser::node_to_bytes;
-use crate::serde::ser_br::node_to_bytes_backrefs;
+use crate::serde::ser_br::{node_to_bytes_backrefs, node_to_bytes_backrefs_max};
 
 fn check_round_trip(obj_ser_br_hex: &str) {
     // serialized with br => obj => serialized no br =(allow_br)=> obj => serialized w br
@@ -37,6 +37,13 @@ fn check_round_trip(obj_ser_br_hex: &str) {
 
     // and compare to original
     assert_eq!(obj_ser_br, obj_ser_br_1);
+
+    // these are small enough for the maximum compression to find the same
+    // back-references
+    assert_eq!(
+        obj_ser_br,
+        node_to_bytes_backrefs_max(&allocator, obj).unwrap()
+    );
 }
 
 #[test]
@@ -1,15 +1,25 @@
 use std::io;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::Cursor;
 
-use super::errors::bad_encoding;
-use super::parse_atom::decode_size;
+use super::errors::{bad_encoding, non_canonical_encoding};
+use super::parse_atom::{check_canonical_atom, decode_size, parse_path, AtomSource};
 
 const MAX_SINGLE_BYTE: u8 = 0x7f;
 const BACK_REFERENCE: u8 = 0xfe;
 const CONS_BOX_MARKER: u8 = 0xff;
 
 pub fn serialized_length_from_bytes_trusted(b: &[u8]) -> io::Result<u64> {
-    let mut f = Cursor::new(b);
+    serialized_length_trusted(&mut Cursor::new(b))
+}
+
+/// read a CLVM serialization from the stream and return its length. Unlike
+/// `serialized_length_from_stream()`, back-references are not validated.
+/// Only the bytes of the serialization are read from the stream
+pub fn serialized_length_from_stream_trusted<S: AtomSource>(f: &mut S) -> io::Result<u64> {
+    serialized_length_trusted(f)
+}
+
+fn serialized_length_trusted<S: AtomSource>(f: &mut S) -> io::Result<u64> {
     let mut ops_counter = 1;
     let mut b = [0; 1];
     while ops_counter > 0 {
@@ -25,24 +35,18 @@ pub fn serialized_length_from_bytes_trusted(b: &[u8]) -> io::Result<u64> {
             let mut first_byte = [0; 1];
             f.read_exact(&mut first_byte)?;
             if first_byte[0] > MAX_SINGLE_BYTE {
-                let path_size = decode_size(&mut f, first_byte[0])?;
-                f.seek(SeekFrom::Current(path_size as i64))?;
-                if (f.get_ref().len() as u64) < f.position() {
-                    return Err(bad_encoding());
-                }
+                let path_size = decode_size(f, first_byte[0])?;
+                f.skip_atom(path_size)?;
             }
         } else if b[0] == 0x80 || b[0] <= MAX_SINGLE_BYTE {
             // This one byte we just read was the whole atom.
             // or the special case of NIL
         } else {
-            let blob_size = decode_size(&mut f, b[0])?;
-            f.seek(SeekFrom::Current(blob_size as i64))?;
-            if (f.get_ref().len() as u64) < f.position() {
-                return Err(bad_encoding());
-            }
+            let blob_size = decode_size(f, b[0])?;
+            f.skip_atom(blob_size)?;
         }
     }
-    Ok(f.position())
+    Ok(f.bytes_read())
 }
 
 use crate::sha2::{Digest, Sha256};
@@ -68,8 +72,9 @@ enum ParseOp {
     Cons,
 }
 
-// computes the tree-hash of a CLVM structure in serialized form
-pub fn tree_hash_from_stream(f: &mut Cursor<&[u8]>) -> io::Result<[u8; 32]> {
+// computes the tree-hash of a CLVM structure in serialized form. Only the
+// bytes of the serialization are read from the stream
+pub fn tree_hash_from_stream<S: AtomSource>(f: &mut S) -> io::Result<[u8; 32]> {
     let mut values: Vec<[u8; 32]> = Vec::new();
     let mut ops = vec![ParseOp::SExp];
 
@@ -84,16 +89,8 @@ pub fn tree_hash_from_stream(f: &mut Cursor<&[u8]>) -> io::Result<[u8; 32]> {
                     ops.push(ParseOp::SExp);
                 } else if b[0] == 0x80 {
                     values.push(hash_atom(&[]));
-                } else if b[0] <= MAX_SINGLE_BYTE {
-                    values.push(hash_atom(&b));
                 } else {
-                    let blob_size = decode_size(f, b[0])?;
-                    let blob = &f.get_ref()[f.position() as usize..];
-                    if (blob.len() as u64) < blob_size {
-                        return Err(bad_encoding());
-                    }
-                    f.set_position(f.position() + blob_size);
-                    values.push(hash_atom(&blob[..blob_size as usize]));
+                    values.push(hash_atom(f.read_atom(b[0])?));
                 }
             }
             ParseOp::Cons => {
@@ -111,11 +108,38 @@ pub fn tree_hash_from_stream(f: &mut Cursor<&[u8]>) -> io::Result<[u8; 32]> {
 /// of the CLVM object. This may fail if the serialization contains an invalid
 /// back-reference or if the buffer is truncated.
 pub fn serialized_length_from_bytes(b: &[u8]) -> io::Result<u64> {
-    use crate::serde::parse_atom::parse_path;
+    serialized_length(&mut Cursor::new(b), false)
+}
+
+/// like `serialized_length_from_bytes()` but fails with a "non-canonical
+/// encoding" error unless every atom is serialized in its shortest form.
+/// Back-references aren't allowed either, since the same tree can be
+/// serialized with or without them.
+pub fn serialized_length_from_bytes_strict(b: &[u8]) -> io::Result<u64> {
+    serialized_length(&mut Cursor::new(b), true)
+}
+
+/// returns true if the buffer holds exactly one CLVM serialization, with every
+/// atom in its canonical form and no back-references. See
+/// `serialized_length_from_bytes_strict()`.
+/// Use this to reject encodings that could be altered without altering the
+/// tree hash
+pub fn is_canonical(b: &[u8]) -> bool {
+    matches!(serialized_length_from_bytes_strict(b), Ok(len) if len == b.len() as u64)
+}
+
+/// read and validate a CLVM serialization from the stream, returning its
+/// length. This may fail if the serialization contains an invalid
+/// back-reference or if the stream is truncated. Only the bytes of the
+/// serialization are read from the stream
+pub fn serialized_length_from_stream<S: AtomSource>(f: &mut S) -> io::Result<u64> {
+    serialized_length(f, false)
+}
+
+fn serialized_length<S: AtomSource>(f: &mut S, strict: bool) -> io::Result<u64> {
     use crate::traverse_path::traverse_path;
     use crate::{allocator::SExp, Allocator};
 
-    let mut f = Cursor::new(b);
     let mut b = [0; 1];
 
     // the allocator is just used to track the tree structure, in order to
@@ -134,19 +158,22 @@ pub fn serialized_length_from_bytes(b: &[u8]) -> io::Result<u64> {
                     ops.push(ParseOp::SExp);
                     ops.push(ParseOp::SExp);
                 } else if b[0] == BACK_REFERENCE {
-                    let path = parse_path(&mut f)?;
+                    if strict {
+                        return Err(non_canonical_encoding());
+                    }
+                    let path = parse_path(f)?;
                     let back_reference = traverse_path(&allocator, path, values)?.1;
                     values = allocator.new_pair(back_reference, values)?;
                 } else if b[0] == 0x80 || b[0] <= MAX_SINGLE_BYTE {
                     // This one byte we just read was the whole atom.
                     // or the special case of NIL
                     values = allocator.new_pair(nil, values)?;
+                } else if strict {
+                    check_canonical_atom(b[0], f.read_atom(b[0])?)?;
+                    values = allocator.new_pair(nil, values)?;
                 } else {
-                    let blob_size = decode_size(&mut f, b[0])?;
-                    f.seek(SeekFrom::Current(blob_size as i64))?;
-                    if (f.get_ref().len() as u64) < f.position() {
-                        return Err(bad_encoding());
-                    }
+                    let blob_size = decode_size(f, b[0])?;
+                    f.skip_atom(blob_size)?;
                     values = allocator.new_pair(nil, values)?;
                 }
             }
@@ -166,7 +193,7 @@ pub fn serialized_length_from_bytes(b: &[u8]) -> io::Result<u64> {
         }
     }
     match allocator.sexp(values) {
-        SExp::Pair(_, _) => Ok(f.position()),
+        SExp::Pair(_, _) => Ok(f.bytes_read()),
         _ => Err(bad_encoding()),
     }
 }
@@ -524,4 +551,177 @@ ae5c3c40c50832a7aecc0b3ba4646568a00c01289c45e1f03b2b488080808080"
 
         assert_eq!(len, buf.len() as u64);
     }
+
+    // returns at most one byte per call to read(), to exercise the paths
+    // that can't rely on seeking or on the whole buffer being available
+    struct OneByteReader<'a>(&'a [u8]);
+
+    impl std::io::Read for OneByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_from_stream() {
+        use crate::serde::{
+            node_from_stream, node_from_stream_backrefs, node_to_bytes, ReadSource,
+        };
+
+        let block = include_bytes!("../../benches/block_af9c3d98.bin");
+        let len = serialized_length_from_bytes(block).unwrap();
+        assert_eq!(
+            serialized_length_from_stream(&mut ReadSource::new(&mut OneByteReader(block))).unwrap(),
+            len
+        );
+        assert_eq!(
+            serialized_length_from_stream_trusted(&mut ReadSource::new(&mut OneByteReader(block)))
+                .unwrap(),
+            len
+        );
+        let block = &block[..len as usize];
+        assert_eq!(
+            tree_hash_from_stream(&mut ReadSource::new(&mut OneByteReader(block))).unwrap(),
+            tree_hash_from_stream(&mut Cursor::new(block)).unwrap()
+        );
+
+        let mut a = Allocator::new();
+        let node =
+            node_from_stream(&mut a, &mut ReadSource::new(&mut OneByteReader(block))).unwrap();
+        assert_eq!(node_to_bytes(&a, node).unwrap(), block);
+
+        // a small tree with back references
+        let mut a = Allocator::new();
+        let buf = Vec::from_hex("ffff0102ff8403040506fe02").unwrap();
+        let node = node_from_stream_backrefs(
+            &mut a,
+            &mut ReadSource::new(&mut OneByteReader(&buf)),
+            |_| {},
+        )
+        .unwrap();
+        let expected = node_from_bytes_backrefs(&mut a, &buf).unwrap();
+        assert_eq!(
+            node_to_bytes(&a, node).unwrap(),
+            node_to_bytes(&a, expected).unwrap()
+        );
+        assert_eq!(
+            serialized_length_from_stream(&mut ReadSource::new(&mut OneByteReader(&buf))).unwrap(),
+            buf.len() as u64
+        );
+
+        // truncated input
+        let e = node_from_stream(
+            &mut a,
+            &mut ReadSource::new(&mut OneByteReader(&block[..1000])),
+        )
+        .unwrap_err();
+        assert_eq!(e.kind(), bad_encoding().kind());
+        let e =
+            serialized_length_from_stream(&mut ReadSource::new(&mut OneByteReader(&block[..1000])))
+                .unwrap_err();
+        assert_eq!(e.kind(), bad_encoding().kind());
+    }
+
+    #[rstest]
+    #[case("80", true)]
+    #[case("01", true)]
+    #[case("7f", true)]
+    #[case("8180", true)]
+    #[case("86666f6f626172", true)]
+    #[case("ff0180", true)]
+    // a single byte in the range 0x00-0x7f doesn't need a length prefix
+    #[case("8101", false)]
+    #[case("8100", false)]
+    #[case("ff018100", false)]
+    // the length prefix is longer than it needs to be
+    #[case("c000", false)]
+    #[case("c00180", false)]
+    #[case("e0000180", false)]
+    #[case("c006666f6f626172", false)]
+    fn test_canonical(#[case] serialization_as_hex: &str, #[case] canonical: bool) {
+        use crate::serde::{node_from_bytes, node_from_bytes_strict};
+        let buf = Vec::from_hex(serialization_as_hex).unwrap();
+        assert_eq!(is_canonical(&buf), canonical);
+        assert_eq!(serialized_length_from_bytes_strict(&buf).is_ok(), canonical);
+        let mut a = Allocator::new();
+        assert_eq!(node_from_bytes_strict(&mut a, &buf).is_ok(), canonical);
+
+        // the lenient versions accept all of them
+        assert_eq!(
+            serialized_length_from_bytes(&buf).unwrap(),
+            buf.len() as u64
+        );
+        assert!(node_from_bytes(&mut a, &buf).is_ok());
+    }
+
+    #[rstest]
+    #[case(0x3f, "bf", "c03f")]
+    #[case(0x40, "c040", "e00040")]
+    #[case(0x1fff, "dfff", "e01fff")]
+    #[case(0x2000, "e02000", "f0002000")]
+    fn test_canonical_prefix(#[case] len: usize, #[case] prefix: &str, #[case] longer: &str) {
+        let mut buf = Vec::from_hex(prefix).unwrap();
+        buf.resize(buf.len() + len, 0xaa);
+        assert!(is_canonical(&buf));
+
+        // the same atom, with a length prefix that's one byte longer
+        let mut buf = Vec::from_hex(longer).unwrap();
+        buf.resize(buf.len() + len, 0xaa);
+        assert_eq!(
+            serialized_length_from_bytes(&buf).unwrap(),
+            buf.len() as u64
+        );
+        assert!(!is_canonical(&buf));
+    }
+
+    #[rstest]
+    #[case("ff86666f6f626172fe01")]
+    #[case("ff86666f6f626172fe8101")]
+    #[case("ff86666f6f626172fe820001")]
+    #[case("ff86666f6f626172fec00101")]
+    fn test_canonical_backrefs(#[case] serialization_as_hex: &str) {
+        let buf = Vec::from_hex(serialization_as_hex).unwrap();
+        assert!(!is_canonical(&buf));
+        assert_eq!(
+            serialized_length_from_bytes_strict(&buf)
+                .unwrap_err()
+                .to_string(),
+            "non-canonical encoding"
+        );
+        assert_eq!(
+            serialized_length_from_bytes(&buf).unwrap(),
+            buf.len() as u64
+        );
+    }
+
+    #[test]
+    fn test_canonical_backref_expanded() {
+        // ("foobar" . "foobar"), with and without a back-reference. Only one
+        // of the two encodings of the same tree is canonical
+        let backref = Vec::from_hex("ff86666f6f626172fe02").unwrap();
+        let expanded = Vec::from_hex("ff86666f6f62617286666f6f626172").unwrap();
+        let mut a = Allocator::new();
+        let node = node_from_bytes_backrefs(&mut a, &backref).unwrap();
+        assert_eq!(
+            tree_hash_from_stream(&mut Cursor::new(&expanded[..])).unwrap(),
+            a.tree_hash(node)
+        );
+        assert!(is_canonical(&expanded));
+        assert!(!is_canonical(&backref));
+        assert!(crate::serde::node_from_bytes_strict(&mut a, &expanded).is_ok());
+        assert!(crate::serde::node_from_bytes_strict(&mut a, &backref).is_err());
+    }
+
+    #[test]
+    fn test_canonical_trailing_bytes() {
+        let buf = Vec::from_hex("ff018000").unwrap();
+        assert_eq!(serialized_length_from_bytes_strict(&buf).unwrap(), 3);
+        assert!(!is_canonical(&buf));
+        assert!(!is_canonical(&[]));
+    }
 }
@@ -0,0 +1,189 @@
+// A `TreeCache` assigns an id to every distinct sub tree of a clvm tree. Two
+// sub trees get the same id if, and only if, they are structurally
+// identical, regardless of whether they share the same `NodePtr` or not.
+// This is what the back-reference compressor works with.
+//
+// Ids are assigned bottom-up, so the children of a pair always have smaller
+// ids than the pair itself. The root has the largest id.
+
+use std::collections::HashMap;
+
+use crate::allocator::{Allocator, Atom, NodePtr, SExp};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Entry {
+    Atom(NodePtr),
+    Pair(u32, u32),
+}
+
+pub struct TreeCache<'a> {
+    allocator: &'a Allocator,
+    entries: Vec<Entry>,
+    // the serialized length (without back-references) of each sub tree
+    serialized_length: Vec<u64>,
+    // whether the sub tree appears more than once in the tree. Only those are
+    // worth back-referencing
+    repeated: Vec<bool>,
+    pairs: HashMap<(u32, u32), u32>,
+    atoms: HashMap<Atom<'a>, u32>,
+}
+
+fn atom_serialized_length(buf: &[u8]) -> u64 {
+    let lb = buf.len() as u64;
+    if lb == 0 || (lb == 1 && buf[0] < 0x80) {
+        1
+    } else if lb < 0x40 {
+        1 + lb
+    } else if lb < 0x2000 {
+        2 + lb
+    } else if lb < 0x10_0000 {
+        3 + lb
+    } else if lb < 0x800_0000 {
+        4 + lb
+    } else {
+        5 + lb
+    }
+}
+
+impl<'a> TreeCache<'a> {
+    pub fn new(allocator: &'a Allocator, root: NodePtr) -> Self {
+        let mut ret = Self {
+            allocator,
+            entries: Vec::new(),
+            serialized_length: Vec::new(),
+            repeated: Vec::new(),
+            pairs: HashMap::new(),
+            atoms: HashMap::new(),
+        };
+
+        // NodePtr -> id. Different NodePtrs may map to the same id
+        let mut ids = HashMap::<NodePtr, u32>::new();
+        let mut stack = vec![(root, false)];
+        while let Some((node, children_done)) = stack.pop() {
+            if ids.contains_key(&node) {
+                continue;
+            }
+            let id = match allocator.sexp(node) {
+                SExp::Atom => {
+                    let atom = allocator.atom(node);
+                    let len = atom_serialized_length(atom.as_ref());
+                    match ret.atoms.get(&atom) {
+                        Some(id) => *id,
+                        None => {
+                            let id = ret.push(Entry::Atom(node), len);
+                            ret.atoms.insert(atom, id);
+                            id
+                        }
+                    }
+                }
+                SExp::Pair(first, rest) => {
+                    if !children_done {
+                        stack.push((node, true));
+                        stack.push((rest, false));
+                        stack.push((first, false));
+                        continue;
+                    }
+                    let key = (ids[&first], ids[&rest]);
+                    match ret.pairs.get(&key) {
+                        Some(id) => *id,
+                        None => {
+                            let len = 1_u64
+                                .saturating_add(ret.serialized_length[key.0 as usize])
+                                .saturating_add(ret.serialized_length[key.1 as usize]);
+                            let id = ret.push(Entry::Pair(key.0, key.1), len);
+                            ret.pairs.insert(key, id);
+                            id
+                        }
+                    }
+                }
+            };
+            ids.insert(node, id);
+        }
+
+        // count the number of times each sub tree appears (saturating at 2).
+        // Parents have larger ids than their children, so by going from the
+        // root down, each parent's count is final before it's added to its
+        // children
+        let mut count = vec![0_u8; ret.entries.len()];
+        *count.last_mut().expect("empty tree") = 1;
+        for id in (0..ret.entries.len()).rev() {
+            if let Entry::Pair(first, rest) = ret.entries[id] {
+                let c = count[id];
+                for child in [first, rest] {
+                    count[child as usize] = (count[child as usize] + c).min(2);
+                }
+            }
+        }
+        ret.repeated = count.into_iter().map(|c| c > 1).collect();
+        ret
+    }
+
+    fn push(&mut self, entry: Entry, serialized_length: u64) -> u32 {
+        let id = self.entries.len() as u32;
+        self.entries.push(entry);
+        self.serialized_length.push(serialized_length);
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn root(&self) -> u32 {
+        (self.entries.len() - 1) as u32
+    }
+
+    pub fn entry(&self, id: u32) -> Entry {
+        self.entries[id as usize]
+    }
+
+    pub fn serialized_length(&self, id: u32) -> u64 {
+        self.serialized_length[id as usize]
+    }
+
+    pub fn is_repeated(&self, id: u32) -> bool {
+        self.repeated[id as usize]
+    }
+
+    pub fn atom(&self, id: u32) -> Atom<'a> {
+        match self.entries[id as usize] {
+            Entry::Atom(node) => self.allocator.atom(node),
+            Entry::Pair(..) => panic!("expected atom, got pair"),
+        }
+    }
+
+    /// the id of the pair `(first . rest)`, if it's part of the tree
+    pub fn find_pair(&self, first: u32, rest: u32) -> Option<u32> {
+        self.pairs.get(&(first, rest)).copied()
+    }
+
+    /// the id of nil, if it's part of the tree
+    pub fn find_nil(&self) -> Option<u32> {
+        self.atoms.get(&self.allocator.atom(NodePtr::NIL)).copied()
+    }
+}
+
+#[cfg(test)]
+use crate::serde::node_from_bytes;
+
+#[test]
+fn test_tree_cache() {
+    let mut a = Allocator::new();
+    // ((1 2) (1 2) 3)
+    let buf = hex::decode("ffff01ff0280ffff01ff0280ff0380").unwrap();
+    let node = node_from_bytes(&mut a, &buf).unwrap();
+    let tc = TreeCache::new(&a, node);
+
+    // 1, 2, nil, (2), (1 2), 3, (3), ((1 2) 3), ((1 2) (1 2) 3)
+    assert_eq!(tc.len(), 9);
+    assert_eq!(tc.root(), 8);
+    assert_eq!(tc.serialized_length(tc.root()), buf.len() as u64);
+
+    let repeated: Vec<u32> = (0..9).filter(|id| tc.is_repeated(*id)).collect();
+    // 1, 2, nil, (2) and (1 2)
+    assert_eq!(repeated, [0, 1, 2, 3, 4]);
+    assert_eq!(tc.find_pair(0, 3), Some(4));
+    assert_eq!(tc.find_pair(3, 0), None);
+    assert_eq!(tc.find_nil(), Some(2));
+    assert_eq!(tc.atom(5).as_ref(), [3]);
+}
@@ -12,8 +12,10 @@ use crate::more_ops::{
     op_point_add, op_pubkey_for_exp, op_sha256, op_strlen, op_substr, op_subtract,
 };
 use crate::number::Number;
-use crate::reduction::{EvalErr, Reduction, Response};
+use crate::reduction::{EvalErr, EvalErrKind, Reduction, Response};
 use crate::secp_ops::{op_secp256k1_verify, op_secp256r1_verify};
+use crate::serde::node_from_bytes;
+use crate::text::keyword_to_atom;
 
 use hex::FromHex;
 use num_traits::Num;
@@ -47,61 +49,9 @@ fn parse_atom(a: &mut Allocator, v: &str) -> NodePtr {
         a.new_number(num).unwrap()
     } else {
         let v = v.strip_prefix('#').unwrap_or(v);
-        match v {
-            "q" => a.new_atom(&[1]).unwrap(),
-            "a" => a.new_atom(&[2]).unwrap(),
-            "i" => a.new_atom(&[3]).unwrap(),
-            "c" => a.new_atom(&[4]).unwrap(),
-            "f" => a.new_atom(&[5]).unwrap(),
-            "r" => a.new_atom(&[6]).unwrap(),
-            "l" => a.new_atom(&[7]).unwrap(),
-            "x" => a.new_atom(&[8]).unwrap(),
-            "=" => a.new_atom(&[9]).unwrap(),
-            ">s" => a.new_atom(&[10]).unwrap(),
-            "sha256" => a.new_atom(&[11]).unwrap(),
-            "substr" => a.new_atom(&[12]).unwrap(),
-            "strlen" => a.new_atom(&[13]).unwrap(),
-            "concat" => a.new_atom(&[14]).unwrap(),
-
-            "+" => a.new_atom(&[16]).unwrap(),
-            "-" => a.new_atom(&[17]).unwrap(),
-            "*" => a.new_atom(&[18]).unwrap(),
-            "/" => a.new_atom(&[19]).unwrap(),
-            "divmod" => a.new_atom(&[20]).unwrap(),
-            ">" => a.new_atom(&[21]).unwrap(),
-            "ash" => a.new_atom(&[22]).unwrap(),
-            "lsh" => a.new_atom(&[23]).unwrap(),
-            "logand" => a.new_atom(&[24]).unwrap(),
-            "logior" => a.new_atom(&[25]).unwrap(),
-            "logxor" => a.new_atom(&[26]).unwrap(),
-            "lognot" => a.new_atom(&[27]).unwrap(),
-
-            "point_add" => a.new_atom(&[29]).unwrap(),
-            "pubkey_for_exp" => a.new_atom(&[30]).unwrap(),
-
-            "not" => a.new_atom(&[32]).unwrap(),
-            "any" => a.new_atom(&[33]).unwrap(),
-            "all" => a.new_atom(&[34]).unwrap(),
-
-            "softfork" => a.new_atom(&[36]).unwrap(),
-
-            "coinid" => a.new_atom(&[48]).unwrap(),
-
-            "g1_add" => a.new_atom(&[29]).unwrap(),
-            "g1_subtract" => a.new_atom(&[49]).unwrap(),
-            "g1_multiply" => a.new_atom(&[50]).unwrap(),
-            "g1_negate" => a.new_atom(&[51]).unwrap(),
-            "g2_add" => a.new_atom(&[52]).unwrap(),
-            "g2_subtract" => a.new_atom(&[53]).unwrap(),
-            "g2_multiply" => a.new_atom(&[54]).unwrap(),
-            "g2_negate" => a.new_atom(&[55]).unwrap(),
-            "g1_map" => a.new_atom(&[56]).unwrap(),
-            "g2_map" => a.new_atom(&[57]).unwrap(),
-            "bls_pairing_identity" => a.new_atom(&[58]).unwrap(),
-            "bls_verify" => a.new_atom(&[59]).unwrap(),
-            "secp256k1_verify" => a.new_atom(&[0x13, 0xd6, 0x1f, 0x00]).unwrap(),
-            "secp256r1_verify" => a.new_atom(&[0x1c, 0x3a, 0x8f, 0x00]).unwrap(),
-            _ => {
+        match keyword_to_atom(v) {
+            Some(op) => a.new_atom(op).unwrap(),
+            None => {
                 panic!("atom not supported \"{}\"", v);
             }
         }
@@ -183,6 +133,11 @@ pub fn node_eq(allocator: &Allocator, s1: NodePtr, s2: NodePtr) -> bool {
     }
 }
 
+// deserialize a node from its hex encoded serialization
+pub fn from_hex(a: &mut Allocator, hex: &str) -> NodePtr {
+    node_from_bytes(a, &Vec::from_hex(hex).unwrap()).unwrap()
+}
+
 type Opf = fn(&mut Allocator, NodePtr, Cost) -> Response;
 
 // the input is a list of test cases, each item is a tuple of:
@@ -320,7 +275,7 @@ fn test_single_argument_raise_atom() {
     let a1 = allocator.new_atom(&[65]).unwrap();
     let args = allocator.new_pair(a1, allocator.nil()).unwrap();
     let result = op_raise(&mut allocator, args, 100000);
-    assert_eq!(result, Err(EvalErr(a1, "clvm raise".to_string())));
+    assert_eq!(result, Err(EvalErr(a1, EvalErrKind::Raise)));
 }
 
 #[test]
@@ -335,7 +290,7 @@ fn test_single_argument_raise_pair() {
     // ((a1 a2))
     args = allocator.new_pair(args, allocator.nil()).unwrap();
     let result = op_raise(&mut allocator, args, 100000);
-    assert_eq!(result, Err(EvalErr(args, "clvm raise".to_string())));
+    assert_eq!(result, Err(EvalErr(args, EvalErrKind::Raise)));
 }
 
 #[test]
@@ -348,7 +303,7 @@ fn test_multi_argument_raise() {
     // (a1 a2)
     args = allocator.new_pair(a1, args).unwrap();
     let result = op_raise(&mut allocator, args, 100000);
-    assert_eq!(result, Err(EvalErr(args, "clvm raise".to_string())));
+    assert_eq!(result, Err(EvalErr(args, EvalErrKind::Raise)));
 }
 
 #[cfg(feature = "pre-eval")]
@@ -0,0 +1,510 @@
+// The clvm assembly syntax, as used by clvm_tools' `opc` and `opd`:
+//
+//   ()           nil
+//   (a b . c)    lists, optionally terminated by something other than nil
+//   -42          decimal integers
+//   0xcafe       hex atoms
+//   "foo" 'bar'  quoted strings
+//   sha256       operator keywords, as named by ChiaDialect
+//   ; ...        comments, until the end of the line
+//
+// Any other word is turned into an atom of its bytes, just like clvm_tools
+// does. Neither the assembler nor the disassembler recurse, so they can be
+// used on arbitrarily deep trees.
+
+use std::fmt;
+
+use crate::allocator::{Allocator, NodePtr, SExp};
+use crate::number::Number;
+use crate::reduction::EvalErr;
+
+// the operator keywords and their opcodes. When two keywords share an opcode,
+// the first one is used by the disassembler
+const KEYWORDS: [(&str, &[u8]); 49] = [
+    ("q", &[1]),
+    ("a", &[2]),
+    ("i", &[3]),
+    ("c", &[4]),
+    ("f", &[5]),
+    ("r", &[6]),
+    ("l", &[7]),
+    ("x", &[8]),
+    ("=", &[9]),
+    (">s", &[10]),
+    ("sha256", &[11]),
+    ("substr", &[12]),
+    ("strlen", &[13]),
+    ("concat", &[14]),
+    ("+", &[16]),
+    ("-", &[17]),
+    ("*", &[18]),
+    ("/", &[19]),
+    ("divmod", &[20]),
+    (">", &[21]),
+    ("ash", &[22]),
+    ("lsh", &[23]),
+    ("logand", &[24]),
+    ("logior", &[25]),
+    ("logxor", &[26]),
+    ("lognot", &[27]),
+    ("point_add", &[29]),
+    ("g1_add", &[29]),
+    ("pubkey_for_exp", &[30]),
+    ("not", &[32]),
+    ("any", &[33]),
+    ("all", &[34]),
+    ("softfork", &[36]),
+    ("coinid", &[48]),
+    ("g1_subtract", &[49]),
+    ("g1_multiply", &[50]),
+    ("g1_negate", &[51]),
+    ("g2_add", &[52]),
+    ("g2_subtract", &[53]),
+    ("g2_multiply", &[54]),
+    ("g2_negate", &[55]),
+    ("g1_map", &[56]),
+    ("g2_map", &[57]),
+    ("bls_pairing_identity", &[58]),
+    ("bls_verify", &[59]),
+    ("modpow", &[60]),
+    ("%", &[61]),
+    ("secp256k1_verify", &[0x13, 0xd6, 0x1f, 0x00]),
+    ("secp256r1_verify", &[0x1c, 0x3a, 0x8f, 0x00]),
+];
+
+/// return the opcode of an operator keyword, e.g. `sha256`
+pub fn keyword_to_atom(keyword: &str) -> Option<&'static [u8]> {
+    KEYWORDS
+        .iter()
+        .find(|(name, _)| *name == keyword)
+        .map(|(_, atom)| *atom)
+}
+
+/// return the keyword of an opcode, if it's an operator ChiaDialect knows
+pub fn atom_to_keyword(atom: &[u8]) -> Option<&'static str> {
+    KEYWORDS
+        .iter()
+        .find(|(_, op)| *op == atom)
+        .map(|(name, _)| *name)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnexpectedEnd,
+    // the byte offset into the input of the unexpected token
+    UnexpectedToken(usize),
+    UnterminatedString(usize),
+    InvalidHex(usize),
+    Alloc(EvalErr),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd => write!(f, "unexpected end of input"),
+            Self::UnexpectedToken(pos) => write!(f, "unexpected token at offset {pos}"),
+            Self::UnterminatedString(pos) => write!(f, "unterminated string at offset {pos}"),
+            Self::InvalidHex(pos) => write!(f, "invalid hex at offset {pos}"),
+            Self::Alloc(err) => write!(f, "{}", err.1),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<EvalErr> for ParseError {
+    fn from(err: EvalErr) -> Self {
+        Self::Alloc(err)
+    }
+}
+
+enum Token<'a> {
+    Open,
+    Close,
+    Dot,
+    Quoted(&'a str),
+    Word(&'a str),
+}
+
+struct Tokenizer<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn skip_whitespace(&mut self) {
+        let mut chars = self.text[self.pos..].char_indices();
+        let mut in_comment = false;
+        for (idx, c) in &mut chars {
+            if in_comment {
+                in_comment = c != '\n';
+            } else if c == ';' {
+                in_comment = true;
+            } else if !c.is_whitespace() {
+                self.pos += idx;
+                return;
+            }
+        }
+        self.pos = self.text.len();
+    }
+
+    // returns the token and its offset in the input
+    fn next_token(&mut self) -> Result<Option<(usize, Token<'a>)>, ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let rest = &self.text[start..];
+        let Some(c) = rest.chars().next() else {
+            return Ok(None);
+        };
+        let token = match c {
+            '(' => {
+                self.pos += 1;
+                Token::Open
+            }
+            ')' => {
+                self.pos += 1;
+                Token::Close
+            }
+            '"' | '\'' => {
+                let Some(len) = rest[1..].find(c) else {
+                    return Err(ParseError::UnterminatedString(start));
+                };
+                self.pos += len + 2;
+                Token::Quoted(&rest[1..len + 1])
+            }
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || "();".contains(c))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                match &rest[..len] {
+                    "." => Token::Dot,
+                    word => Token::Word(word),
+                }
+            }
+        };
+        Ok(Some((start, token)))
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    // an odd number of digits is padded with a leading 0
+    let padded;
+    let hex = if hex.len() % 2 == 1 {
+        padded = format!("0{hex}");
+        &padded
+    } else {
+        hex
+    };
+    // from_str_radix() would accept a leading + sign
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn is_decimal(word: &str) -> bool {
+    let digits = word.strip_prefix('-').unwrap_or(word);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn parse_word(a: &mut Allocator, word: &str, pos: usize) -> Result<NodePtr, ParseError> {
+    if is_decimal(word) {
+        let num: Number = word.parse().map_err(|_| ParseError::UnexpectedToken(pos))?;
+        return Ok(a.new_number(num)?);
+    }
+    if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        let buf = decode_hex(hex).ok_or(ParseError::InvalidHex(pos))?;
+        return Ok(a.new_atom(&buf)?);
+    }
+    if let Some(op) = keyword_to_atom(word) {
+        return Ok(a.new_atom(op)?);
+    }
+    Ok(a.new_atom(word.as_bytes())?)
+}
+
+// a list whose closing parenthesis we haven't seen yet
+#[derive(Default)]
+struct OpenList {
+    items: Vec<NodePtr>,
+    // set once we've seen the dot
+    dotted: bool,
+    tail: Option<NodePtr>,
+}
+
+/// Parse clvm assembly into a tree in the allocator. The input must contain
+/// exactly one expression.
+pub fn assemble(a: &mut Allocator, text: &str) -> Result<NodePtr, ParseError> {
+    let mut tokens = Tokenizer { text, pos: 0 };
+    let mut stack = Vec::<OpenList>::new();
+    let mut result = None;
+
+    while let Some((pos, token)) = tokens.next_token()? {
+        if result.is_some() {
+            return Err(ParseError::UnexpectedToken(pos));
+        }
+        let value = match token {
+            Token::Open => {
+                stack.push(OpenList::default());
+                continue;
+            }
+            Token::Dot => {
+                match stack.last_mut() {
+                    Some(list) if !list.items.is_empty() && !list.dotted => {
+                        list.dotted = true;
+                    }
+                    _ => return Err(ParseError::UnexpectedToken(pos)),
+                }
+                continue;
+            }
+            Token::Close => {
+                let Some(list) = stack.pop() else {
+                    return Err(ParseError::UnexpectedToken(pos));
+                };
+                let mut node = match (list.dotted, list.tail) {
+                    (false, _) => a.nil(),
+                    (true, Some(tail)) => tail,
+                    (true, None) => return Err(ParseError::UnexpectedToken(pos)),
+                };
+                for item in list.items.into_iter().rev() {
+                    node = a.new_pair(item, node)?;
+                }
+                node
+            }
+            Token::Quoted(s) => a.new_atom(s.as_bytes())?,
+            Token::Word(word) => parse_word(a, word, pos)?,
+        };
+        match stack.last_mut() {
+            None => result = Some(value),
+            Some(list) if list.tail.is_some() => {
+                return Err(ParseError::UnexpectedToken(pos));
+            }
+            Some(list) if list.dotted => list.tail = Some(value),
+            Some(list) => list.items.push(value),
+        }
+    }
+    result.ok_or(ParseError::UnexpectedEnd)
+}
+
+// whether the atom is the canonical representation of an integer
+fn is_canonical_int(buf: &[u8]) -> bool {
+    match buf {
+        [] => true,
+        [0] => false,
+        [0, b, ..] | [0xff, b, ..] => (buf[0] == 0) == (b & 0x80 != 0),
+        _ => true,
+    }
+}
+
+fn write_atom(out: &mut String, a: &Allocator, node: NodePtr, allow_keyword: bool) {
+    let atom = a.atom(node);
+    let buf = atom.as_ref();
+    if buf.is_empty() {
+        out.push_str("()");
+        return;
+    }
+    if allow_keyword {
+        if let Some(keyword) = atom_to_keyword(buf) {
+            out.push_str(keyword);
+            return;
+        }
+    }
+    if buf.len() <= 2 {
+        if is_canonical_int(buf) {
+            out.push_str(&a.number(node).to_string());
+            return;
+        }
+    } else if buf.iter().all(|b| (0x20..0x7f).contains(b)) {
+        // the string can't contain the quote character, there's no escaping
+        for quote in ['"', '\''] {
+            if !buf.contains(&(quote as u8)) {
+                out.push(quote);
+                out.push_str(std::str::from_utf8(buf).expect("ascii"));
+                out.push(quote);
+                return;
+            }
+        }
+    }
+    out.push_str("0x");
+    for b in buf {
+        out.push_str(&format!("{b:02x}"));
+    }
+}
+
+enum DisassembleOp {
+    // the bool is set for the first item of a list, which is disassembled as
+    // an operator keyword if it is one
+    Node(NodePtr, bool),
+    // the rest of a list, after its first item
+    Rest(NodePtr),
+}
+
+/// Convert a tree into clvm assembly. The first item of every list is printed
+/// as an operator keyword if it is one, e.g. `(a (q . 2) 1)`. `assemble()`
+/// of the result produces the same tree.
+pub fn disassemble(a: &Allocator, node: NodePtr) -> String {
+    let mut out = String::new();
+    let mut ops = vec![DisassembleOp::Node(node, false)];
+    while let Some(op) = ops.pop() {
+        match op {
+            DisassembleOp::Node(node, allow_keyword) => match a.sexp(node) {
+                SExp::Atom => write_atom(&mut out, a, node, allow_keyword),
+                SExp::Pair(first, rest) => {
+                    out.push('(');
+                    ops.push(DisassembleOp::Rest(rest));
+                    ops.push(DisassembleOp::Node(first, true));
+                }
+            },
+            DisassembleOp::Rest(node) => match a.sexp(node) {
+                SExp::Pair(first, rest) => {
+                    out.push(' ');
+                    ops.push(DisassembleOp::Rest(rest));
+                    ops.push(DisassembleOp::Node(first, false));
+                }
+                SExp::Atom => {
+                    if a.atom_len(node) != 0 {
+                        out.push_str(" . ");
+                        write_atom(&mut out, a, node, false);
+                    }
+                    out.push(')');
+                }
+            },
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+use crate::serde::{node_from_bytes, node_to_bytes};
+
+#[cfg(test)]
+use rstest::rstest;
+
+#[cfg(test)]
+#[rstest]
+#[case("()", "80")]
+#[case("0", "80")]
+#[case("1", "01")]
+#[case("-1", "81ff")]
+#[case("128", "820080")]
+#[case("-129", "82ff7f")]
+#[case("0x", "80")]
+#[case("0x00", "00")]
+#[case("0xfff", "820fff")]
+#[case("0XCAFE", "82cafe")]
+#[case("\"foobar\"", "86666f6f626172")]
+#[case("'foo\"bar'", "87666f6f22626172")]
+#[case("\"\"", "80")]
+#[case("foobar", "86666f6f626172")]
+#[case("q", "01")]
+#[case("sha256", "0b")]
+#[case("secp256k1_verify", "8413d61f00")]
+#[case("(q . 1)", "ff0101")]
+#[case("(1 2 3)", "ff01ff02ff0380")]
+#[case("(1 2 . 3)", "ff01ff0203")]
+#[case("(() . ())", "ff8080")]
+#[case("((1 . 2) . (3 . 4))", "ffff0102ff0304")]
+#[case("  ( a  (q . 2)\n 1 ) ", "ff02ffff0102ff0180")]
+#[case("(a ; comment (\n 1)", "ff02ff0180")]
+#[case("(\"a b\" . '(c)')", "ff8361206283286329")]
+#[case("(g1_add point_add)", "ff1dff1d80")]
+fn test_assemble(#[case] text: &str, #[case] expected: &str) {
+    let mut a = Allocator::new();
+    let node = assemble(&mut a, text).unwrap();
+    assert_eq!(hex::encode(node_to_bytes(&a, node).unwrap()), expected);
+}
+
+#[cfg(test)]
+#[rstest]
+#[case("", ParseError::UnexpectedEnd)]
+#[case("(1 2", ParseError::UnexpectedEnd)]
+#[case("1 2", ParseError::UnexpectedToken(2))]
+#[case("(1))", ParseError::UnexpectedToken(3))]
+#[case(")", ParseError::UnexpectedToken(0))]
+#[case("(. 1)", ParseError::UnexpectedToken(1))]
+#[case("(1 . )", ParseError::UnexpectedToken(5))]
+#[case("(1 . 2 3)", ParseError::UnexpectedToken(7))]
+#[case("(1 . 2 . 3)", ParseError::UnexpectedToken(7))]
+#[case("\"foo", ParseError::UnterminatedString(0))]
+#[case("(1 'foo)", ParseError::UnterminatedString(3))]
+#[case("0xcafg", ParseError::InvalidHex(0))]
+#[case("0x+f", ParseError::InvalidHex(0))]
+fn test_assemble_errors(#[case] text: &str, #[case] expected: ParseError) {
+    let mut a = Allocator::new();
+    assert_eq!(assemble(&mut a, text), Err(expected));
+}
+
+#[cfg(test)]
+#[rstest]
+#[case("80", "()")]
+#[case("01", "1")]
+#[case("81ff", "-1")]
+#[case("820080", "128")]
+#[case("8100", "0x00")]
+#[case("820001", "0x0001")]
+#[case("83018000", "0x018000")]
+#[case("86666f6f626172", "\"foobar\"")]
+#[case("87666f6f22626172", "'foo\"bar'")]
+#[case("87666f6f22272062", "0x666f6f22272062")]
+#[case("ff0101", "(q . 1)")]
+#[case("ff01ff01ff0180", "(q 1 1)")]
+#[case("ff02ffff0102ff0180", "(a (q . 2) 1)")]
+#[case("ff01ff0203", "(q 2 . 3)")]
+#[case("ff8080", "(())")]
+#[case("ffff0b80ff0b80", "((sha256) 11)")]
+#[case("ff8413d61f00ff0580", "(secp256k1_verify 5)")]
+#[case("ff1dff1d80", "(point_add 29)")]
+#[case("ff0fff0f80", "(15 15)")]
+fn test_disassemble(#[case] serialized: &str, #[case] expected: &str) {
+    let mut a = Allocator::new();
+    let node = node_from_bytes(&mut a, &hex::decode(serialized).unwrap()).unwrap();
+    assert_eq!(disassemble(&a, node), expected);
+}
+
+#[test]
+fn test_round_trip() {
+    let mut a = Allocator::new();
+    let texts = [
+        "(a (q 2 (i (= 11 ()) (q 1 . 1) (q 18 5 (a 2 (c 2 (c 5 (c (- 11 (q . 1)) ())))))) 1) 1)",
+        "(softfork (q . 1000) (q . 0) (q g1_multiply (q . 0x97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb) (q . 2)) ())",
+        "(\"hello world\" 'it\"s' -32768 32767 0x0100 (()) . 0xff)",
+        "((c . 1) . (4 . 4))",
+    ];
+    for text in texts {
+        let node = assemble(&mut a, text).unwrap();
+        let disassembled = disassemble(&a, node);
+        let node2 = assemble(&mut a, &disassembled).unwrap();
+        assert_eq!(
+            node_to_bytes(&a, node).unwrap(),
+            node_to_bytes(&a, node2).unwrap()
+        );
+        assert_eq!(disassemble(&a, node2), disassembled);
+    }
+
+    // every byte value survives the round trip, in either position
+    for b in 0..=255_u8 {
+        for atom in [vec![b], vec![b, b], vec![b; 3]] {
+            let atom = a.new_atom(&atom).unwrap();
+            let list = a.new_pair(atom, atom).unwrap();
+            let text = disassemble(&a, list);
+            let node = assemble(&mut a, &text).unwrap();
+            assert_eq!(
+                node_to_bytes(&a, node).unwrap(),
+                node_to_bytes(&a, list).unwrap(),
+                "{text}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_deep_tree() {
+    let mut a = Allocator::new();
+    let depth = 100_000;
+    let text = format!("{}{}", "(".repeat(depth), ")".repeat(depth));
+    let node = assemble(&mut a, &text).unwrap();
+    assert_eq!(disassemble(&a, node), text);
+}
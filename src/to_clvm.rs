@@ -0,0 +1,152 @@
+use crate::allocator::{Allocator, NodePtr};
+use crate::number::Number;
+use crate::reduction::EvalErr;
+
+pub use clvm_derive::ToClvm;
+
+/// Convert a value into a clvm tree in the given allocator.
+///
+/// * integers and `Number` become atoms, in their canonical representation
+/// * `[u8; N]` becomes an atom with those bytes
+/// * `()` becomes nil
+/// * `(A, B)` becomes the pair `(A . B)`
+/// * `Vec<T>` and `[T]` become proper lists
+/// * `Option<T>` becomes nil for `None`
+///
+/// Structs can derive this trait, see the `clvm-derive` crate.
+pub trait ToClvm {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr>;
+}
+
+impl ToClvm for NodePtr {
+    fn to_clvm(&self, _a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        Ok(*self)
+    }
+}
+
+impl<T: ToClvm + ?Sized> ToClvm for &T {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        (*self).to_clvm(a)
+    }
+}
+
+impl<T: ToClvm + ?Sized> ToClvm for Box<T> {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        self.as_ref().to_clvm(a)
+    }
+}
+
+macro_rules! int_to_clvm {
+    ($($t:ty),*) => {
+        $(
+            impl ToClvm for $t {
+                fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+                    a.new_number(Number::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+int_to_clvm!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, usize, isize);
+
+impl ToClvm for Number {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        a.new_number(self.clone())
+    }
+}
+
+impl<const N: usize> ToClvm for [u8; N] {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        a.new_atom(self)
+    }
+}
+
+impl ToClvm for () {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        Ok(a.nil())
+    }
+}
+
+impl<A: ToClvm, B: ToClvm> ToClvm for (A, B) {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        let first = self.0.to_clvm(a)?;
+        let rest = self.1.to_clvm(a)?;
+        a.new_pair(first, rest)
+    }
+}
+
+impl<T: ToClvm> ToClvm for [T] {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        let mut ret = a.nil();
+        for item in self.iter().rev() {
+            let value = item.to_clvm(a)?;
+            ret = a.new_pair(value, ret)?;
+        }
+        Ok(ret)
+    }
+}
+
+impl<T: ToClvm> ToClvm for Vec<T> {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        self.as_slice().to_clvm(a)
+    }
+}
+
+impl<T: ToClvm> ToClvm for Option<T> {
+    fn to_clvm(&self, a: &mut Allocator) -> Result<NodePtr, EvalErr> {
+        match self {
+            Some(value) => value.to_clvm(a),
+            None => Ok(a.nil()),
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::serde::node_to_bytes;
+
+#[cfg(test)]
+fn to_hex<T: ToClvm>(value: T) -> String {
+    let mut a = Allocator::new();
+    let node = value.to_clvm(&mut a).unwrap();
+    hex::encode(node_to_bytes(&a, node).unwrap())
+}
+
+#[test]
+fn test_to_clvm_primitives() {
+    assert_eq!(to_hex(0_u8), "80");
+    assert_eq!(to_hex(1_u32), "01");
+    assert_eq!(to_hex(-1_i8), "81ff");
+    assert_eq!(to_hex(0x80_u16), "820080");
+    assert_eq!(to_hex(u64::MAX), "8900ffffffffffffffff");
+    assert_eq!(to_hex(i128::MIN), "9080000000000000000000000000000000");
+    assert_eq!(to_hex(Number::from(1337)), "820539");
+    assert_eq!(to_hex([0xff_u8, 0, 1]), "83ff0001");
+    assert_eq!(to_hex([0_u8; 0]), "80");
+    assert_eq!(to_hex(()), "80");
+}
+
+#[test]
+fn test_to_clvm_compound() {
+    // (1 . 2)
+    assert_eq!(to_hex((1, 2)), "ff0102");
+    // (1 2 3)
+    assert_eq!(to_hex(vec![1, 2, 3]), "ff01ff02ff0380");
+    assert_eq!(to_hex([1, 2, 3].as_slice()), "ff01ff02ff0380");
+    assert_eq!(to_hex(Vec::<u8>::new()), "80");
+    assert_eq!(to_hex(Some(5)), "05");
+    assert_eq!(to_hex(None::<u32>), "80");
+    // ((1 . 2) (3 4))
+    assert_eq!(to_hex(((1, 2), (vec![3, 4], ()))), "ffff0102ffff03ff048080");
+    assert_eq!(to_hex(Box::new((1, 2))), "ff0102");
+
+    let mut a = Allocator::new();
+    let node = a.new_atom(b"foobar").unwrap();
+    assert_eq!(node.to_clvm(&mut a).unwrap(), node);
+}
+
+#[test]
+fn test_to_clvm_limits() {
+    let mut a = Allocator::new_limited(500);
+    assert!(vec![[0_u8; 100]; 10].to_clvm(&mut a).is_err());
+}
@@ -1,12 +1,6 @@
 use crate::allocator::{Allocator, NodePtr, SExp};
-use crate::cost::Cost;
-use crate::reduction::{EvalErr, Reduction, Response};
-
-// lowered from measured 147 per bit. It doesn't seem to take this long in
-// practice
-const TRAVERSE_BASE_COST: Cost = 40;
-const TRAVERSE_COST_PER_ZERO_BYTE: Cost = 4;
-const TRAVERSE_COST_PER_BIT: Cost = 4;
+use crate::cost::{Cost, CostSchedule};
+use crate::reduction::{EvalErr, EvalErrKind, Reduction, Response};
 
 // `run_program` has two stacks: the operand stack (of `Node` objects) and the
 // operator stack (of Operation)
@@ -32,14 +26,28 @@ const fn first_non_zero(buf: &[u8]) -> usize {
 }
 
 pub fn traverse_path(allocator: &Allocator, node_index: &[u8], args: NodePtr) -> Response {
+    traverse_path_with_costs(allocator, node_index, args, &CostSchedule::DEFAULT)
+}
+
+pub fn traverse_path_with_costs(
+    allocator: &Allocator,
+    node_index: &[u8],
+    args: NodePtr,
+    costs: &CostSchedule,
+) -> Response {
     let mut arg_list: NodePtr = args;
 
     // find first non-zero byte
     let first_bit_byte_index = first_non_zero(node_index);
 
-    let mut cost: Cost = TRAVERSE_BASE_COST
-        + (first_bit_byte_index as Cost) * TRAVERSE_COST_PER_ZERO_BYTE
-        + TRAVERSE_COST_PER_BIT;
+    let mut cost: Cost = costs
+        .traverse_base_cost
+        .saturating_add(
+            costs
+                .traverse_cost_per_zero_byte
+                .saturating_mul(first_bit_byte_index as Cost),
+        )
+        .saturating_add(costs.traverse_cost_per_bit);
 
     if first_bit_byte_index >= node_index.len() {
         return Ok(Reduction(cost, allocator.nil()));
@@ -55,7 +63,7 @@ pub fn traverse_path(allocator: &Allocator, node_index: &[u8], args: NodePtr) ->
         let is_bit_set: bool = (node_index[byte_idx] & bitmask) != 0;
         match allocator.sexp(arg_list) {
             SExp::Atom => {
-                return Err(EvalErr(arg_list, "path into atom".into()));
+                return Err(EvalErr(arg_list, EvalErrKind::PathIntoAtom));
             }
             SExp::Pair(left, right) => {
                 arg_list = if is_bit_set { right } else { left };
@@ -67,7 +75,7 @@ pub fn traverse_path(allocator: &Allocator, node_index: &[u8], args: NodePtr) ->
         } else {
             bitmask <<= 1;
         }
-        cost += TRAVERSE_COST_PER_BIT;
+        cost = cost.saturating_add(costs.traverse_cost_per_bit);
     }
     Ok(Reduction(cost, arg_list))
 }
@@ -75,21 +83,34 @@ pub fn traverse_path(allocator: &Allocator, node_index: &[u8], args: NodePtr) ->
 // The cost calculation for this version of traverse_path assumes the node_index has the canonical
 // integer representation (which is true for SmallAtom in the allocator). If there are any
 // redundant leading zeros, the slow path must be used
-pub fn traverse_path_fast(allocator: &Allocator, mut node_index: u32, args: NodePtr) -> Response {
+pub fn traverse_path_fast(allocator: &Allocator, node_index: u32, args: NodePtr) -> Response {
+    traverse_path_fast_with_costs(allocator, node_index, args, &CostSchedule::DEFAULT)
+}
+
+pub fn traverse_path_fast_with_costs(
+    allocator: &Allocator,
+    mut node_index: u32,
+    args: NodePtr,
+    costs: &CostSchedule,
+) -> Response {
     if node_index == 0 {
         return Ok(Reduction(
-            TRAVERSE_BASE_COST + TRAVERSE_COST_PER_BIT,
+            costs
+                .traverse_base_cost
+                .saturating_add(costs.traverse_cost_per_bit),
             allocator.nil(),
         ));
     }
 
     let mut arg_list: NodePtr = args;
 
-    let mut cost: Cost = TRAVERSE_BASE_COST + TRAVERSE_COST_PER_BIT;
+    let mut cost: Cost = costs
+        .traverse_base_cost
+        .saturating_add(costs.traverse_cost_per_bit);
     let mut num_bits = 0;
     while node_index != 1 {
         let SExp::Pair(left, right) = allocator.sexp(arg_list) else {
-            return Err(EvalErr(arg_list, "path into atom".into()));
+            return Err(EvalErr(arg_list, EvalErrKind::PathIntoAtom));
         };
 
         let is_bit_set: bool = (node_index & 0x01) != 0;
@@ -98,11 +119,11 @@ pub fn traverse_path_fast(allocator: &Allocator, mut node_index: u32, args: Node
         num_bits += 1
     }
 
-    cost += num_bits * TRAVERSE_COST_PER_BIT;
+    cost = cost.saturating_add(costs.traverse_cost_per_bit.saturating_mul(num_bits));
     // since positive numbers sometimes need a leading zero, e.g. 0x80, 0x8000 etc. We also
     // need to add the cost of that leading zero byte
     if num_bits == 7 || num_bits == 15 || num_bits == 23 || num_bits == 31 {
-        cost += TRAVERSE_COST_PER_ZERO_BYTE;
+        cost = cost.saturating_add(costs.traverse_cost_per_zero_byte);
     }
 
     Ok(Reduction(cost, arg_list))
@@ -183,23 +204,23 @@ fn test_traverse_path() {
     // errors
     assert_eq!(
         traverse_path(&a, &[0b1011], list).unwrap_err(),
-        EvalErr(nul, "path into atom".to_string())
+        EvalErr(nul, EvalErrKind::PathIntoAtom)
     );
     assert_eq!(
         traverse_path(&a, &[0b1101], list).unwrap_err(),
-        EvalErr(n1, "path into atom".to_string())
+        EvalErr(n1, EvalErrKind::PathIntoAtom)
     );
     assert_eq!(
         traverse_path(&a, &[0b1001], list).unwrap_err(),
-        EvalErr(n1, "path into atom".to_string())
+        EvalErr(n1, EvalErrKind::PathIntoAtom)
     );
     assert_eq!(
         traverse_path(&a, &[0b1010], list).unwrap_err(),
-        EvalErr(n2, "path into atom".to_string())
+        EvalErr(n2, EvalErrKind::PathIntoAtom)
     );
     assert_eq!(
         traverse_path(&a, &[0b1110], list).unwrap_err(),
-        EvalErr(n2, "path into atom".to_string())
+        EvalErr(n2, EvalErrKind::PathIntoAtom)
     );
 }
 
@@ -241,22 +262,22 @@ fn test_traverse_path_fast_fast() {
     // errors
     assert_eq!(
         traverse_path_fast(&a, 0b1011, list).unwrap_err(),
-        EvalErr(nul, "path into atom".to_string())
+        EvalErr(nul, EvalErrKind::PathIntoAtom)
     );
     assert_eq!(
         traverse_path_fast(&a, 0b1101, list).unwrap_err(),
-        EvalErr(n1, "path into atom".to_string())
+        EvalErr(n1, EvalErrKind::PathIntoAtom)
     );
     assert_eq!(
         traverse_path_fast(&a, 0b1001, list).unwrap_err(),
-        EvalErr(n1, "path into atom".to_string())
+        EvalErr(n1, EvalErrKind::PathIntoAtom)
     );
     assert_eq!(
         traverse_path_fast(&a, 0b1010, list).unwrap_err(),
-        EvalErr(n2, "path into atom".to_string())
+        EvalErr(n2, EvalErrKind::PathIntoAtom)
     );
     assert_eq!(
         traverse_path_fast(&a, 0b1110, list).unwrap_err(),
-        EvalErr(n2, "path into atom".to_string())
+        EvalErr(n2, EvalErrKind::PathIntoAtom)
     );
 }
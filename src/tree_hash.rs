@@ -0,0 +1,310 @@
+use crate::allocator::{len_for_value, Allocator, NodePtr, NodeVisitor};
+use crate::more_ops::PRECOMPUTED_HASHES;
+use crate::sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+pub type TreeHash = [u8; 32];
+
+pub(crate) fn hash_atom(buf: &[u8]) -> TreeHash {
+    let mut ctx = Sha256::new();
+    ctx.update([1_u8]);
+    ctx.update(buf);
+    ctx.finalize().into()
+}
+
+// the hash of a small atom (as stored in a NodePtr). This covers nil and one,
+// which are very common, and uses the precomputed hashes for small values
+pub(crate) fn hash_small_atom(val: u32) -> TreeHash {
+    if let Some(hash) = PRECOMPUTED_HASHES.get(val as usize) {
+        *hash
+    } else {
+        let buf = val.to_be_bytes();
+        hash_atom(&buf[4 - len_for_value(val)..])
+    }
+}
+
+pub(crate) fn hash_pair(first: &TreeHash, rest: &TreeHash) -> TreeHash {
+    let mut ctx = Sha256::new();
+    ctx.update([2_u8]);
+    ctx.update(first);
+    ctx.update(rest);
+    ctx.finalize().into()
+}
+
+// the tree hashes remembered by an Allocator, see
+// Allocator::enable_tree_hash_cache()
+#[derive(Debug, Default)]
+pub(crate) struct TreeHashCache {
+    hashes: HashMap<NodePtr, TreeHash>,
+    // the nodes added to hashes, in the order they were added. This lets
+    // truncate() find the entries added since a checkpoint without scanning
+    // the whole map
+    log: Vec<NodePtr>,
+}
+
+impl TreeHashCache {
+    fn insert(&mut self, node: NodePtr, hash: TreeHash) {
+        self.hashes.insert(node, hash);
+        self.log.push(node);
+    }
+
+    // the number of entries added so far. This is recorded in a Checkpoint
+    pub(crate) fn len(&self) -> usize {
+        self.log.len()
+    }
+
+    // forget the entries added after the first len ones whose nodes no longer
+    // exist (according to keep), after restoring a checkpoint. Nodes that
+    // still exist are kept, and stay in the log, since restoring an earlier
+    // checkpoint may still need to remove them
+    pub(crate) fn truncate(&mut self, len: usize, keep: impl Fn(NodePtr) -> bool) {
+        if len >= self.log.len() {
+            return;
+        }
+        for node in self.log.split_off(len) {
+            if keep(node) {
+                self.log.push(node);
+            } else {
+                self.hashes.remove(&node);
+            }
+        }
+    }
+}
+
+// compute the tree hash of node, using (and adding to) the hashes in cache.
+// This does not recurse, so it's safe to use on arbitrarily deep trees
+pub(crate) fn tree_hash_cached(
+    a: &Allocator,
+    node: NodePtr,
+    cache: &mut TreeHashCache,
+) -> TreeHash {
+    let mut stack = vec![node];
+    while let Some(n) = stack.last().copied() {
+        if cache.hashes.contains_key(&n) {
+            stack.pop();
+            continue;
+        }
+        let hash = match a.node(n) {
+            NodeVisitor::Buffer(buf) => hash_atom(buf),
+            NodeVisitor::U32(val) => hash_small_atom(val),
+            NodeVisitor::Pair(first, rest) => {
+                match (cache.hashes.get(&first), cache.hashes.get(&rest)) {
+                    (Some(first), Some(rest)) => hash_pair(first, rest),
+                    (first_hash, rest_hash) => {
+                        if rest_hash.is_none() {
+                            stack.push(rest);
+                        }
+                        if first_hash.is_none() {
+                            stack.push(first);
+                        }
+                        continue;
+                    }
+                }
+            }
+        };
+        cache.insert(n, hash);
+        stack.pop();
+    }
+    cache.hashes[&node]
+}
+
+enum TreeOp {
+    SExp(NodePtr),
+    Cons(NodePtr),
+}
+
+// compute the tree hash of node without a persistent cache. The hashes of
+// pairs are remembered for the duration of the call, so shared sub trees (e.g.
+// from back-references) are only hashed once
+pub(crate) fn tree_hash_uncached(a: &Allocator, node: NodePtr) -> TreeHash {
+    let mut hashes = Vec::<TreeHash>::new();
+    let mut pairs = HashMap::<NodePtr, TreeHash>::new();
+    let mut ops = vec![TreeOp::SExp(node)];
+
+    while let Some(op) = ops.pop() {
+        match op {
+            TreeOp::SExp(node) => match a.node(node) {
+                NodeVisitor::Buffer(buf) => hashes.push(hash_atom(buf)),
+                NodeVisitor::U32(val) => hashes.push(hash_small_atom(val)),
+                NodeVisitor::Pair(first, rest) => {
+                    if let Some(hash) = pairs.get(&node) {
+                        hashes.push(*hash);
+                        continue;
+                    }
+                    ops.push(TreeOp::Cons(node));
+                    ops.push(TreeOp::SExp(first));
+                    ops.push(TreeOp::SExp(rest));
+                }
+            },
+            TreeOp::Cons(node) => {
+                let first = hashes.pop().unwrap();
+                let rest = hashes.pop().unwrap();
+                let hash = hash_pair(&first, &rest);
+                pairs.insert(node, hash);
+                hashes.push(hash);
+            }
+        }
+    }
+
+    assert!(hashes.len() == 1);
+    hashes[0]
+}
+
+/// compute the standard `sha256tree` hash of a node. This does not recurse,
+/// so it can be used on arbitrarily deep trees. If the allocator has its tree
+/// hash cache enabled, it's used (see `Allocator::enable_tree_hash_cache()`)
+pub fn tree_hash(a: &Allocator, node: NodePtr) -> TreeHash {
+    a.tree_hash(node)
+}
+
+#[cfg(test)]
+use crate::serde::node_from_bytes;
+
+#[cfg(test)]
+use hex::FromHex;
+
+#[cfg(test)]
+fn check_tree_hash(a: &mut Allocator, serialized: &str, expected: &str) -> NodePtr {
+    let buf = Vec::from_hex(serialized).unwrap();
+    let node = node_from_bytes(a, &buf).unwrap();
+    assert_eq!(hex::encode(tree_hash(a, node)), expected);
+    assert_eq!(hex::encode(tree_hash_uncached(a, node)), expected);
+    assert_eq!(
+        hex::encode(tree_hash_cached(a, node, &mut TreeHashCache::default())),
+        expected
+    );
+    node
+}
+
+#[cfg(test)]
+const TREE_HASH_CASES: [(&str, &str); 5] = [
+    (
+        "80",
+        "4bf5122f344554c53bde2ebb8cd2b7e3d1600ad631c385a5d7cce23c7785459a",
+    ), // ()
+    (
+        "ff83666f6f83626172",
+        "c518e45ae6a7b4146017b7a1d81639051b132f1f5572ce3088a3898a9ed1280b",
+    ), // (foo . bar)
+    (
+        "ff83666f6fff8362617280",
+        "c97d97cc81100a4980080ba81ff1ba3985f7cff1db9d41d904b9d512bb875144",
+    ), // (foo bar)
+    (
+        "ffff0102ff0304",
+        "2824018d148bc6aed0847e2c86aaa8a5407b916169f15b12cea31fa932fc4c8d",
+    ), // ((1 . 2) . (3 . 4))
+    (
+        "ff01ff02ff03ff04ff05ff0680",
+        "65de5098d18bebd62aee37de32f0b62d1803d9c7c48f10dca25501243d7a0392",
+    ), // (1 2 3 4 5 6)
+];
+
+#[test]
+fn test_tree_hash() {
+    let mut a = Allocator::new();
+    for (serialized, expected) in TREE_HASH_CASES {
+        check_tree_hash(&mut a, serialized, expected);
+    }
+}
+
+#[test]
+fn test_tree_hash_cache() {
+    let mut a = Allocator::new();
+    a.enable_tree_hash_cache();
+    assert!(a.has_tree_hash_cache());
+    let mut nodes = vec![];
+    for (serialized, expected) in TREE_HASH_CASES {
+        nodes.push(check_tree_hash(&mut a, serialized, expected));
+    }
+    // the second time, the hashes come from the cache
+    for (node, (_, expected)) in nodes.iter().zip(TREE_HASH_CASES) {
+        assert_eq!(hex::encode(a.tree_hash(*node)), expected);
+    }
+}
+
+#[test]
+fn test_tree_hash_cache_checkpoint() {
+    let mut a = Allocator::new();
+    a.enable_tree_hash_cache();
+    let (serialized, expected) = TREE_HASH_CASES[1];
+    let foo_bar = check_tree_hash(&mut a, serialized, expected);
+    let cp = a.checkpoint();
+
+    // (foo bar) is allocated after the checkpoint. Once we restore it,
+    // (1 2 3 4 5 6) reuses the same pair slots, so the hashes cached for them
+    // must have been dropped
+    let (serialized, expected) = TREE_HASH_CASES[2];
+    check_tree_hash(&mut a, serialized, expected);
+    a.restore_checkpoint(&cp);
+    let (serialized, expected) = TREE_HASH_CASES[4];
+    check_tree_hash(&mut a, serialized, expected);
+    let (serialized, expected) = TREE_HASH_CASES[2];
+    check_tree_hash(&mut a, serialized, expected);
+
+    // nodes allocated before the checkpoint are still cached
+    assert_eq!(hex::encode(a.tree_hash(foo_bar)), TREE_HASH_CASES[1].1);
+}
+
+#[test]
+fn test_tree_hash_cache_nested_checkpoints() {
+    let mut a = Allocator::new();
+    a.enable_tree_hash_cache();
+    let cp1 = a.checkpoint();
+    let (serialized, expected) = TREE_HASH_CASES[3];
+    let node = node_from_bytes(&mut a, &Vec::from_hex(serialized).unwrap()).unwrap();
+    let cp2 = a.checkpoint();
+
+    // node is allocated before cp2, but hashed after it. Restoring cp2 keeps
+    // its cached hash, restoring cp1 must drop it
+    assert_eq!(hex::encode(a.tree_hash(node)), expected);
+    a.restore_checkpoint(&cp2);
+    assert_eq!(hex::encode(a.tree_hash(node)), expected);
+    a.restore_checkpoint(&cp1);
+
+    // (1 2 3 4 5 6) reuses the pair slots of ((1 . 2) . (3 . 4))
+    let (serialized, expected) = TREE_HASH_CASES[4];
+    check_tree_hash(&mut a, serialized, expected);
+}
+
+#[test]
+fn test_tree_hash_deep_tree() {
+    let mut a = Allocator::new();
+    a.enable_tree_hash_cache();
+    let mut top = a.nil();
+    for _ in 0..100_000 {
+        let one = a.one();
+        top = a.new_pair(one, top).unwrap();
+    }
+    let hash = a.tree_hash(top);
+    let mut b = Allocator::new();
+    let top = b.copy_from(&a, top).unwrap();
+    assert_eq!(b.tree_hash(top), hash);
+}
+
+#[test]
+fn test_hash_small_atom() {
+    let mut a = Allocator::new();
+    for val in [0, 1, 2, 35, 36, 37, 0x7f, 0x80, 0xffff, 0x3ff_ffff] {
+        let node = a.new_small_number(val).unwrap();
+        let expected = hash_atom(a.atom(node).as_ref());
+        assert_eq!(hash_small_atom(val), expected);
+        assert_eq!(tree_hash(&a, node), expected);
+    }
+}
+
+#[test]
+fn test_tree_hash_dag() {
+    // a tree where every pair refers to the same sub tree twice. Expanded, it
+    // has 2^64 atoms, but each pair is only hashed once
+    let mut a = Allocator::new();
+    let mut node = a.new_atom(b"foobar").unwrap();
+    let mut expected = hash_atom(b"foobar");
+    for _ in 0..64 {
+        node = a.new_pair(node, node).unwrap();
+        expected = hash_pair(&expected, &expected);
+    }
+    assert!(!a.has_tree_hash_cache());
+    assert_eq!(tree_hash(&a, node), expected);
+    assert_eq!(tree_hash_uncached(&a, node), expected);
+}
@@ -1,7 +1,9 @@
 use clap::Parser;
 use clvmr::allocator::{Allocator, NodePtr};
 use clvmr::chia_dialect::{ChiaDialect, ENABLE_BLS_OPS_OUTSIDE_GUARD};
+use clvmr::cost::CostSchedule;
 use clvmr::run_program::run_program;
+use clvmr::to_clvm::ToClvm;
 use linreg::linear_regression_of;
 use std::fs::{create_dir_all, File};
 use std::io::{sink, Write};
@@ -32,28 +34,18 @@ fn build_call(
     num: i32,
     extra: Option<NodePtr>,
 ) -> NodePtr {
-    let mut args = a.nil();
+    let mut args = Vec::<NodePtr>::new();
+    if let Some(extra) = extra {
+        args.push(extra);
+    }
     for _i in 0..num {
         match arg {
-            OpArgs::SingleArg(a1) => {
-                args = a.new_pair(a1, args).unwrap();
-            }
-            OpArgs::TwoArgs(first, second) => {
-                args = a.new_pair(second, args).unwrap();
-                args = a.new_pair(first, args).unwrap();
-            }
-            OpArgs::ThreeArgs(first, second, third) => {
-                args = a.new_pair(third, args).unwrap();
-                args = a.new_pair(second, args).unwrap();
-                args = a.new_pair(first, args).unwrap();
-            }
+            OpArgs::SingleArg(a1) => args.push(a1),
+            OpArgs::TwoArgs(first, second) => args.extend([first, second]),
+            OpArgs::ThreeArgs(first, second, third) => args.extend([first, second, third]),
         }
     }
-    if let Some(extra) = extra {
-        args = a.new_pair(extra, args).unwrap();
-    }
-    let op_code = a.new_number(op.into()).unwrap();
-    a.new_pair(op_code, args).unwrap()
+    (op, args).to_clvm(a).unwrap()
 }
 
 // builds calls in the form:
@@ -281,6 +273,10 @@ struct Operator {
     arg: Placeholder,
     extra: Option<NodePtr>,
     flags: u32,
+    // the names of the costs in the CostSchedule the measurements apply to
+    base_cost: Option<&'static str>,
+    per_arg_cost: Option<&'static str>,
+    per_byte_cost: Option<&'static str>,
 }
 
 /// Measure CPU cost of CLVM operators to aid in determining their cost
@@ -290,6 +286,15 @@ struct Args {
     /// enable plotting of measurements
     #[arg(short, long, default_value_t = false)]
     plot: bool,
+
+    /// write the measured costs to this file, as a cost schedule. Costs that
+    /// aren't measured keep their current value
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// the version of the cost schedule written to the output file
+    #[arg(long, default_value_t = 2)]
+    schedule_version: u32,
 }
 
 fn maybe_open(plot: bool, op: &str, name: &str) -> Box<dyn Write> {
@@ -387,6 +392,9 @@ pub fn main() {
             arg: Placeholder::ThreeArgs(Some(number), Some(number), None),
             extra: None,
             flags: PER_BYTE_COST | EXPONENTIAL_COST,
+            base_cost: None,
+            per_arg_cost: None,
+            per_byte_cost: Some("modpow_cost_per_byte_mod"),
         },
         Operator {
             opcode: 60,
@@ -394,6 +402,9 @@ pub fn main() {
             arg: Placeholder::ThreeArgs(Some(number), None, Some(number)),
             extra: None,
             flags: PER_BYTE_COST | EXPONENTIAL_COST,
+            base_cost: None,
+            per_arg_cost: None,
+            per_byte_cost: Some("modpow_cost_per_byte_exponent"),
         },
         Operator {
             opcode: 60,
@@ -401,6 +412,9 @@ pub fn main() {
             arg: Placeholder::ThreeArgs(None, Some(number), Some(number)),
             extra: None,
             flags: PER_BYTE_COST,
+            base_cost: Some("modpow_base_cost"),
+            per_arg_cost: None,
+            per_byte_cost: Some("modpow_cost_per_byte_base_value"),
         },
         Operator {
             opcode: 29,
@@ -408,6 +422,9 @@ pub fn main() {
             arg: Placeholder::SingleArg(Some(g1)),
             extra: None,
             flags: PER_ARG_COST | NESTING_BASE_COST,
+            base_cost: Some("point_add_base_cost"),
+            per_arg_cost: Some("point_add_cost_per_arg"),
+            per_byte_cost: None,
         },
         Operator {
             opcode: 49,
@@ -415,6 +432,9 @@ pub fn main() {
             arg: Placeholder::SingleArg(Some(g1)),
             extra: None,
             flags: PER_ARG_COST | NESTING_BASE_COST,
+            base_cost: Some("bls_g1_subtract_base_cost"),
+            per_arg_cost: Some("bls_g1_subtract_cost_per_arg"),
+            per_byte_cost: None,
         },
         Operator {
             opcode: 50,
@@ -422,6 +442,9 @@ pub fn main() {
             arg: Placeholder::TwoArgs(Some(g1), None),
             extra: Some(g1),
             flags: PER_BYTE_COST,
+            base_cost: Some("bls_g1_multiply_base_cost"),
+            per_arg_cost: None,
+            per_byte_cost: Some("bls_g1_multiply_cost_per_byte"),
         },
         Operator {
             opcode: 51,
@@ -429,6 +452,9 @@ pub fn main() {
             arg: Placeholder::SingleArg(Some(g1)),
             extra: None,
             flags: 0,
+            base_cost: Some("bls_g1_negate_base_cost"),
+            per_arg_cost: None,
+            per_byte_cost: None,
         },
         Operator {
             opcode: 52,
@@ -436,6 +462,9 @@ pub fn main() {
             arg: Placeholder::SingleArg(Some(g2)),
             extra: None,
             flags: PER_ARG_COST | NESTING_BASE_COST,
+            base_cost: Some("bls_g2_add_base_cost"),
+            per_arg_cost: Some("bls_g2_add_cost_per_arg"),
+            per_byte_cost: None,
         },
         Operator {
             opcode: 53,
@@ -443,6 +472,9 @@ pub fn main() {
             arg: Placeholder::SingleArg(Some(g2)),
             extra: None,
             flags: PER_ARG_COST | NESTING_BASE_COST,
+            base_cost: Some("bls_g2_subtract_base_cost"),
+            per_arg_cost: Some("bls_g2_subtract_cost_per_arg"),
+            per_byte_cost: None,
         },
         Operator {
             opcode: 54,
@@ -450,6 +482,9 @@ pub fn main() {
             arg: Placeholder::TwoArgs(Some(g2), None),
             extra: Some(g2),
             flags: PER_BYTE_COST,
+            base_cost: Some("bls_g2_multiply_base_cost"),
+            per_arg_cost: None,
+            per_byte_cost: Some("bls_g2_multiply_cost_per_byte"),
         },
         Operator {
             opcode: 55,
@@ -457,6 +492,9 @@ pub fn main() {
             arg: Placeholder::SingleArg(Some(g2)),
             extra: None,
             flags: 0,
+            base_cost: Some("bls_g2_negate_base_cost"),
+            per_arg_cost: None,
+            per_byte_cost: None,
         },
         Operator {
             opcode: 56,
@@ -464,6 +502,9 @@ pub fn main() {
             arg: Placeholder::SingleArg(None),
             extra: None,
             flags: PER_BYTE_COST | LARGE_BUFFERS,
+            base_cost: Some("bls_map_to_g1_base_cost"),
+            per_arg_cost: None,
+            per_byte_cost: Some("bls_map_to_g1_cost_per_byte"),
         },
         Operator {
             opcode: 57,
@@ -471,6 +512,9 @@ pub fn main() {
             arg: Placeholder::SingleArg(None),
             extra: None,
             flags: PER_BYTE_COST | LARGE_BUFFERS,
+            base_cost: Some("bls_map_to_g2_base_cost"),
+            per_arg_cost: None,
+            per_byte_cost: Some("bls_map_to_g2_cost_per_byte"),
         },
         Operator {
             opcode: 58,
@@ -478,6 +522,9 @@ pub fn main() {
             arg: Placeholder::TwoArgs(Some(g1), Some(g2)),
             extra: None,
             flags: PER_ARG_COST | ALLOW_FAILURE,
+            base_cost: Some("bls_pairing_base_cost"),
+            per_arg_cost: Some("bls_pairing_cost_per_arg"),
+            per_byte_cost: None,
         },
         Operator {
             opcode: 59,
@@ -485,6 +532,9 @@ pub fn main() {
             arg: Placeholder::TwoArgs(Some(g1), Some(g2)),
             extra: Some(g2),
             flags: PER_ARG_COST | ALLOW_FAILURE,
+            base_cost: None,
+            per_arg_cost: None,
+            per_byte_cost: None,
         },
         Operator {
             opcode: 0x13d61f00,
@@ -492,6 +542,9 @@ pub fn main() {
             arg: Placeholder::ThreeArgs(Some(k1_pk), Some(k1_msg), Some(k1_sig)),
             extra: None,
             flags: ALLOW_FAILURE,
+            base_cost: Some("secp256k1_verify_cost"),
+            per_arg_cost: None,
+            per_byte_cost: None,
         },
         Operator {
             opcode: 0x1c3a8f00,
@@ -499,6 +552,9 @@ pub fn main() {
             arg: Placeholder::ThreeArgs(Some(r1_pk), Some(r1_msg), Some(r1_sig)),
             extra: None,
             flags: ALLOW_FAILURE,
+            base_cost: Some("secp256r1_verify_cost"),
+            per_arg_cost: None,
+            per_byte_cost: None,
         },
     ];
 
@@ -515,6 +571,16 @@ pub fn main() {
     writeln!(gnuplot, "set term png size 1200,600").expect("failed to write");
     writeln!(gnuplot, "set key top right").expect("failed to write");
 
+    let mut schedule = CostSchedule {
+        version: options.schedule_version,
+        ..CostSchedule::DEFAULT
+    };
+    let mut set_cost = |name: Option<&str>, cost: f64| {
+        if let Some(name) = name {
+            *schedule.cost_mut(name).expect("unknown cost") = cost.round() as u64;
+        }
+    };
+
     for op in &ops {
         println!("opcode: {} ({})", op.name, op.opcode);
         let time_per_byte = if (op.flags & PER_BYTE_COST) != 0 {
@@ -522,6 +588,7 @@ pub fn main() {
             let time_per_byte = time_per_byte(&mut a, op, &mut *output);
             println!("   time: per-byte: {time_per_byte:.2}ns");
             println!("   cost: per-byte: {:.0}", time_per_byte * cost_scale);
+            set_cost(op.per_byte_cost, time_per_byte * cost_scale);
             time_per_byte
         } else {
             0.0
@@ -531,6 +598,7 @@ pub fn main() {
             let time_per_arg = time_per_arg(&mut a, op, &mut *output);
             println!("   time: per-arg: {time_per_arg:.2}ns");
             println!("   cost: per-arg: {:.0}", time_per_arg * arg_cost_scale);
+            set_cost(op.per_arg_cost, time_per_arg * arg_cost_scale);
             time_per_arg
         } else {
             0.0
@@ -541,6 +609,7 @@ pub fn main() {
             let base_call_time = base_call_time(&mut a, op, time_per_arg, &mut *output);
             println!("   time: base: {base_call_time:.2}ns");
             println!("   cost: base: {:.0}", base_call_time * base_cost_scale);
+            set_cost(op.base_cost, base_call_time * base_cost_scale);
 
             print_plot(&mut *gnuplot, &base_call_time, &0.0, op.name, "base");
             base_call_time
@@ -548,6 +617,7 @@ pub fn main() {
             let base_call_time = base_call_time_no_nest(&mut a, op, time_per_arg);
             println!("   time: base: {base_call_time:.2}ns");
             println!("   cost: base: {:.0}", base_call_time * base_cost_scale);
+            set_cost(op.base_cost, base_call_time * base_cost_scale);
             base_call_time
         };
 
@@ -577,6 +647,11 @@ pub fn main() {
             );
         }
     }
+    if let Some(output) = options.output {
+        let mut f = File::create(&output).expect("failed to open file");
+        write!(f, "{schedule}").expect("failed to write");
+        println!("wrote cost schedule to {output}");
+    }
     if options.plot {
         println!("To generate plots, run:\n   (cd measurements; gnuplot gen-graphs.gnuplot)");
     }
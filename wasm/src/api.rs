@@ -1,5 +1,5 @@
 use js_sys::Array;
-use std::rc::Rc;
+use std::sync::Arc;
 use wasm_bindgen::prelude::*;
 
 use crate::lazy_node::LazyNode;
@@ -72,7 +72,7 @@ pub fn run_chia_program(
     match r {
         Ok(reduction) => {
             let cost = JsValue::from(reduction.0);
-            let node = LazyNode::new(Rc::new(allocator), reduction.1);
+            let node = LazyNode::new(Arc::new(allocator.freeze()), reduction.1);
             let val = JsValue::from(node);
 
             let tuple = Array::new_with_length(2);
@@ -1,5 +1,5 @@
-use clvmr::allocator::{Allocator, NodePtr, SExp};
-use std::rc::Rc;
+use clvmr::allocator::{FrozenAllocator, NodePtr, SExp};
+use std::sync::Arc;
 
 use js_sys::Array;
 use wasm_bindgen::prelude::*;
@@ -7,7 +7,7 @@ use wasm_bindgen::prelude::*;
 #[wasm_bindgen]
 #[derive(Clone)]
 pub struct LazyNode {
-    allocator: Rc<Allocator>,
+    allocator: Arc<FrozenAllocator>,
     node: NodePtr,
 }
 
@@ -38,7 +38,7 @@ impl LazyNode {
 }
 
 impl LazyNode {
-    pub const fn new(a: Rc<Allocator>, n: NodePtr) -> Self {
+    pub const fn new(a: Arc<FrozenAllocator>, n: NodePtr) -> Self {
         Self {
             allocator: a,
             node: n,
@@ -9,6 +9,9 @@ def deserialize_as_tree(
     blob: bytes, calculate_tree_hashes: bool
 ) -> Tuple[List[Tuple[int, int, int]], Optional[List[bytes]]]: ...
 def serialized_length(blob: bytes) -> int: ...
+def curry(program: bytes, args: List[bytes]) -> bytes: ...
+def uncurry(program: bytes) -> Optional[Tuple[bytes, List[bytes]]]: ...
+def curry_tree_hash(mod_hash: bytes, arg_hashes: List[bytes]) -> bytes: ...
 
 NO_NEG_DIV: int
 NO_UNKNOWN_OPS: int
@@ -4,7 +4,7 @@ from typing import Iterator, List, Tuple, Optional, BinaryIO
 from .at import at
 from .casts import CastableType, to_clvm_object, int_from_bytes, int_to_bytes
 from .chia_dialect import CHIA_DIALECT
-from .clvm_rs import run_serialized_chia_program
+from .clvm_rs import run_serialized_chia_program, curry, uncurry, curry_tree_hash
 from .clvm_storage import CLVMStorage
 from .clvm_tree import CLVMTree
 from .curry_and_treehash import CurryTreehasher
@@ -293,7 +293,8 @@ class Program(CLVMStorage):
 
         Returns a program with the given values bound.
         """
-        return self.to(self.curry_treehasher.curry(self, *args))
+        blob = curry(bytes(self), [bytes(self.to(_)) for _ in args])
+        return self.from_bytes(blob)
 
     def uncurry(self) -> Tuple[Program, Optional[List[Program]]]:
         """
@@ -306,9 +307,11 @@ class Program(CLVMStorage):
         This distinguishes it from the case of a valid curry of 0 arguments
         (which is rather pointless but possible), which returns `self, []`
         """
-        mod, args = self.curry_treehasher.uncurry(self)
-        p_args = args if args is None else [self.to(_) for _ in args]
-        return self.to(mod), p_args
+        r = uncurry(bytes(self))
+        if r is None:
+            return self, None
+        mod, args = r
+        return self.from_bytes(mod), [self.from_bytes(_) for _ in args]
 
     def curry_hash(self, *args: bytes) -> bytes:
         """
@@ -327,11 +330,7 @@ class Program(CLVMStorage):
         hash where you don't actually know the contents of a clvm subtree -- just its
         hash. This lets you calculate the puzzle hash with hidden information.
         """
-        curry_treehasher = self.curry_treehasher
-        quoted_mod_hash = curry_treehasher.calculate_hash_of_quoted_mod_hash(
-            self.tree_hash()
-        )
-        return curry_treehasher.curry_and_treehash(quoted_mod_hash, *args)
+        return curry_tree_hash(self.tree_hash(), list(args))
 
     def as_int(self) -> Optional[int]:
         v = self.atom
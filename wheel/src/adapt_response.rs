@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::lazy_node::LazyNode;
 use clvmr::allocator::Allocator;
@@ -15,12 +15,12 @@ pub fn adapt_response(
 ) -> PyResult<(u64, LazyNode)> {
     match response {
         Ok(reduction) => {
-            let val = LazyNode::new(Rc::new(allocator), reduction.1);
+            let val = LazyNode::new(Arc::new(allocator.freeze()), reduction.1);
             Ok((reduction.0, val))
         }
         Err(eval_err) => {
-            let sexp = LazyNode::new(Rc::new(allocator), eval_err.0).to_object(py);
-            let msg = eval_err.1.to_object(py);
+            let sexp = LazyNode::new(Arc::new(allocator.freeze()), eval_err.0).to_object(py);
+            let msg = eval_err.1.to_string().to_object(py);
             let tuple = PyTuple::new(py, [msg, sexp]);
             let value_error: PyErr = PyValueError::new_err(tuple.to_object(py));
             Err(value_error)
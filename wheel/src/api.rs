@@ -5,10 +5,15 @@ use crate::adapt_response::adapt_response;
 use clvmr::allocator::Allocator;
 use clvmr::chia_dialect::ChiaDialect;
 use clvmr::cost::Cost;
+use clvmr::curry as clvm_curry;
 use clvmr::reduction::Response;
-use clvmr::run_program::run_program;
-use clvmr::serde::{node_from_bytes, parse_triples, serialized_length_from_bytes, ParsedTriple};
+use clvmr::run_program::{run_program_with_options, RunOptions};
+use clvmr::serde::{
+    node_from_bytes, node_to_bytes, parse_triples, serialized_length_from_bytes, ParsedTriple,
+};
+use clvmr::tree_hash::TreeHash;
 use clvmr::{LIMIT_HEAP, MEMPOOL_MODE, NO_UNKNOWN_OPS};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::types::{PyBytes, PyTuple};
 use pyo3::wrap_pyfunction;
@@ -26,18 +31,17 @@ pub fn run_serialized_chia_program(
     max_cost: Cost,
     flags: u32,
 ) -> PyResult<(u64, LazyNode)> {
-    let mut allocator = if flags & LIMIT_HEAP != 0 {
-        Allocator::new_limited(500000000)
-    } else {
-        Allocator::new()
-    };
+    let options = RunOptions::from_flags(flags).with_max_cost(max_cost);
+    let mut allocator = options.new_allocator();
 
     let r: Response = (|| -> PyResult<Response> {
         let program = node_from_bytes(&mut allocator, program)?;
         let args = node_from_bytes(&mut allocator, args)?;
         let dialect = ChiaDialect::new(flags);
 
-        Ok(py.allow_threads(|| run_program(&mut allocator, &dialect, program, args, max_cost)))
+        Ok(py.allow_threads(|| {
+            run_program_with_options(&mut allocator, &dialect, program, args, &options)
+        }))
     })()?;
     adapt_response(py, allocator, r)
 }
@@ -71,11 +75,67 @@ fn deserialize_as_tree(
     Ok((r, s))
 }
 
+#[pyfunction]
+fn curry<'p>(py: Python<'p>, program: &[u8], args: Vec<&[u8]>) -> PyResult<&'p PyBytes> {
+    let mut allocator = Allocator::new();
+    let program = node_from_bytes(&mut allocator, program)?;
+    let args = args
+        .iter()
+        .map(|arg| node_from_bytes(&mut allocator, arg))
+        .collect::<io::Result<Vec<_>>>()?;
+    let curried = clvm_curry::curry(&mut allocator, program, &args)
+        .map_err(|e| PyValueError::new_err(e.1.to_string()))?;
+    Ok(PyBytes::new(py, &node_to_bytes(&allocator, curried)?))
+}
+
+#[pyfunction]
+fn uncurry<'p>(
+    py: Python<'p>,
+    program: &[u8],
+) -> PyResult<Option<(&'p PyBytes, Vec<&'p PyBytes>)>> {
+    let mut allocator = Allocator::new();
+    let program = node_from_bytes(&mut allocator, program)?;
+    let Some((mod_node, args)) = clvm_curry::uncurry(&allocator, program) else {
+        return Ok(None);
+    };
+    let mod_blob = PyBytes::new(py, &node_to_bytes(&allocator, mod_node)?);
+    let args = args
+        .into_iter()
+        .map(|arg| Ok(PyBytes::new(py, &node_to_bytes(&allocator, arg)?)))
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(Some((mod_blob, args)))
+}
+
+fn to_tree_hash(blob: &[u8]) -> PyResult<TreeHash> {
+    blob.try_into()
+        .map_err(|_| PyValueError::new_err("tree hashes must be 32 bytes"))
+}
+
+#[pyfunction]
+fn curry_tree_hash<'p>(
+    py: Python<'p>,
+    mod_hash: &[u8],
+    arg_hashes: Vec<&[u8]>,
+) -> PyResult<&'p PyBytes> {
+    let mod_hash = to_tree_hash(mod_hash)?;
+    let arg_hashes = arg_hashes
+        .into_iter()
+        .map(to_tree_hash)
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(PyBytes::new(
+        py,
+        &clvm_curry::curry_tree_hash(&mod_hash, &arg_hashes),
+    ))
+}
+
 #[pymodule]
 fn clvm_rs(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(run_serialized_chia_program, m)?)?;
     m.add_function(wrap_pyfunction!(serialized_length, m)?)?;
     m.add_function(wrap_pyfunction!(deserialize_as_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(curry, m)?)?;
+    m.add_function(wrap_pyfunction!(uncurry, m)?)?;
+    m.add_function(wrap_pyfunction!(curry_tree_hash, m)?)?;
 
     m.add("NO_UNKNOWN_OPS", NO_UNKNOWN_OPS)?;
     m.add("LIMIT_HEAP", LIMIT_HEAP)?;
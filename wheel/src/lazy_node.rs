@@ -1,13 +1,13 @@
-use clvmr::allocator::{Allocator, NodePtr, SExp};
-use std::rc::Rc;
+use clvmr::allocator::{FrozenAllocator, NodePtr, SExp};
+use std::sync::Arc;
 
 use pyo3::prelude::*;
 use pyo3::types::{PyBytes, PyTuple};
 
-#[pyclass(subclass, unsendable)]
+#[pyclass(subclass)]
 #[derive(Clone)]
 pub struct LazyNode {
-    allocator: Rc<Allocator>,
+    allocator: Arc<FrozenAllocator>,
     node: NodePtr,
 }
 
@@ -44,7 +44,7 @@ impl LazyNode {
 }
 
 impl LazyNode {
-    pub const fn new(a: Rc<Allocator>, n: NodePtr) -> Self {
+    pub const fn new(a: Arc<FrozenAllocator>, n: NodePtr) -> Self {
         Self {
             allocator: a,
             node: n,